@@ -1,169 +1,1617 @@
-use crossterm::event::{Event, KeyEvent, KeyEventKind, read};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
 use std::{
-    env,
-    io::Error,
+    collections::{HashMap, VecDeque},
+    env, fs,
+    io::{Error, ErrorKind},
     panic::{set_hook, take_hook},
+    path::{Path, PathBuf},
+    rc::Rc,
+    time::Duration,
 };
+mod annotatedstring;
+mod bufferlist;
 mod command;
 mod commandbar;
 mod commandparser;
+mod config;
 mod documentstatus;
+mod explorer;
+mod jumplist;
+mod keymap;
 mod line;
+mod macros;
+mod linerange;
 mod messagebar;
 mod mode;
+mod normalparser;
 mod position;
+mod registers;
+mod repeat;
+mod settings;
 mod size;
 mod statusbar;
 mod terminal;
+mod theme;
 mod uicomponent;
 mod view;
+mod windows;
+use annotatedstring::{AnnotatedString, AnnotationType};
+use bufferlist::BufferList;
+use commandparser::SubstituteFlags;
 use line::Line;
+use linerange::{LineContext, LineRange};
+use regex::RegexBuilder;
 use documentstatus::DocumentStatus;
+use explorer::{Explorer, ExplorerCommand};
+use jumplist::{Jump, JumpList};
+use keymap::{Keymap, MapCommand, MapMode, Next};
+use macros::Macros;
 
 use self::command::{
     Command::{self, Edit, Move, System},
     Edit::InsertNewLine,
-    System::{Dismiss, Resize, ShowCommandBar},
+    Direction, Motion, Operator,
+    System::{Dismiss, InsertRegister, Resize, ShowCommandBar},
+    WindowCommand,
 };
 use messagebar::MessageBar;
 use mode::Mode;
+use normalparser::{Key, NormalCommand, NormalParser, ParseResult, VisualCommand};
 use position::Position;
+use registers::{Register, RegisterKind, Registers};
+use repeat::{Change, ChangeCommand, DotRepeat};
+use config::ConfigValue;
+use settings::{Field, OptionInfo, OptionScope, SetAction, Settings, Toggle};
 use size::Size;
 use statusbar::StatusBar;
-use terminal::Terminal;
+use terminal::{CrosstermTerminal, Input, Terminal};
+use theme::Theme;
 use uicomponent::UIComponent;
-use view::View;
+use view::{
+    Buffer, FileType, GlobalMark, LineEnding, SearchDirection, Substitution, SwapFile, View,
+};
+use windows::{Rect, Windows};
 pub const NAME: &str = env!("CARGO_PKG_NAME");
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+// Unsaved changes are written to the swap file once typing pauses for this long
+const SWAP_FLUSH_DELAY: Duration = Duration::from_secs(4);
+// Columns taken by the explorer pane, at most half the screen
+const EXPLORER_WIDTH: usize = 30;
+
+// What the text typed into the command bar is for
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+enum PromptType {
+    #[default]
+    Command,
+    Search(SearchDirection),
+    // A file name or confirmation for a change in the file explorer
+    Explorer(ExplorerCommand),
+}
+
+// A question in the message bar that the next key press answers
+#[derive(Clone, Copy)]
+enum Question {
+    ConfirmSubstitution,
+    Recovery,
+}
 
-#[derive(Default)]
 pub struct Editor {
+    terminal: Box<dyn Terminal>,
     should_quit: bool,
     view: View,
     status_bar: StatusBar,
     message_bar: MessageBar,
     command_bar: Option<commandbar::CommandBar>,
+    prompt_type: PromptType,
     terminal_size: Size,
     title: String,
     quit_times: u8,
     mode: Mode,
+    normal_parser: NormalParser,
+    registers: Registers,
+    awaiting_register: bool,
+    settings: Settings,
+    question: Option<Question>,
+    buffers: BufferList,
+    windows: Windows,
+    explorer: Option<Explorer>,
+    keymap: Keymap,
+    dot_repeat: DotRepeat,
+    macros: Macros,
+    // Events read while a macro played, handled once it is done
+    typeahead: VecDeque<Event>,
+    jump_list: JumpList,
+    global_marks: HashMap<char, GlobalMark>,
 }
 
 impl Editor {
     pub fn new() -> Result<Self, Error> {
         let current_hook = take_hook();
         set_hook(Box::new(move |panic_info| {
-            let _ = Terminal::terminate();
+            SwapFile::flush_all();
+            let _ = CrosstermTerminal.terminate();
             current_hook(panic_info);
         }));
-        Terminal::initialize()?;
+        let mut terminal = CrosstermTerminal;
+        terminal.initialize()?;
+
+        let mut editor = Self::with_terminal(Box::new(terminal));
+        editor.load_config();
+        editor.view.set_window_options(editor.settings.window);
+
+        editor.add_buffer(Buffer::default());
+        let (directories, files): (Vec<String>, Vec<String>) =
+            env::args().skip(1).partition(|name| Path::new(name).is_dir());
+        let opened: Vec<usize> = files
+            .iter()
+            .filter_map(|file_name| editor.open(file_name))
+            .collect();
+        editor.show_buffer(opened.first().copied().unwrap_or(1));
+        if let Some(directory) = directories.first() {
+            editor.explore(directory, false);
+        }
+        editor.refresh_status();
+        editor.status_bar.set_requires_redraw(true);
+        Ok(editor)
+    }
 
-        let mut editor = Self::default();
-        let size = Terminal::size().unwrap_or_default();
+    // An editor on a terminal that is ready to draw on, with the default
+    // settings and no buffers yet
+    fn with_terminal(terminal: Box<dyn Terminal>) -> Self {
+        let size = terminal.size().unwrap_or_default();
+        let mut editor = Self {
+            terminal,
+            should_quit: false,
+            view: View::default(),
+            status_bar: StatusBar::default(),
+            message_bar: MessageBar::default(),
+            command_bar: None,
+            prompt_type: PromptType::default(),
+            terminal_size: Size::default(),
+            title: String::new(),
+            quit_times: 0,
+            mode: Mode::default(),
+            normal_parser: NormalParser::default(),
+            registers: Registers::default(),
+            awaiting_register: false,
+            settings: Settings::default(),
+            question: None,
+            buffers: BufferList::default(),
+            windows: Windows::default(),
+            explorer: None,
+            keymap: Keymap::default(),
+            dot_repeat: DotRepeat::default(),
+            macros: Macros::default(),
+            typeahead: VecDeque::new(),
+            jump_list: JumpList::default(),
+            global_marks: HashMap::new(),
+        };
         editor.resize(size);
+        editor
+    }
 
-        let args: Vec<String> = env::args().collect();
-        if let Some(file_name) = args.get(1) {
-            if editor.view.load(file_name).is_err() {
-                editor
-                    .message_bar
-                    .update_message(&format!("ERR: Could not open file: {file_name}"));
+    pub fn resize(&mut self, size: Size) {
+        self.terminal_size = size;
+        self.arrange_windows();
+
+        self.message_bar.resize(Size {
+            height: 1,
+            width: size.width,
+        });
+
+        if let Some(command_bar) = &mut self.command_bar {
+            command_bar.resize(Size {
+                height: 1,
+                width: size.width,
+            });
+        }
+    }
+
+    // Lays out the explorer pane, if there is one, and the windows right of it.
+    // Together they take all but the last row, which the message bar uses.
+    fn arrange_windows(&mut self) {
+        let pane_width = self.pane_width();
+        self.windows.set_area(Rect {
+            origin: Position {
+                row: 0,
+                col: pane_width,
+            },
+            size: Size {
+                height: self.terminal_size.height.saturating_sub(1),
+                width: self.terminal_size.width.saturating_sub(pane_width),
+            },
+        });
+        self.windows.arrange();
+        let rect = self.windows.active_rect();
+        self.view.resize(rect.text_size());
+        self.status_bar.resize(Size {
+            height: 1,
+            width: rect.size.width,
+        });
+        let explorer_rect = self.explorer_rect();
+        if let Some(explorer) = &mut self.explorer {
+            explorer.resize(explorer_rect.size);
+        }
+    }
+
+    fn pane_width(&self) -> usize {
+        match &self.explorer {
+            Some(explorer) if explorer.is_pane() => {
+                EXPLORER_WIDTH.min(self.terminal_size.width.checked_div(2).unwrap_or(0))
             }
+            _ => 0,
+        }
+    }
+
+    // Where the explorer goes: the pane, or the text area of the active window
+    fn explorer_rect(&self) -> Rect {
+        match &self.explorer {
+            Some(explorer) if explorer.is_pane() => Rect {
+                origin: Position::default(),
+                size: Size {
+                    height: self.terminal_size.height.saturating_sub(1),
+                    width: self.pane_width(),
+                },
+            },
+            _ => {
+                let rect = self.windows.active_rect();
+                Rect {
+                    origin: rect.origin,
+                    size: rect.text_size(),
+                }
+            }
+        }
+    }
+
+    pub fn refresh_status(&mut self) {
+        let status = self.view.get_status();
+        let title = format!("{} - {NAME} [{}]", status.file_name, self.mode.name());
+        self.status_bar.update_status(status);
+
+        if title != self.title && matches!(self.terminal.set_title(&title), Ok(())) {
+            self.title = title;
+        }
+    }
+
+    pub fn run(&mut self) {
+        loop {
+            self.refresh_screen();
+            if self.should_quit {
+                break;
+            }
+            // Keys that may be the start of a mapping wait at most 'timeoutlen'
+            let mapping_time_left = self.keymap.time_left(self.mapping_timeout());
+            let timeout = mapping_time_left.map_or(SWAP_FLUSH_DELAY, |left| left.min(SWAP_FLUSH_DELAY));
+            let input = match self.typeahead.pop_front() {
+                Some(event) => Ok(Input::Event(event)),
+                None => self.terminal.read_event(timeout),
+            };
+            match input {
+                Ok(Input::Event(event)) => {
+                    self.macros.record(&event);
+                    self.evaluate_event(event);
+                }
+                Ok(Input::Timeout) if mapping_time_left.is_some_and(|left| left <= timeout) => {
+                    self.handle_pending_keys(true);
+                }
+                Ok(Input::Timeout) => SwapFile::flush_all(),
+                Ok(Input::End) => break,
+                Err(error) => {
+                    self.message_bar
+                        .update_message(&format!("Could not read event: {error}"));
+                }
+            }
+            if let Some(error) = self.view.take_swap_error() {
+                self.message_bar
+                    .update_message(&format!("Could not create swap file: {error}"));
+            }
+
+            let status = self.view.get_status();
+            self.status_bar.update_status(status);
+        }
+    }
+
+    // needless_pass_by_value: Event is not huge, so there is not a
+    // performance overhead in passing by value, and pattern matching in this
+    // function would be needlessly complicated if we pass by reference here.
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn evaluate_event(&mut self, event: Event) {
+
+        #[cfg(debug_assertions)]
+        if let Event::Key(key_event) = &event {
+            self.message_bar.update_message(&format!(
+                "Key: {:?}, Mods: {:?}, Kind: {:?}, State: {:?}",
+                key_event.code, key_event.modifiers, key_event.kind, key_event.state
+            ));
+        }
+
+        let should_process = match &event {
+            Event::Key(KeyEvent { kind, .. }) => kind == &KeyEventKind::Press,
+            Event::Resize(_, _) => true,
+            _ => false,
+        };
+
+        if !should_process {
+            return;
+        }
+        if let Event::Key(key_event) = event {
+            self.keymap.type_key(key_event);
+            self.handle_pending_keys(false);
+        } else {
+            self.handle_event(event);
+        }
+    }
+
+    fn mapping_timeout(&self) -> Duration {
+        Duration::from_millis(u64::try_from(self.settings.timeout_len).unwrap_or(u64::MAX))
+    }
+
+    // The mappings that apply to the next key, if any
+    fn map_mode(&self) -> Option<MapMode> {
+        if self.question.is_some() || self.awaiting_register {
+            None
+        } else if self.command_bar.is_some() {
+            Some(MapMode::Command)
+        } else if self.mode.is_insert() {
+            Some(MapMode::Insert)
+        } else if !self.normal_parser.accepts_mappings() {
+            None
+        } else if self.mode.is_normal() {
+            Some(MapMode::Normal)
+        } else if self.mode.is_visual() {
+            Some(MapMode::Visual)
+        } else {
+            None
+        }
+    }
+
+    // Handles the keys typed so far, expanding mappings. Keys that may be the
+    // start of a mapping wait for the next key, unless `timed_out`.
+    fn handle_pending_keys(&mut self, timed_out: bool) {
+        loop {
+            match self.keymap.next(self.map_mode(), timed_out) {
+                Next::Key(key_event) => self.handle_event(Event::Key(key_event)),
+                Next::Wait => break,
+                Next::Recursive => {
+                    self.message_bar.update_message("Recursive mapping");
+                    break;
+                }
+            }
+        }
+    }
+
+    #[allow(clippy::needless_pass_by_value)]
+    fn handle_event(&mut self, event: Event) {
+        if self.awaiting_register && matches!(event, Event::Key(_)) {
+            // The key after Ctrl-R names the register to insert; anything else cancels
+            self.awaiting_register = false;
+            if let Event::Key(KeyEvent {
+                code: KeyCode::Char(name),
+                ..
+            }) = event
+            {
+                self.insert_register(name);
+            }
+            return;
+        }
+        if let Some(question) = self.question {
+            if let Event::Key(KeyEvent { code, .. }) = event {
+                match question {
+                    Question::ConfirmSubstitution => self.handle_substitution_key(code),
+                    Question::Recovery => self.handle_recovery_key(code),
+                }
+            } else if let Ok(System(Resize(size))) = Command::try_from(event) {
+                self.resize(size);
+            }
+            return;
+        }
+        if let Event::Key(key_event) = event
+            && self.macros.recording().is_some()
+            && self.command_bar.is_none()
+            && (self.mode.is_normal() || self.mode.is_visual())
+            && !self.normal_parser.is_pending()
+            && Key::try_from(key_event) == Ok(Key::Char('q'))
+        {
+            self.stop_recording();
+            return;
+        }
+        if let Event::Key(key_event) = event
+            && self.command_bar.is_none()
+            && self.mode.is_normal()
+            && self.handle_normal_key(key_event)
+        {
+            return;
+        }
+        if let Event::Key(key_event) = event
+            && self.command_bar.is_none()
+            && self.mode.is_explorer()
+            && self.handle_explorer_key(key_event)
+        {
+            return;
+        }
+        if let Event::Key(key_event) = event
+            && self.command_bar.is_none()
+            && self.mode.is_visual()
+            && self.handle_visual_key(key_event)
+        {
+            return;
+        }
+        if let Ok(command) = Command::try_from(event) {
+            self.process_command(command);
+        }
+    }
+
+    // Feeds a key typed in Normal mode to the Normal mode grammar.
+    // Returns false if the key is not part of it and should be handled as a regular command.
+    fn handle_normal_key(&mut self, key_event: KeyEvent) -> bool {
+        if !self.normal_parser.is_pending() {
+            // ESC and ':' keep their usual meaning unless a command is being typed
+            if matches!(
+                Command::try_from(Event::Key(key_event)),
+                Ok(System(Dismiss | ShowCommandBar))
+            ) {
+                return false;
+            }
+        } else if key_event.code == KeyCode::Esc {
+            self.normal_parser.reset();
+            return true;
+        }
+        let Ok(key) = Key::try_from(key_event) else {
+            self.normal_parser.reset();
+            return false;
+        };
+        if let ParseResult::Complete(command) = self.normal_parser.push(key) {
+            self.reset_quit_times();
+            self.execute_normal_command(command);
+        }
+        true
+    }
+
+    // Feeds a key typed in a Visual mode to the Visual mode grammar.
+    // Returns false if the key should be handled as a regular command.
+    fn handle_visual_key(&mut self, key_event: KeyEvent) -> bool {
+        if !self.normal_parser.is_pending() {
+            match Command::try_from(Event::Key(key_event)) {
+                Ok(System(Dismiss)) => {
+                    self.exit_visual_mode();
+                    return true;
+                }
+                Ok(System(ShowCommandBar)) => {
+                    // Ex commands typed from a selection act on its lines
+                    self.exit_visual_mode();
+                    self.show_prompt(PromptType::Command);
+                    if let Some(command_bar) = &mut self.command_bar {
+                        command_bar.insert_text("'<,'>");
+                    }
+                    return true;
+                }
+                _ => {}
+            }
+        } else if key_event.code == KeyCode::Esc {
+            self.normal_parser.reset();
+            return true;
+        }
+        let Ok(key) = Key::try_from(key_event) else {
+            self.normal_parser.reset();
+            return false;
+        };
+        if let ParseResult::Complete(command) = self.normal_parser.push_visual(key) {
+            self.execute_visual_command(command);
+        }
+        true
+    }
+
+    fn execute_visual_command(&mut self, command: VisualCommand) {
+        match command {
+            VisualCommand::Move { motion, count } => self.move_caret(motion, count),
+            VisualCommand::SwapEnds => self.view.swap_selection_ends(),
+            VisualCommand::Object { object, count } => match self.view.select_object(object, count) {
+                Some(kind) => self.set_mode(Mode::visual(kind)),
+                None => self.macros.abort(),
+            },
+            VisualCommand::Select(kind) => {
+                if self.mode.selection_kind() == Some(kind) {
+                    self.exit_visual_mode();
+                } else {
+                    self.view.set_selection_kind(kind);
+                    self.set_mode(Mode::visual(kind));
+                }
+            }
+            VisualCommand::Operate {
+                operator: Operator::Change,
+                register,
+            } => {
+                self.view.begin_change();
+                if let Some(deleted) = self.view.apply_operator_to_selection(Operator::Change) {
+                    self.registers.delete(register, deleted);
+                }
+                self.set_mode(Mode::Insert);
+            }
+            VisualCommand::Operate { operator, register } => {
+                if let Some(text) = self.view.apply_operator_to_selection(operator) {
+                    self.store_operator_text(operator, register, text);
+                }
+                self.set_mode(Mode::Normal);
+            }
+        }
+        self.refresh_status();
+    }
+
+    fn move_caret(&mut self, motion: Motion, count: Option<usize>) {
+        let from = self.here();
+        // A global mark may be in another file, which a selection can't reach
+        if let Motion::Mark(name) | Motion::MarkLine(name) = motion
+            && name.is_ascii_uppercase()
+            && self.mode.is_normal()
+        {
+            self.show_global_mark(name);
+        }
+        let moved = self.view.handle_motion(motion, count);
+        if !moved {
+            self.macros.abort();
+        }
+        if moved
+            && motion.is_jump()
+            && let Some(from) = from
+        {
+            self.remember_jump(from);
+        }
+        match motion {
+            Motion::NextMatch | Motion::PreviousMatch => match self.view.search_pattern() {
+                None => self.message_bar.update_message("No previous search pattern"),
+                Some(pattern) if !moved => {
+                    let message = format!("Pattern not found: {pattern}");
+                    self.message_bar.update_message(&message);
+                }
+                Some(_) => self.show_search_wrap(),
+            },
+            Motion::Mark(_) | Motion::MarkLine(_) if !moved => {
+                self.message_bar.update_message("Mark not set");
+            }
+            _ => {}
+        }
+    }
+
+    // Where the caret is, as a place to jump back to
+    fn here(&self) -> Option<Jump> {
+        let buffer = self.buffers.number_of(&self.view.buffer())?;
+        Some(Jump {
+            buffer,
+            location: self.view.position().text_location,
+        })
+    }
+
+    // Keeps where a jump was made from in the jump list and in the ' mark
+    fn remember_jump(&mut self, from: Jump) {
+        self.jump_list.push(from);
+        if let Some(entry) = self.buffers.get(from.buffer) {
+            entry.buffer.borrow_mut().set_mark('\'', from.location);
+        }
+    }
+
+    // Ctrl-O and Ctrl-I
+    fn follow_jump_list(&mut self, back: bool, count: usize) {
+        let jump = if back {
+            self.here().and_then(|from| self.jump_list.back(from, count))
+        } else {
+            self.jump_list.forward(count)
+        };
+        let Some(jump) = jump else {
+            self.macros.abort();
+            return;
+        };
+        if self.buffers.number_of(&self.view.buffer()) != Some(jump.buffer) {
+            self.show_buffer(jump.buffer);
+        }
+        self.view.restore_location(jump.location);
+    }
+
+    // m{a-zA-Z}. A global mark (A-Z) is in one file only, so any other buffer
+    // holding it loses it.
+    fn set_mark(&mut self, name: char) {
+        let buffer = self.view.buffer();
+        let location = self.view.position().text_location;
+        if name.is_ascii_uppercase() {
+            let Some(path) = buffer.borrow().file_info.get_path().map(Path::to_path_buf) else {
+                self.message_bar.update_message("No file name");
+                return;
+            };
+            for entry in self.buffers.entries() {
+                entry.buffer.borrow_mut().remove_mark(name);
+            }
+            self.global_marks.insert(name, GlobalMark { path, location });
+        }
+        buffer.borrow_mut().set_mark(name, location);
+    }
+
+    // Shows the file a global mark is in, opening it if need be. A buffer
+    // opened again gets the mark back where it was when it was closed.
+    fn show_global_mark(&mut self, name: char) {
+        let Some(mark) = self.global_marks.get(&name).cloned() else {
+            return;
+        };
+        let Some(number) = self.open(&mark.path.to_string_lossy()) else {
+            return;
+        };
+        if self.buffers.number_of(&self.view.buffer()) != Some(number) {
+            self.show_buffer(number);
+        }
+        let buffer = self.view.buffer();
+        let mut buffer = buffer.borrow_mut();
+        if buffer.mark(name).is_none() {
+            buffer.set_mark(name, mark.location);
+        }
+    }
+
+    fn show_search_wrap(&mut self) {
+        match self.view.search_wrapped() {
+            Some(SearchDirection::Forward) => self
+                .message_bar
+                .update_message("search hit BOTTOM, continuing at TOP"),
+            Some(SearchDirection::Backward) => self
+                .message_bar
+                .update_message("search hit TOP, continuing at BOTTOM"),
+            None => {}
+        }
+    }
+
+    // Jumps to the first match of what has been typed into the search prompt so far
+    fn update_search(&mut self) {
+        let (Some(command_bar), PromptType::Search(direction)) =
+            (&self.command_bar, self.prompt_type)
+        else {
+            return;
+        };
+        let pattern = command_bar.value();
+        let ignore_case = self.settings.search_ignores_case(&pattern);
+        self.view.search(&pattern, direction, ignore_case);
+    }
+
+    fn accept_search(&mut self, direction: SearchDirection, pattern: &str) {
+        // An empty pattern repeats the last search
+        let pattern = if pattern.is_empty() {
+            self.view.search_pattern().unwrap_or_default().to_string()
+        } else {
+            pattern.to_string()
+        };
+        if pattern.is_empty() {
+            self.view.cancel_search();
+            self.message_bar.update_message("No previous search pattern");
+            self.macros.abort();
+            return;
+        }
+        let ignore_case = self.settings.search_ignores_case(&pattern);
+        let found = self.view.search(&pattern, direction, ignore_case);
+        if found {
+            self.message_bar.update_message("");
+            self.show_search_wrap();
+        } else {
+            self.message_bar
+                .update_message(&format!("Pattern not found: {pattern}"));
+            self.macros.abort();
+        }
+        // Finding a match is a jump from where the search started
+        if let Some(location) = self.view.end_search().filter(|_| found)
+            && let Some(from) = self.here()
+        {
+            self.remember_jump(Jump { location, ..from });
+        }
+    }
+
+    fn substitute(
+        &mut self,
+        range: LineRange,
+        pattern: &str,
+        replacement: String,
+        flags: SubstituteFlags,
+    ) {
+        let lines = match range.resolve(&self.line_context()) {
+            Ok(lines) => lines,
+            Err(error) => {
+                self.message_bar.update_message(&error);
+                return;
+            }
+        };
+        // An empty pattern reuses the last search, which is plain text
+        let pattern = if pattern.is_empty() {
+            let Some(search) = self.view.search_pattern() else {
+                self.message_bar.update_message("No previous search pattern");
+                return;
+            };
+            regex::escape(search)
+        } else {
+            pattern.to_string()
+        };
+        let ignore_case = flags
+            .ignore_case
+            .unwrap_or_else(|| self.settings.search_ignores_case(&pattern));
+        let Ok(regex) = RegexBuilder::new(&pattern)
+            .case_insensitive(ignore_case)
+            .build()
+        else {
+            self.message_bar
+                .update_message(&format!("Invalid pattern: {pattern}"));
+            return;
+        };
+        let substitution = Substitution::new(regex, replacement, flags.global);
+        let (first, last) = lines;
+        if !self.view.start_substitution(substitution, first, last) {
+            self.view.finish_substitution();
+            self.message_bar
+                .update_message(&format!("Pattern not found: {pattern}"));
+        } else if flags.confirm {
+            self.question = Some(Question::ConfirmSubstitution);
+            self.message_bar
+                .update_prompt("replace with this? (y/n/a/q/l)");
+        } else {
+            self.view.substitute_all();
+            self.finish_substitution();
+        }
+    }
+
+    // Answers the confirmation prompt of :s with the c flag
+    fn handle_substitution_key(&mut self, code: KeyCode) {
+        let pending = match code {
+            KeyCode::Char('y') => self.view.confirm_substitution(true),
+            KeyCode::Char('n') => self.view.confirm_substitution(false),
+            KeyCode::Char('a') => {
+                self.view.substitute_all();
+                false
+            }
+            KeyCode::Char('l') => {
+                self.view.confirm_substitution(true);
+                false
+            }
+            KeyCode::Char('q') | KeyCode::Esc => false,
+            _ => true,
+        };
+        if !pending {
+            self.finish_substitution();
+        }
+    }
+
+    fn finish_substitution(&mut self) {
+        self.question = None;
+        let (substitutions, lines) = self.view.finish_substitution();
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        self.message_bar.update_message(&format!(
+            "{substitutions} substitution{} on {lines} line{}",
+            plural(substitutions),
+            plural(lines)
+        ));
+        self.refresh_status();
+    }
+
+    // :set, applying each argument in turn
+    fn set_options(&mut self, arguments: &[String]) {
+        for argument in arguments {
+            let result = settings::parse_set(argument)
+                .and_then(|(option, action)| self.apply_option(option, action, false));
+            match result {
+                Ok(Some(message)) => self.message_bar.update_message(&message),
+                Ok(None) => {}
+                Err(error) => {
+                    self.message_bar.update_message(&error);
+                    break;
+                }
+            }
+        }
+    }
+
+    // Shows or changes an option. Buffer and window options are those of the
+    // current buffer and window, or with `defaults`, the ones new buffers and
+    // windows start with. Returns the message to show, if any.
+    fn apply_option(
+        &mut self,
+        option: &OptionInfo,
+        action: SetAction,
+        defaults: bool,
+    ) -> Result<Option<String>, String> {
+        let mut indentation = if defaults { self.settings.indentation } else { self.view.indentation() };
+        let mut window = if defaults { self.settings.window } else { self.view.window_options() };
+        let mut file_format = self.view.line_ending().name().to_string();
+        let mut file_type = self.view.file_type().name().to_string();
+        let mut theme = terminal::theme_name();
+        let field = match (option.scope, option.name) {
+            (OptionScope::Global, "theme") => Some(Field::String(&mut theme)),
+            (OptionScope::Global, name) => self.settings.field(name),
+            // The file format and type belong to one file, so they have no defaults
+            (OptionScope::Buffer, "fileformat") if !defaults => Some(Field::String(&mut file_format)),
+            (OptionScope::Buffer, "filetype") if !defaults => Some(Field::String(&mut file_type)),
+            (OptionScope::Buffer, name) => settings::indentation_field(&mut indentation, name),
+            (OptionScope::Window, name) => settings::window_field(&mut window, name),
+        }
+        .ok_or_else(|| format!("Option {} can only be set for a file", option.name))?;
+        let message = settings::apply(field, option, action)?;
+
+        if theme != terminal::theme_name() {
+            self.set_color_scheme(&theme)?;
+        }
+        let timeout = u64::try_from(self.settings.message_timeout).unwrap_or(u64::MAX);
+        self.message_bar.set_timeout(Duration::from_secs(timeout));
+        if defaults {
+            self.settings.indentation = indentation;
+            self.settings.window = window;
+            return Ok(message);
+        }
+        if file_format != self.view.line_ending().name() {
+            let line_ending = LineEnding::from_name(&file_format)
+                .ok_or_else(|| format!("Invalid argument: fileformat={file_format}"))?;
+            self.view.set_line_ending(line_ending);
+        }
+        if file_type != self.view.file_type().name() {
+            let file_type = FileType::from_name(&file_type)
+                .ok_or_else(|| format!("Unknown filetype: {file_type}"))?;
+            self.view.set_file_type(file_type);
+        }
+        if indentation != self.view.indentation() {
+            self.view.set_indentation(indentation);
+        }
+        self.view.set_window_options(window);
+        self.refresh_status();
+        Ok(message)
+    }
+
+    // Switches to another theme
+    fn set_color_scheme(&mut self, name: &str) -> Result<(), String> {
+        terminal::set_theme(Theme::load(name)?);
+        self.resize(self.terminal_size);
+        Ok(())
+    }
+
+    // Maps keys, or lists the mappings starting with `lhs` without `rhs`
+    fn map_keys(&mut self, command: MapCommand, lhs: &str, rhs: &str) {
+        let leader = &self.settings.map_leader;
+        if rhs.is_empty() {
+            let mappings = self.keymap.list(command.modes, lhs, leader);
+            let message = if mappings.is_empty() {
+                "No mapping found".to_string()
+            } else {
+                mappings.join(" | ")
+            };
+            self.message_bar.update_message(&message);
+        } else if let Err(error) = self.keymap.map(command, lhs, rhs, leader) {
+            self.message_bar.update_message(&error);
+        }
+    }
+
+    // Applies the options in the config file. Problems with it are shown in the
+    // message bar, and the lines that are fine still apply.
+    fn load_config(&mut self) {
+        let (path, text) = match config::load() {
+            None => return,
+            Some(Ok(config)) => config,
+            Some(Err(error)) => {
+                self.message_bar
+                    .update_message(&format!("Could not read the config file: {error}"));
+                return;
+            }
+        };
+        let (entries, mut errors) = config::parse(&text);
+        for entry in entries {
+            let map_command = MapCommand::from_name(&entry.section);
+            let result = if let Some(command) = map_command {
+                match entry.value {
+                    ConfigValue::String(rhs) => {
+                        self.keymap
+                            .map(command, &entry.key, &rhs, &self.settings.map_leader)
+                            .map(|()| None)
+                    }
+                    _ => Err(format!("Mapping for {} must be a string", entry.key)),
+                }
+            } else if entry.section.is_empty() {
+                settings::find_option(&entry.key)
+                    .ok_or_else(|| format!("Unknown option: {}", entry.key))
+                    .and_then(|option| {
+                        let action = match entry.value {
+                            ConfigValue::Bool(true) => SetAction::Toggle(Toggle::On),
+                            ConfigValue::Bool(false) => SetAction::Toggle(Toggle::Off),
+                            ConfigValue::Number(number) => SetAction::Assign(number.to_string()),
+                            ConfigValue::String(text) => SetAction::Assign(text),
+                        };
+                        self.apply_option(option, action, true)
+                    })
+            } else {
+                Err(format!("Unknown section: [{}]", entry.section))
+            };
+            if let Err(error) = result {
+                errors.push((entry.line, error));
+            }
+        }
+        errors.sort_by_key(|(line, _)| *line);
+        if let Some((line, error)) = errors.first() {
+            let more = match errors.len() {
+                1 => String::new(),
+                count => format!(" (and {} more)", count.saturating_sub(1)),
+            };
+            self.message_bar.update_message(&format!(
+                "Error in {} line {line}: {error}{more}",
+                path.display()
+            ));
+        }
+    }
+
+    // Adds a buffer to the buffer list with the default options, returning its number
+    fn add_buffer(&mut self, mut buffer: Buffer) -> usize {
+        buffer.set_indentation(self.settings.indentation);
+        self.buffers.add(buffer)
+    }
+
+    // Adds the file to the buffer list, unless it is already there, and returns
+    // its buffer number
+    fn open(&mut self, file_name: &str) -> Option<usize> {
+        if let Some(number) = self.buffers.find_file(Path::new(file_name)) {
+            return Some(number);
+        }
+        let buffer = match Buffer::load(file_name) {
+            Ok(buffer) => buffer,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                self.message_bar
+                    .update_message(&format!("\"{file_name}\" [New]"));
+                Buffer::new_file(file_name)
+            }
+            Err(error) => {
+                self.message_bar
+                    .update_message(&format!("ERR: Could not open file {file_name}: {error}"));
+                return None;
+            }
+        };
+        Some(self.add_buffer(buffer))
+    }
+
+    fn edit(&mut self, file_name: &str) {
+        if file_name.is_empty() {
+            self.message_bar.update_message("No file name. Use :e <filename>");
+        } else if Path::new(file_name).is_dir() {
+            self.explore(file_name, false);
+        } else if let Some(number) = self.open(file_name) {
+            self.show_buffer(number);
+        }
+    }
+
+    fn show_buffer(&mut self, number: usize) {
+        if self.mode.is_explorer() {
+            self.leave_explorer();
+        }
+        let Some(entry) = self.buffers.select(number, self.view.position()) else {
+            return;
+        };
+        let first_visit = !entry.visited;
+        entry.visited = true;
+        self.view.show_buffer(Rc::clone(&entry.buffer), entry.position);
+        if first_visit {
+            self.offer_recovery();
+        }
+        self.refresh_status();
+    }
+
+    // The buffer a :b or :bd argument refers to: a number, part of a name or,
+    // without an argument, the current buffer
+    fn find_buffer(&mut self, argument: &str) -> Option<usize> {
+        let found = if argument.is_empty() {
+            self.buffers.current().map(|entry| entry.number)
+        } else if let Ok(number) = argument.parse() {
+            self.buffers.get(number).map(|entry| entry.number)
+        } else {
+            self.buffers.find_name(argument)
+        };
+        if found.is_none() {
+            self.message_bar
+                .update_message(&format!("No matching buffer for {argument}"));
+        }
+        found
+    }
+
+    fn delete_buffer(&mut self, argument: &str, force: bool) {
+        let Some(number) = self.find_buffer(argument) else {
+            return;
+        };
+        let Some(entry) = self.buffers.get(number) else {
+            return;
+        };
+        if !force && entry.buffer.borrow().dirty {
+            self.message_bar.update_message(&format!(
+                "No write since last change for buffer {number} (add ! to override)"
+            ));
+            return;
+        }
+        // Its global marks are kept for when the file is opened again
+        let buffer = Rc::clone(&entry.buffer);
+        for (name, mark) in &mut self.global_marks {
+            if let Some(location) = buffer.borrow().mark(*name) {
+                mark.location = location;
+            }
+        }
+        self.jump_list.remove_buffer(number);
+        let was_current = self.buffers.current().map(|entry| entry.number) == Some(number);
+        self.buffers.remove(number);
+        if self.buffers.current().is_none() {
+            self.add_buffer(Buffer::default());
+        }
+        if was_current && let Some(current) = self.buffers.current().map(|entry| entry.number) {
+            self.show_buffer(current);
+        }
+        // Other windows showing the deleted buffer show the current one instead
+        if let Some(entry) = self.buffers.current() {
+            for window in self.windows.inactive_mut() {
+                if self.buffers.number_of(&window.view.buffer()).is_none() {
+                    window.view.show_buffer(Rc::clone(&entry.buffer), entry.position);
+                }
+            }
+        }
+    }
+
+    // Lists the buffers like `1 %a + "main.rs" line 3`: % marks the current
+    // buffer, # the alternate one and + unsaved changes
+    fn list_buffers(&mut self) {
+        let current = self.buffers.current().map(|entry| entry.number);
+        let alternate = self.buffers.alternate();
+        let list: Vec<String> = self
+            .buffers
+            .entries()
+            .map(|entry| {
+                let buffer = entry.buffer.borrow();
+                let (flag, position) = if Some(entry.number) == current {
+                    ("%a", self.view.position())
+                } else if Some(entry.number) == alternate {
+                    ("#", entry.position)
+                } else {
+                    ("", entry.position)
+                };
+                format!(
+                    "{} {flag}{} \"{}\" line {}",
+                    entry.number,
+                    if buffer.dirty { " +" } else { "" },
+                    buffer.file_info,
+                    position.text_location.line_index.saturating_add(1)
+                )
+            })
+            .collect();
+        self.message_bar.update_message(&list.join(" | "));
+    }
+
+    // Saves every buffer with unsaved changes. Returns false if one couldn't be saved.
+    fn save_all(&mut self) -> bool {
+        let backup = self.settings.backup;
+        let mut failed = Vec::new();
+        for entry in self.buffers.modified() {
+            let mut buffer = entry.buffer.borrow_mut();
+            let result = if buffer.is_file_loaded() {
+                buffer.save(backup)
+            } else {
+                Err(Error::other("No file name"))
+            };
+            if let Err(error) = result {
+                failed.push(format!("{} \"{}\": {error}", entry.number, buffer.file_info));
+            }
+        }
+        if failed.is_empty() {
+            self.message_bar.update_message("All files saved.");
+        } else {
+            self.message_bar
+                .update_message(&format!("Error writing buffer {}", failed.join(", ")));
+        }
+        self.refresh_status();
+        failed.is_empty()
+    }
+
+    // Quits unless a buffer has unsaved changes, in which case they are listed
+    fn quit(&mut self) {
+        let unsaved: Vec<String> = self
+            .buffers
+            .modified()
+            .map(|entry| format!("{} \"{}\"", entry.number, entry.buffer.borrow().file_info))
+            .collect();
+        if unsaved.is_empty() {
+            self.should_quit = true;
+        } else {
+            self.message_bar.update_message(&format!(
+                "No write since last change for buffer {} (add ! to override)",
+                unsaved.join(", ")
+            ));
+        }
+    }
+
+    // Closes the window, or quits if it is the last one
+    fn close_or_quit(&mut self, force: bool) {
+        if self.windows.count() > 1 {
+            self.close_window();
+        } else if force {
+            self.should_quit = true;
+        } else {
+            self.quit();
+        }
+    }
+
+    fn close_window(&mut self) {
+        match self.windows.close() {
+            Some(next) => {
+                self.arrange_windows();
+                self.focus_window(next);
+            }
+            None => self.message_bar.update_message("Cannot close last window"),
+        }
+    }
+
+    // Makes another window the active one, along with the buffer it shows
+    fn focus_window(&mut self, id: usize) {
+        let old = self.windows.active();
+        let position = self.view.position();
+        self.windows.focus(id, &mut self.view, &mut self.status_bar);
+        if let Some(window) = self.windows.get(old) {
+            self.view.copy_search_from(&window.view);
+        }
+        let buffer = self.view.buffer();
+        if let Some(number) = self.buffers.number_of(&buffer) {
+            self.buffers.select(number, position);
+        }
+        // Another window may have changed the buffer in the meantime
+        self.view.show_buffer(buffer, self.view.position());
+        self.arrange_windows();
+        self.refresh_status();
+    }
+
+    fn split_window(&mut self, vertical: bool, file_name: &str) {
+        let mut view = View::default();
+        view.show_buffer(self.view.buffer(), self.view.position());
+        view.copy_search_from(&self.view);
+        view.set_window_options(self.view.window_options());
+        match self.windows.split(vertical, view) {
+            Ok(id) => {
+                self.arrange_windows();
+                self.focus_window(id);
+                if !file_name.is_empty() {
+                    self.edit(file_name);
+                }
+            }
+            Err(error) => self.message_bar.update_message(&error),
+        }
+    }
+
+    fn execute_window_command(&mut self, command: WindowCommand) {
+        // From the explorer, Ctrl-W goes back to the windows or closes it
+        if self.mode.is_explorer() {
+            match command {
+                WindowCommand::Close | WindowCommand::Quit => self.close_explorer(),
+                _ => self.leave_explorer(),
+            }
+            return;
+        }
+        match command {
+            WindowCommand::Split { vertical } => self.split_window(vertical, ""),
+            WindowCommand::Focus(direction) => {
+                let caret = self
+                    .view
+                    .caret_position()
+                    .saturating_add(self.windows.active_rect().origin);
+                if let Some(id) = self.windows.neighbour(direction, caret) {
+                    self.focus_window(id);
+                } else if direction == Direction::Left
+                    && self.explorer.as_ref().is_some_and(Explorer::is_pane)
+                {
+                    self.focus_explorer();
+                }
+            }
+            WindowCommand::Next { backwards } => self.focus_window(self.windows.next(backwards)),
+            WindowCommand::Close => self.close_window(),
+            WindowCommand::Quit => self.close_or_quit(false),
+            WindowCommand::Only => {
+                self.windows.only();
+                self.arrange_windows();
+            }
+            WindowCommand::Resize { vertical, amount } => {
+                if self.windows.resize(vertical, amount) {
+                    self.arrange_windows();
+                }
+            }
+            WindowCommand::SetSize { vertical, size } => {
+                if self.windows.set_size(vertical, size) {
+                    self.arrange_windows();
+                }
+            }
+            WindowCommand::Equalize => {
+                self.windows.equalize();
+                self.arrange_windows();
+            }
+        }
+    }
+
+    // Opens the file explorer on a directory: by default the current one in a
+    // pane, or the one of the current file in place of the window. Without a
+    // directory, :Lexplore closes the pane if it is open.
+    fn explore(&mut self, directory: &str, pane: bool) {
+        if pane && directory.is_empty() && self.explorer.as_ref().is_some_and(Explorer::is_pane) {
+            self.close_explorer();
+            return;
+        }
+        let file = self
+            .view
+            .buffer()
+            .borrow()
+            .file_info
+            .get_path()
+            .map(Path::to_path_buf);
+        let directory = if !directory.is_empty() {
+            PathBuf::from(directory)
+        } else if pane {
+            PathBuf::from(".")
+        } else {
+            file.as_deref()
+                .and_then(Path::parent)
+                .filter(|parent| !parent.as_os_str().is_empty())
+                .map_or_else(|| PathBuf::from("."), Path::to_path_buf)
+        };
+        if !directory.is_dir() {
+            self.message_bar
+                .update_message(&format!("Not a directory: {}", directory.display()));
+            return;
+        }
+        let mut explorer = Explorer::new(&directory, pane);
+        if let Some(file) = file.and_then(|file| fs::canonicalize(file).ok()) {
+            explorer.select_path(&file);
         }
-        editor.refresh_status();
-        editor.status_bar.set_requires_redraw(true);
-        Ok(editor)
+        self.explorer = Some(explorer);
+        self.arrange_windows();
+        self.focus_explorer();
     }
 
-    pub fn resize(&mut self, size: Size) {
-        self.terminal_size = size;
-        self.view.resize(Size {
-            height: size.height.saturating_sub(2),
-            width: size.width,
-        });
+    fn focus_explorer(&mut self) {
+        if let Some(explorer) = &mut self.explorer {
+            explorer.set_requires_redraw(true);
+            self.set_mode(Mode::Explorer);
+        }
+    }
 
-        self.status_bar.resize(Size {
-            height: 1,
-            width: size.width,
-        });
+    // Goes back to the windows. An explorer in place of a window is closed.
+    fn leave_explorer(&mut self) {
+        if self.explorer.as_ref().is_some_and(Explorer::is_pane) {
+            self.set_mode(Mode::Normal);
+        } else {
+            self.close_explorer();
+        }
+    }
 
-        self.message_bar.resize(Size {
-            height: 1,
-            width: size.width,
-        });
+    fn close_explorer(&mut self) {
+        self.explorer = None;
+        if self.mode.is_explorer() {
+            self.set_mode(Mode::Normal);
+        }
+        self.arrange_windows();
+        self.view.set_requires_redraw(true);
+    }
 
-        if let Some(command_bar) = &mut self.command_bar {
-            command_bar.resize(Size {
-                height: 1,
-                width: size.width,
-            });
+    // Handles a key typed in the explorer. Returns false if it should be
+    // handled as a regular command, like ':'.
+    fn handle_explorer_key(&mut self, key_event: KeyEvent) -> bool {
+        if matches!(
+            Command::try_from(Event::Key(key_event)),
+            Ok(System(ShowCommandBar))
+        ) {
+            return false;
+        }
+        // Ctrl-W commands lead back to the windows
+        if let Ok(key) = Key::try_from(key_event)
+            && (self.normal_parser.is_pending() || key == Key::Ctrl('w'))
+        {
+            if let ParseResult::Complete(command) = self.normal_parser.push(key) {
+                self.execute_normal_command(command);
+            }
+            return true;
+        }
+        if let Ok(command) = ExplorerCommand::try_from(key_event) {
+            self.execute_explorer_command(command);
         }
+        true
     }
 
-    pub fn refresh_status(&mut self) {
-        let status = self.view.get_status();
-        let mode_str = match self.mode {
-            Mode::Normal => "NORMAL",
-            Mode::Insert => "INSERT",
+    fn execute_explorer_command(&mut self, command: ExplorerCommand) {
+        let Some(explorer) = &mut self.explorer else {
+            return;
         };
+        let selected = explorer
+            .selected()
+            .map(|entry| (entry.path.clone(), entry.is_dir));
+        match command {
+            ExplorerCommand::Open => {
+                if let Some((path, false)) = selected {
+                    self.leave_explorer();
+                    self.edit(&explorer::display_path(&path));
+                } else {
+                    explorer.execute(command);
+                }
+            }
+            ExplorerCommand::Create { .. } => self.show_prompt(PromptType::Explorer(command)),
+            ExplorerCommand::Rename | ExplorerCommand::Delete => {
+                if let Some((path, _)) = selected {
+                    self.show_prompt(PromptType::Explorer(command));
+                    if command == ExplorerCommand::Rename
+                        && let Some(command_bar) = &mut self.command_bar
+                    {
+                        let name = path.file_name().unwrap_or_default().to_string_lossy();
+                        command_bar.insert_text(&name);
+                    }
+                }
+            }
+            ExplorerCommand::Leave => self.leave_explorer(),
+            ExplorerCommand::Close => self.close_explorer(),
+            _ => explorer.execute(command),
+        }
+    }
 
-        let title = format!("{} - {NAME} [{}]", status.file_name, mode_str);
-        self.status_bar.update_status(status);
+    fn explorer_prompt(&self, command: ExplorerCommand) -> String {
+        let selected = self
+            .explorer
+            .as_ref()
+            .and_then(Explorer::selected)
+            .map(|entry| explorer::display_path(&entry.path))
+            .unwrap_or_default();
+        match command {
+            ExplorerCommand::Create { directory: false } => "New file: ".to_string(),
+            ExplorerCommand::Create { directory: true } => "New directory: ".to_string(),
+            ExplorerCommand::Rename => format!("Rename {selected} to: "),
+            _ => format!("Delete {selected}? (y/n): "),
+        }
+    }
 
-        if title != self.title && matches!(Terminal::set_title(&title), Ok(())) {
-            self.title = title;
+    // Creates, renames or deletes a file once its name (or y) has been typed
+    fn accept_explorer_prompt(&mut self, command: ExplorerCommand, input: &str) {
+        let Some(explorer) = &mut self.explorer else {
+            return;
+        };
+        let input = input.trim();
+        let result = match command {
+            _ if input.is_empty() => return,
+            ExplorerCommand::Create { directory } => explorer
+                .create(input, directory)
+                .map(|path| format!("Created {}", explorer::display_path(&path)))
+                .map_err(|error| format!("Could not create {input}: {error}")),
+            ExplorerCommand::Rename => explorer
+                .rename(input)
+                .map(|(from, to)| {
+                    format!(
+                        "Renamed {} to {}",
+                        explorer::display_path(&from),
+                        explorer::display_path(&to)
+                    )
+                })
+                .map_err(|error| format!("Could not rename to {input}: {error}")),
+            ExplorerCommand::Delete if input.eq_ignore_ascii_case("y") => explorer
+                .delete()
+                .map(|path| format!("Deleted {}", explorer::display_path(&path)))
+                .map_err(|error| format!("Could not delete: {error}")),
+            _ => return,
+        };
+        match result {
+            Ok(message) | Err(message) => self.message_bar.update_message(&message),
         }
     }
 
-    pub fn run(&mut self) {
-        loop {
-            self.refresh_screen();
-            if self.should_quit {
-                break;
-            }
-            match read() {
-                Ok(event) => self.evaluate_event(event),
-                Err(err) => {
-                    #[cfg(debug_assertions)]
-                    {
-                        panic!("Could not read event: {err:?}");
+    // Asks what to do about a swap file left behind by a crash (or another instance)
+    fn offer_recovery(&mut self) {
+        let Some(path) = self.view.stale_swap_file() else {
+            return;
+        };
+        let owner = SwapFile::running_owner(&path)
+            .map(|pid| format!(", still in use by process {pid}"))
+            .unwrap_or_default();
+        self.question = Some(Question::Recovery);
+        self.message_bar.update_prompt(&format!(
+            "Found swap file {}{owner}: [r]ecover, [d]elete it, open [o] read-only, [q]uit",
+            path.display()
+        ));
+    }
+
+    fn handle_recovery_key(&mut self, code: KeyCode) {
+        match code {
+            KeyCode::Char('r') => match self.view.recover() {
+                Ok(count) => self.message_bar.update_message(&format!(
+                    "Recovered {count} changes, use :w to keep them"
+                )),
+                Err(error) => self
+                    .message_bar
+                    .update_message(&format!("Could not recover: {error}")),
+            },
+            KeyCode::Char('d') => {
+                let path = self.view.stale_swap_file();
+                // Another editor may still be journaling into it
+                if let Some(pid) = path.as_deref().and_then(SwapFile::running_owner) {
+                    self.message_bar
+                        .update_message(&format!("Swap file is in use by process {pid}"));
+                } else {
+                    if let Some(path) = path {
+                        let _ = fs::remove_file(path);
                     }
+                    self.message_bar.update_message("");
                 }
             }
+            KeyCode::Char('o') | KeyCode::Esc => {
+                self.view.set_read_only(true);
+                self.message_bar.update_message("Opened read-only");
+            }
+            KeyCode::Char('q') => self.should_quit = true,
+            _ => return,
+        }
+        self.question = None;
+        self.refresh_status();
+    }
 
-            let status = self.view.get_status();
-            self.status_bar.update_status(status);
+    fn line_context(&self) -> LineContext {
+        let status = self.view.get_status();
+        LineContext {
+            current: status.current_line_index,
+            count: status.total_lines,
+            selection: self.view.last_selected_lines(),
         }
     }
 
-    // needless_pass_by_value: Event is not huge, so there is not a
-    // performance overhead in passing by value, and pattern matching in this
-    // function would be needlessly complicated if we pass by reference here.
+    fn exit_visual_mode(&mut self) {
+        self.view.end_selection();
+        self.set_mode(Mode::Normal);
+    }
 
-    #[allow(clippy::needless_pass_by_value)]
-    fn evaluate_event(&mut self, event: Event) {
+    // Keeps the text deleted or yanked by an operator in the registers
+    fn store_operator_text(&mut self, operator: Operator, register: Option<char>, text: Register) {
+        match operator {
+            Operator::Delete if !text.text.is_empty() => {
+                self.registers.delete(register, text);
+            }
+            Operator::Yank => {
+                let lines = match text.kind {
+                    RegisterKind::Linewise => text.text.matches('\n').count(),
+                    RegisterKind::Charwise | RegisterKind::Blockwise => 0,
+                };
+                if lines > 1 {
+                    self.message_bar
+                        .update_message(&format!("{lines} lines yanked"));
+                }
+                self.registers.yank(register, text);
+            }
+            _ => {}
+        }
+    }
 
-        #[cfg(debug_assertions)]
-        if let Event::Key(key_event) = &event {
-            self.message_bar.update_message(&format!(
-                "Key: {:?}, Mods: {:?}, Kind: {:?}, State: {:?}",
-                key_event.code, key_event.modifiers, key_event.kind, key_event.state
-            ));
+    fn execute_normal_command(&mut self, command: NormalCommand) {
+        // A change that fails isn't one for . to repeat, like in Vim
+        let mut failed = false;
+        match command {
+            NormalCommand::Move { motion, count } => self.move_caret(motion, count),
+            NormalCommand::Operate {
+                operator: Operator::Change,
+                target,
+                count,
+                register,
+            } => {
+                // The deletion and the text typed afterwards are undone together
+                self.view.begin_change();
+                if let Some(deleted) = self.view.apply_operator(Operator::Change, target, count) {
+                    self.registers.delete(register, deleted);
+                    self.set_mode(Mode::Insert);
+                } else {
+                    self.view.end_change();
+                    self.macros.abort();
+                    failed = true;
+                }
+            }
+            NormalCommand::Operate {
+                operator,
+                target,
+                count,
+                register,
+            } => {
+                if let Some(text) = self.view.apply_operator(operator, target, count) {
+                    self.store_operator_text(operator, register, text);
+                } else {
+                    self.macros.abort();
+                    failed = true;
+                }
+            }
+            NormalCommand::Put {
+                before,
+                count,
+                register,
+            } => {
+                if let Some(content) = self.registers.get(register) {
+                    self.view.put(content, before, count);
+                } else {
+                    self.message_bar.update_message(&format!(
+                        "Nothing in register {}",
+                        register.unwrap_or('"')
+                    ));
+                    self.macros.abort();
+                    failed = true;
+                }
+            }
+            NormalCommand::Insert { position, .. } => {
+                self.enter_insert_mode();
+                self.view.prepare_insert(position);
+            }
+            NormalCommand::Visual(kind) => {
+                self.view.start_selection(kind);
+                self.set_mode(Mode::visual(kind));
+            }
+            NormalCommand::Search(direction) => {
+                self.view.start_search();
+                self.show_prompt(PromptType::Search(direction));
+            }
+            NormalCommand::Undo(count) => {
+                for _ in 0..count {
+                    if !self.undo() {
+                        break;
+                    }
+                }
+            }
+            NormalCommand::Redo(count) => {
+                for _ in 0..count {
+                    if !self.redo() {
+                        break;
+                    }
+                }
+            }
+            NormalCommand::Repeat(count) => self.repeat_change(count),
+            NormalCommand::Record(register) => {
+                self.macros.start_recording(register);
+                self.show_mode();
+            }
+            NormalCommand::Play { register, count } => self.play_macro(register, count),
+            NormalCommand::SetMark(name) => self.set_mark(name),
+            NormalCommand::Jump { back, count } => self.follow_jump_list(back, count),
+            NormalCommand::Window(command) => self.execute_window_command(command),
+        }
+        if !failed && Change::is_change(&command) {
+            // A change that goes on in Insert mode is complete when it is left
+            let change = Change::new(ChangeCommand::Normal(command));
+            if self.mode.is_insert() {
+                self.dot_repeat.begin(change);
+            } else {
+                self.dot_repeat.set_last(change);
+            }
         }
+        self.clamp_caret();
+        self.refresh_status();
+    }
 
-        let should_process = match &event {
-            Event::Key(KeyEvent { kind, .. }) => kind == &KeyEventKind::Press,
-            Event::Resize(_, _) => true,
-            _ => false,
+    // `.`: makes the last change again, taking the count typed before it if any
+    fn repeat_change(&mut self, count: Option<usize>) {
+        let Some(mut change) = self.dot_repeat.last().cloned() else {
+            return;
         };
+        if let Some(count) = count {
+            change.set_count(count);
+        }
+        match &change.command {
+            ChangeCommand::Normal(command) => self.execute_normal_command(*command),
+            ChangeCommand::Ex(input) => self.execute_command(input),
+        }
+        if self.mode.is_insert() {
+            // Leaving Insert mode types the text the other times it goes in
+            for command in &change.typed {
+                self.dot_repeat.record(*command);
+            }
+            self.type_commands(&change.typed);
+            self.leave_insert_mode();
+        }
+        self.dot_repeat.set_last(change);
+    }
 
-        if should_process {
-            if let Ok(command) = Command::try_from(event) {
-                self.process_command(command);
+    // Types commands recorded in Insert mode again
+    fn type_commands(&mut self, commands: &[Command]) {
+        for command in commands {
+            match *command {
+                Edit(edit_command) => self.view.handle_edit_command(edit_command),
+                Move(move_command) => self.view.handle_move_command(move_command),
+                System(_) => {}
             }
         }
     }
 
+    // Normal mode keeps the caret on a grapheme rather than after the last one
+    fn clamp_caret(&mut self) {
+        if self.mode.is_normal() {
+            self.view.clamp_caret();
+        }
+    }
+
     fn process_command(&mut self, command: Command) {
         match command {
             System(Resize(size)) => self.resize(size),
@@ -171,70 +1619,218 @@ impl Editor {
         }
 
         match command {
-            System( Resize(_)) => {}
+            System(Resize(_)) => {}
             System(Dismiss) => {
                 if self.command_bar.is_some() {
+                    if let PromptType::Search(_) = self.prompt_type {
+                        self.view.cancel_search();
+                    }
                     self.dismiss_prompt();
                     self.message_bar.update_message("Command cancelled.");
                 } else if self.mode.is_insert() {
-                    // ESC exits insert mode
-                    self.mode = Mode::Normal;
-                    self.message_bar.update_message("");
-                    self.refresh_status();
+                    self.leave_insert_mode();
                 }
             }
             System(ShowCommandBar) => {
-                self.show_prompt();
+                // ':' is just a character while typing text or a command
+                if let Some(command_bar) = &mut self.command_bar {
+                    command_bar.handle_edit_command(command::Edit::Insert(':'));
+                    self.update_search();
+                } else if self.mode.is_insert() {
+                    self.view.handle_edit_command(command::Edit::Insert(':'));
+                    self.dot_repeat.record(Edit(command::Edit::Insert(':')));
+                } else {
+                    self.show_prompt(PromptType::Command);
+                }
+            }
+            System(InsertRegister) => {
+                if self.command_bar.is_some() || self.mode.is_insert() {
+                    self.awaiting_register = true;
+                }
             }
             Edit(edit_command) => {
                 if let Some(command_bar) = &mut self.command_bar {
                     if matches!(edit_command, InsertNewLine) {
                         let command_input = command_bar.value();
                         self.dismiss_prompt();
-                        self.execute_command(&command_input);
+                        match self.prompt_type {
+                            PromptType::Command => self.execute_command(&command_input),
+                            PromptType::Search(direction) => {
+                                self.accept_search(direction, &command_input);
+                            }
+                            PromptType::Explorer(command) => {
+                                self.accept_explorer_prompt(command, &command_input);
+                            }
+                        }
                     } else {
                         command_bar.handle_edit_command(edit_command);
+                        self.update_search();
                     }
                 } else if self.mode.is_insert() {
                     // Only allow editing in Insert mode
                     self.view.handle_edit_command(edit_command);
-                } else if self.mode.is_normal() {
-                    // In Normal mode, 'i' or 'I' enters Insert mode
-                    if let command::Edit::Insert(ch) = edit_command {
-                        match ch {
-                            '\u{1b}' => { /* Ignore ESC in normal mode */},
-                            'i' => {
-                                self.mode = Mode::Insert;
-                                self.message_bar.update_message("-- INSERT --");
-                                self.refresh_status();
-                            }
-                            'I' => {
-                                self.view.handle_move_command(command::Move::StartOfLine);
-                                self.mode = Mode::Insert;
-                                self.message_bar.update_message("-- INSERT --");
-                                self.refresh_status();
-                            },
-                        _ => {},
-                            
-                        }
-                    }
+                    self.dot_repeat.record(command);
                 }
             }
             Move(move_command) => {
                 if self.command_bar.is_none() {
                     self.view.handle_move_command(move_command);
+                    if self.mode.is_insert() {
+                        self.dot_repeat.record(command);
+                    }
+                }
+            }
+        }
+        self.clamp_caret();
+    }
+
+    // Inserts a register's content for Ctrl-R, into the command bar if it is open
+    fn insert_register(&mut self, name: char) {
+        let Some(register) = self.registers.get(Some(name)) else {
+            return;
+        };
+        if let Some(command_bar) = &mut self.command_bar {
+            command_bar.insert_text(&register.text);
+            self.update_search();
+        } else if self.mode.is_insert() {
+            self.view.insert_text(&register.text);
+            // `.` types the text again
+            for character in register.text.chars() {
+                self.dot_repeat.record(Edit(if character == '\n' {
+                    InsertNewLine
+                } else {
+                    command::Edit::Insert(character)
+                }));
+            }
+        }
+    }
+
+    // ESC in Insert mode, which types the text again as many times as the
+    // count asks, and ends the undo step and the change `.` repeats
+    fn leave_insert_mode(&mut self) {
+        let repeated = self
+            .dot_repeat
+            .pending()
+            .map(Change::repeated_commands)
+            .unwrap_or_default();
+        self.type_commands(&repeated);
+        self.view.end_change();
+        self.dot_repeat.finish();
+        self.view.leave_insert();
+        self.set_mode(Mode::Normal);
+    }
+
+    fn enter_insert_mode(&mut self) {
+        // Everything typed until ESC is undone as a single step
+        self.view.begin_change();
+        self.set_mode(Mode::Insert);
+    }
+
+    fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+        self.clamp_caret();
+        self.show_mode();
+        self.refresh_status();
+    }
+
+    // Shows the mode in the message bar, followed by the register keys are
+    // being recorded into
+    fn show_mode(&mut self) {
+        let mut message = match self.mode {
+            Mode::Normal => String::new(),
+            mode => format!("-- {} --", mode.name()),
+        };
+        if let Some(register) = self.macros.recording() {
+            message.push_str("recording @");
+            message.push(register);
+        }
+        self.message_bar.update_message(&message);
+    }
+
+    // q while recording: the keys typed go in the register
+    fn stop_recording(&mut self) {
+        if let Some((register, keys)) = self.macros.stop_recording() {
+            self.registers.record(register, keys);
+        }
+        self.show_mode();
+    }
+
+    // @{reg}: types the keys in a register `count` times, as if typed. A macro
+    // played from another is typed by the first one's loop.
+    fn play_macro(&mut self, register: Option<char>, count: usize) {
+        let Some(name) = register.or(self.macros.last_played()) else {
+            self.message_bar.update_message("No previously used register");
+            return;
+        };
+        let Some(text) = self.registers.get(Some(name)).map(|register| register.text.clone()) else {
+            self.message_bar
+                .update_message(&format!("Nothing in register {name}"));
+            self.macros.abort();
+            return;
+        };
+        if !self.macros.queue(name, &text, &self.settings.map_leader, count) {
+            self.message_bar.update_message("Recursive macro");
+            return;
+        }
+        if self.macros.begin_playing() {
+            while let Some(key_event) = self.macros.next_key() {
+                if self.interrupted() {
+                    self.message_bar.update_message("Interrupted");
+                    self.macros.abort();
+                } else {
+                    self.evaluate_event(Event::Key(key_event));
                 }
             }
         }
     }
+
+    // Whether Ctrl-C was typed while a macro plays. Other events read meanwhile
+    // wait in the typeahead until it is done.
+    fn interrupted(&mut self) -> bool {
+        while let Ok(Input::Event(event)) = self.terminal.read_event(Duration::ZERO) {
+            if let Event::Key(key_event) = event
+                && key_event.code == KeyCode::Char('c')
+                && key_event.modifiers.contains(KeyModifiers::CONTROL)
+            {
+                return true;
+            }
+            self.typeahead.push_back(event);
+        }
+        false
+    }
+
+    // Returns false if there was nothing to undo
+    fn undo(&mut self) -> bool {
+        let undone = self.view.undo();
+        if !undone {
+            self.message_bar.update_message("Already at oldest change");
+        }
+        undone
+    }
+
+    // Returns false if there was nothing to redo
+    fn redo(&mut self) -> bool {
+        let redone = self.view.redo();
+        if !redone {
+            self.message_bar.update_message("Already at newest change");
+        }
+        redone
+    }
+
     fn dismiss_prompt(&mut self) {
         self.command_bar = None;
         self.message_bar.set_requires_redraw(true);
     }
 
-    fn show_prompt(&mut self) {
+    fn show_prompt(&mut self, prompt_type: PromptType) {
         let mut command_bar = commandbar::CommandBar::default();
-        command_bar.set_prompt(":");
+        command_bar.set_prompt(&match prompt_type {
+            PromptType::Command => ":".to_string(),
+            PromptType::Search(SearchDirection::Forward) => "/".to_string(),
+            PromptType::Search(SearchDirection::Backward) => "?".to_string(),
+            PromptType::Explorer(command) => self.explorer_prompt(command),
+        });
+        self.prompt_type = prompt_type;
         command_bar.resize(Size {
             height: 1,
             width: self.terminal_size.width,
@@ -244,19 +1840,21 @@ impl Editor {
     }
 
     fn save(&mut self, file_name: Option<&str>) -> Result<(), std::io::Error> {
+        let backup = self.settings.backup;
         let result = if let Some(name) = file_name {
-            self.view.save_as(name)
+            self.view.save_as(name, backup)
         } else {
-            self.view.save()
+            self.view.save(backup)
         };
 
         match &result {
-            Ok(_) => {
+            Ok(()) => {
                 self.message_bar.update_message("File saved successfully.");
                 self.refresh_status(); // Refresh to update modified status
             }
-            Err(_) => {
-                self.message_bar.update_message("Error writing file!");
+            Err(error) => {
+                self.message_bar
+                    .update_message(&format!("Error writing file: {error}"));
             }
         }
         result
@@ -274,21 +1872,33 @@ impl Editor {
             return;
         }
         let bottom_bar_row = self.terminal_size.height.saturating_sub(1);
-        let _ = Terminal::hide_caret();
+        let _ = self.terminal.hide_caret();
 
         if let Some(command_bar) = &mut self.command_bar {
-            command_bar.render(bottom_bar_row);
+            command_bar.render(&mut *self.terminal, Position {
+                row: bottom_bar_row,
+                col: 0,
+            });
         } else {
-            self.message_bar.render(bottom_bar_row);
+            self.message_bar.render(&mut *self.terminal, Position {
+                row: bottom_bar_row,
+                col: 0,
+            });
         }
 
+        let explorer_rect = self.explorer_rect();
         if self.terminal_size.height > 1 {
-            self.status_bar
-                .render(self.terminal_size.height.saturating_sub(2));
-        }
-
-        if self.terminal_size.height > 2 {
-            self.view.render(0);
+            if let Some(explorer) = &mut self.explorer
+                && !explorer.is_pane()
+                && self.view.requires_redraw()
+            {
+                // The explorer is drawn over the text of the window it replaces
+                explorer.set_requires_redraw(true);
+            }
+            self.windows.render(&mut *self.terminal, &mut self.view, &mut self.status_bar);
+            if let Some(explorer) = &mut self.explorer {
+                explorer.render(&mut *self.terminal, explorer_rect.origin);
+            }
         }
 
         let new_carret_position = if let Some(command_bar) = &self.command_bar {
@@ -296,12 +1906,30 @@ impl Editor {
                 row: bottom_bar_row,
                 col: command_bar.caret_position_col(),
             }
+        } else if let Some(explorer) = &self.explorer
+            && self.mode.is_explorer()
+        {
+            explorer.caret_position().saturating_add(explorer_rect.origin)
         } else {
-            self.view.caret_position()
+            self.view
+                .caret_position()
+                .saturating_add(self.windows.active_rect().origin)
         };
-        let _ = Terminal::move_caret_to(new_carret_position);
-        let _ = Terminal::show_caret();
-        let _ = Terminal::execute();
+        let _ = self.terminal.move_caret_to(new_carret_position);
+        let _ = self.terminal.show_caret();
+        let _ = self.terminal.execute();
+    }
+
+    // :d, :y, :> and :< over a range of lines
+    fn operate_on_lines(&mut self, range: LineRange, operator: Operator) {
+        match range.resolve(&self.line_context()) {
+            Ok((first, last)) => {
+                let text = self.view.apply_operator_to_lines(operator, first, last);
+                self.store_operator_text(operator, None, text);
+                self.refresh_status();
+            }
+            Err(error) => self.message_bar.update_message(&error),
+        }
     }
 
     fn execute_command(&mut self, input: &str) {
@@ -321,21 +1949,16 @@ impl Editor {
             ParsedCommand::WriteAs(filename) => {
                 let _ = self.save(Some(&filename));
             }
-            ParsedCommand::Quit => {
-                if self.view.get_status().is_modified {
-                    self.message_bar
-                        .update_message("No write since last change. Use :q! to force quit.");
-                } else {
-                    self.should_quit = true;
-                }
-            }
-            ParsedCommand::ForceQuit => {
+            ParsedCommand::Quit => self.close_or_quit(false),
+            ParsedCommand::ForceQuit => self.close_or_quit(true),
+            ParsedCommand::QuitAll => self.quit(),
+            ParsedCommand::ForceQuitAll => {
                 self.should_quit = true;
             }
             ParsedCommand::WriteQuit => {
                 if self.view.is_file_loaded() {
                     if self.save(None).is_ok() {
-                        self.should_quit = true;
+                        self.close_or_quit(false);
                     }
                 } else {
                     self.message_bar
@@ -344,19 +1967,68 @@ impl Editor {
             }
             ParsedCommand::WriteAsAndQuit(filename) => {
                 if self.save(Some(&filename)).is_ok() {
+                    self.close_or_quit(false);
+                }
+            }
+            ParsedCommand::WriteAll => {
+                self.save_all();
+            }
+            ParsedCommand::WriteAllQuit => {
+                if self.save_all() {
                     self.should_quit = true;
                 }
             }
-             ParsedCommand::Unknown(cmd) => {
+            ParsedCommand::Edit(file_name) => self.edit(&file_name),
+            ParsedCommand::NextBuffer | ParsedCommand::PreviousBuffer => {
+                let backwards = command == ParsedCommand::PreviousBuffer;
+                if let Some(number) = self.buffers.neighbour(1, backwards) {
+                    self.show_buffer(number);
+                }
+            }
+            ParsedCommand::Buffer(argument) => {
+                if let Some(number) = self.find_buffer(&argument) {
+                    self.show_buffer(number);
+                }
+            }
+            ParsedCommand::ListBuffers => self.list_buffers(),
+            ParsedCommand::DeleteBuffer { buffer, force } => self.delete_buffer(&buffer, force),
+            ParsedCommand::Split {
+                vertical,
+                file_name,
+            } => self.split_window(vertical, &file_name),
+            ParsedCommand::Window(command) => self.execute_window_command(command),
+            ParsedCommand::Explore { pane, directory } => self.explore(&directory, pane),
+            ParsedCommand::Unknown(cmd) => {
                 if cmd.is_empty() {
                     self.message_bar.update_message("");
                 } else {
                     self.message_bar
-                        .update_message(&format!("Unknown command: {}", cmd));
+                        .update_message(&format!("Unknown command: {cmd}"));
+                }
+            }
+            ParsedCommand::Operate(range, operator) => self.operate_on_lines(range, operator),
+            ParsedCommand::Goto(range) => match range.resolve(&self.line_context()) {
+                Ok((_, last)) => self.move_caret(Motion::FileStart, Some(last.saturating_add(1))),
+                Err(error) => self.message_bar.update_message(&error),
+            },
+            ParsedCommand::Substitute { range, pattern, replacement, flags } => {
+                // `.` runs it again as it was typed
+                self.dot_repeat.set_last(Change::new(ChangeCommand::Ex(input.to_string())));
+                self.substitute(range, &pattern, replacement, flags);
+            }
+            ParsedCommand::Set(arguments) => self.set_options(&arguments),
+            ParsedCommand::NoHighlight => self.view.clear_search_highlight(),
+            ParsedCommand::Map { command, lhs, rhs } => self.map_keys(command, &lhs, &rhs),
+            ParsedCommand::Colorscheme(name) if name.is_empty() => {
+                self.message_bar.update_message(&terminal::theme_name());
+            }
+            ParsedCommand::Colorscheme(name) => {
+                if let Err(error) = self.set_color_scheme(&name) {
+                    self.message_bar.update_message(&error);
                 }
             }
             ParsedCommand::Help =>{
-                let help_message = "Commands: :w (write), :w <filename> (write as), :q (quit), :q! (force quit), :wq (write and quit), :wq <filename> (write as and quit), :help (this message)";
+                let help_message = "Commands: :w (write), :w <filename> (write as), :q (quit), :q! (force quit), :wq (write and quit), :wq <filename> (write as and quit), :e <filename> (edit), :ls (buffers), :bn/:bp/:b N (switch buffer), :bd (close buffer), :sp/:vs (split window), :clo/:on (close windows), :Lex/:E (file explorer), :colo <name> (theme), :help (this message)";
                 self.message_bar.update_message(help_message);
             }
         }
@@ -365,9 +2037,288 @@ impl Editor {
 
 impl Drop for Editor {
     fn drop(&mut self) {
-        let _ = Terminal::terminate();
+        let _ = self.terminal.terminate();
         if self.should_quit {
-            let _ = Terminal::print("Goodbye.\r\n");
+            let _ = self.terminal.print("Goodbye.\r\n");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env::temp_dir, process};
+    use terminal::HeadlessTerminal;
+
+    // An editor on a headless terminal, editing a file (or an empty buffer)
+    fn start(size: Size, file_name: Option<&str>) -> (Editor, HeadlessTerminal) {
+        let terminal = HeadlessTerminal::new(size);
+        let mut editor = Editor::with_terminal(Box::new(terminal.clone()));
+        editor.add_buffer(Buffer::default());
+        if let Some(file_name) = file_name {
+            editor.edit(file_name);
+        } else {
+            editor.show_buffer(1);
         }
+        (editor, terminal)
+    }
+
+    // Types keys written as in mappings, running the editor until they run out
+    fn type_keys(editor: &mut Editor, terminal: &HeadlessTerminal, keys: &str) {
+        let events = keymap::parse_keys(keys, "\\")
+            .into_iter()
+            .map(|key| Event::Key(key.to_event()));
+        terminal.push_events(events);
+        editor.run();
+    }
+
+    #[test]
+    fn test_edit_and_save() {
+        let path = temp_dir().join(format!("amoxcalli-editor-{}.txt", process::id()));
+        fs::write(&path, "one\ntwo\n").unwrap();
+        let size = Size {
+            height: 6,
+            width: 80,
+        };
+        let (mut editor, terminal) = start(size, path.to_str());
+        type_keys(&mut editor, &terminal, "jAx<Esc>ggOzero<Esc>");
+        assert_eq!(terminal.row(0), "zero");
+        assert_eq!(terminal.row(2), "twox");
+        assert_eq!(terminal.row(3), "~");
+        assert_eq!(terminal.caret(), Some(Position { col: 3, row: 0 }));
+        assert!(terminal.row(4).contains("3 lines (modified)"));
+        assert!(terminal.title().contains("amoxcalli-editor-"));
+
+        type_keys(&mut editor, &terminal, ":w<CR>");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "zero\none\ntwox\n");
+        type_keys(&mut editor, &terminal, "dd:q<CR>");
+        assert!(terminal.row(5).contains("No write since last change"));
+        type_keys(&mut editor, &terminal, ":q!<CR>");
+        assert!(editor.should_quit);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "zero\none\ntwox\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_caret_stays_on_text() {
+        let size = Size {
+            height: 8,
+            width: 30,
+        };
+        let (mut editor, terminal) = start(size, None);
+        let caret_col = || terminal.caret().map(|caret| caret.col);
+        // $ and ESC leave the caret on the last grapheme, and l can't go past it
+        type_keys(&mut editor, &terminal, "iabc<Esc>");
+        assert_eq!(caret_col(), Some(2));
+        type_keys(&mut editor, &terminal, "0$l$x");
+        assert_eq!(terminal.row(0), "ab");
+        assert_eq!(caret_col(), Some(1));
+        type_keys(&mut editor, &terminal, "$dw");
+        assert_eq!(terminal.row(0), "a");
+        type_keys(&mut editor, &terminal, "Sone two three<Esc>$d^");
+        assert_eq!(terminal.row(0), "e");
+        type_keys(&mut editor, &terminal, "Sabc<Esc>$i!<Esc>");
+        assert_eq!(terminal.row(0), "ab!c");
+        // c$ types at the end of the line, where Normal mode's caret can't be
+        type_keys(&mut editor, &terminal, "Sone two<Esc>0wc$three<Esc>");
+        assert_eq!(terminal.row(0), "one three");
+        assert_eq!(caret_col(), Some(8));
+
+        // A count types the inserted text that many times
+        type_keys(&mut editor, &terminal, "S<Esc>3ifoo<Esc>");
+        assert_eq!(terminal.row(0), "foofoofoo");
+        assert_eq!(caret_col(), Some(8));
+        type_keys(&mut editor, &terminal, "3ox<Esc>");
+        let rows = (0..5).map(|row| terminal.row(row)).collect::<Vec<_>>();
+        assert_eq!(rows, ["foofoofoo", "x", "x", "x", "~"]);
+        type_keys(&mut editor, &terminal, "u");
+        assert_eq!(terminal.row(1), "~");
+    }
+
+    #[test]
+    fn test_dot_repeat() {
+        let size = Size {
+            height: 12,
+            width: 30,
+        };
+        let (mut editor, terminal) = start(size, None);
+        let rows = |count| (0..count).map(|row| terminal.row(row)).collect::<Vec<_>>();
+        type_keys(&mut editor, &terminal, "Afoo <Esc>..oa<Esc>3.");
+        assert_eq!(rows(6), ["foo foo foo", "a", "a", "a", "a", "~"]);
+        type_keys(&mut editor, &terminal, ":s/a/b/<CR>k.ggdw.");
+        assert_eq!(rows(6), ["foo", "a", "a", "b", "b", "~"]);
+        type_keys(&mut editor, &terminal, "jyyp.");
+        assert_eq!(rows(8), ["foo", "a", "a", "a", "a", "b", "b", "~"]);
+        // Each repeat is undone on its own
+        type_keys(&mut editor, &terminal, "u");
+        assert_eq!(rows(7), ["foo", "a", "a", "a", "b", "b", "~"]);
+        // Operators and puts that fail leave the last change as it was
+        type_keys(&mut editor, &terminal, "ggxdfz.");
+        assert_eq!(terminal.row(0), "o");
+        type_keys(&mut editor, &terminal, "Sabc<Esc>0x\"zp.");
+        assert_eq!(terminal.row(0), "c");
+    }
+
+    #[test]
+    fn test_substitute() {
+        let size = Size {
+            height: 10,
+            width: 40,
+        };
+        let (mut editor, terminal) = start(size, None);
+        let rows = |count| (0..count).map(|row| terminal.row(row)).collect::<Vec<_>>();
+        // Empty matches are stepped over, and the whole :s is one undo step
+        type_keys(&mut editor, &terminal, "ione two<Esc>:%s/x*/-/g<CR>");
+        assert_eq!(rows(2), ["-o-n-e- -t-w-o-", "~"]);
+        assert_eq!(terminal.row(9), "8 substitutions on 1 line");
+        type_keys(&mut editor, &terminal, "u");
+        assert_eq!(rows(2), ["one two", "~"]);
+
+        // Line breaks in the replacement split the line, which still counts as one
+        type_keys(&mut editor, &terminal, "ccbanana bar<Esc>:s/a/\\r/g<CR>");
+        assert_eq!(rows(6), ["b", "n", "n", " b", "r", "~"]);
+        assert_eq!(terminal.row(9), "4 substitutions on 1 line");
+        type_keys(&mut editor, &terminal, "u");
+        assert_eq!(rows(2), ["banana bar", "~"]);
+
+        // Confirming each match: yes, no, then quit
+        type_keys(&mut editor, &terminal, "cca a<CR>a a<CR>a a<Esc>ggVj:s/a/b/gc<CR>");
+        assert_eq!(terminal.row(9), "replace with this? (y/n/a/q/l)");
+        type_keys(&mut editor, &terminal, "ynq");
+        assert_eq!(rows(3), ["b a", "a a", "a a"]);
+        assert_eq!(terminal.row(9), "1 substitution on 1 line");
+        type_keys(&mut editor, &terminal, "u");
+        assert_eq!(rows(3), ["a a", "a a", "a a"]);
+        // ...no, then this one and stop
+        type_keys(&mut editor, &terminal, ":%s/a/c/gc<CR>nl");
+        assert_eq!(rows(3), ["a c", "a a", "a a"]);
+        assert_eq!(terminal.row(9), "1 substitution on 1 line");
+        // ...and no, then all the rest, one a line without g
+        type_keys(&mut editor, &terminal, ":%s/a/d/c<CR>na");
+        assert_eq!(rows(3), ["a c", "d a", "d a"]);
+        assert_eq!(terminal.row(9), "2 substitutions on 2 lines");
+
+        type_keys(&mut editor, &terminal, ":s/x/y/<CR>");
+        assert_eq!(terminal.row(9), "Pattern not found: x");
+    }
+
+    #[test]
+    fn test_macros() {
+        let size = Size {
+            height: 10,
+            width: 30,
+        };
+        let (mut editor, terminal) = start(size, None);
+        let rows = |count| (0..count).map(|row| terminal.row(row)).collect::<Vec<_>>();
+        type_keys(&mut editor, &terminal, "ione<CR>two<CR>three<CR>four<Esc>ggqa");
+        assert_eq!(terminal.row(9), "recording @a");
+        // The last play fails to move down, which stops the other four
+        type_keys(&mut editor, &terminal, "A!<Esc>jq@a@@5@a");
+        assert_eq!(rows(5), ["one!", "two!", "three!", "four!", "~"]);
+
+        // The keys are text in the register, and can be edited as such
+        type_keys(&mut editor, &terminal, "Go<Esc>\"ap");
+        assert_eq!(terminal.row(4), "A!<Esc>j");
+        type_keys(&mut editor, &terminal, "0lxi?<Esc>0\"ay$ddgg@a");
+        assert_eq!(rows(5), ["one!?", "two!", "three!", "four!", "~"]);
+
+        // So does a search that finds nothing
+        type_keys(&mut editor, &terminal, "qb/hree<CR>xq@b");
+        assert_eq!(rows(4), ["one!?", "two!", "tree!", "four!"]);
+        // ...and a put from an empty register
+        type_keys(&mut editor, &terminal, "qcx\"zpxq@c");
+        assert_eq!(rows(4), ["one!?", "two!", "t!", "four!"]);
+
+        // A macro that plays itself stops after a while
+        type_keys(&mut editor, &terminal, "ggdGqcia<Esc>@cq@c");
+        assert_eq!(terminal.row(0), "a".repeat(30));
+        assert_eq!(terminal.row(9), "Recursive macro");
+        // Ctrl-C stops one, and the keys typed after it are not lost
+        type_keys(&mut editor, &terminal, "ddqdAb<Esc>q@d<C-c>Ac<Esc>");
+        assert_eq!(rows(2), ["bc", "~"]);
+    }
+
+    #[test]
+    fn test_marks_and_jumps() {
+        let first = temp_dir().join(format!("amoxcalli-marks-{}.txt", process::id()));
+        let second = temp_dir().join(format!("amoxcalli-marks-{}-2.txt", process::id()));
+        fs::write(&first, "one\ntwo\nthree\nfour\nfive\n").unwrap();
+        fs::write(&second, "other\n").unwrap();
+        let size = Size {
+            height: 10,
+            width: 40,
+        };
+        let (mut editor, terminal) = start(size, first.to_str());
+        let caret_row = || terminal.caret().map(|caret| caret.row);
+        // The mark stays on its line as one is inserted above it
+        type_keys(&mut editor, &terminal, "jjmxggOzero<Esc>'x");
+        assert_eq!(caret_row(), Some(3));
+        type_keys(&mut editor, &terminal, "<C-o>");
+        assert_eq!(caret_row(), Some(0));
+        type_keys(&mut editor, &terminal, "<Tab>");
+        assert_eq!(caret_row(), Some(3));
+        // ...and goes away with it
+        type_keys(&mut editor, &terminal, "ddk'x");
+        assert_eq!(caret_row(), Some(2));
+        assert_eq!(terminal.row(9), "Mark not set");
+        // ...until that is undone
+        type_keys(&mut editor, &terminal, "ugg'x");
+        assert_eq!(caret_row(), Some(3));
+        type_keys(&mut editor, &terminal, "<C-r>");
+        type_keys(&mut editor, &terminal, "GmyggddG'y");
+        assert_eq!(terminal.row(3), "five");
+        assert_eq!(caret_row(), Some(3));
+        type_keys(&mut editor, &terminal, "gg/fo<CR>''");
+        assert_eq!(caret_row(), Some(0));
+        type_keys(&mut editor, &terminal, "<C-o>");
+        assert_eq!(caret_row(), Some(2));
+
+        // A global mark opens its file
+        let command = format!("jmA:e {}<CR>'A", second.display());
+        type_keys(&mut editor, &terminal, &command);
+        assert_eq!(terminal.row(0), "one");
+        assert_eq!(caret_row(), Some(3));
+        type_keys(&mut editor, &terminal, "<C-o>");
+        assert_eq!(terminal.row(0), "other");
+        fs::remove_file(&first).unwrap();
+        fs::remove_file(&second).unwrap();
+    }
+
+    #[test]
+    fn test_render_windows() {
+        let size = Size {
+            height: 8,
+            width: 25,
+        };
+        let (mut editor, terminal) = start(size, None);
+        type_keys(&mut editor, &terminal, "ifirst<CR>second<Esc>:set nu<CR>:vs<CR>");
+        assert_eq!(terminal.row(0), "  1 first   |  1 first");
+        assert_eq!(terminal.row(1), "  2 second  |  2 second");
+        assert_eq!(terminal.row(2), "~           |~");
+        assert_eq!(terminal.caret(), Some(Position { col: 9, row: 1 }));
+        let border = terminal.cell(Position { col: 12, row: 0 }).unwrap();
+        assert_eq!(border.grapheme, "|");
+
+        type_keys(&mut editor, &terminal, "<C-w>lk0vl");
+        assert_eq!(terminal.caret(), Some(Position { col: 18, row: 0 }));
+        let style_at = |col| terminal.cell(Position { col, row: 0 }).unwrap().style;
+        assert_ne!(style_at(17), style_at(20));
+        assert_eq!(style_at(19), style_at(20));
+    }
+
+    #[test]
+    fn test_text_objects() {
+        let size = Size {
+            height: 10,
+            width: 40,
+        };
+        let (mut editor, terminal) = start(size, None);
+        type_keys(&mut editor, &terminal, "ifoo(\"bar\", [baz])<Esc>0ci\"qux<Esc>");
+        assert_eq!(terminal.row(0), "foo(\"qux\", [baz])");
+        // Selecting the same object again in Visual mode takes the next one out
+        type_keys(&mut editor, &terminal, "f[lvi[i(d");
+        assert_eq!(terminal.row(0), "foo()");
+        type_keys(&mut editor, &terminal, "u0fbdaw0fq.");
+        assert_eq!(terminal.row(0), "foo(\"\", [])");
     }
 }
@@ -0,0 +1,100 @@
+use std::fmt::{self, Display};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnnotationType {
+    Selection,
+    Match,
+    SelectedMatch,
+    // Syntax highlighting
+    Keyword,
+    Type,
+    Literal,
+    String,
+    Number,
+    Comment,
+    Heading,
+}
+
+// Marks a range of text with a type. On a Line, start and end are grapheme indices;
+// within an AnnotatedString, they are byte indices into its string.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Annotation {
+    pub kind: AnnotationType,
+    pub start: usize,
+    pub end: usize,
+}
+
+// A consecutive piece of an AnnotatedString sharing the same annotation
+pub struct AnnotatedStringPart<'a> {
+    pub string: &'a str,
+    pub annotation_type: Option<AnnotationType>,
+}
+
+#[derive(Default, Debug)]
+pub struct AnnotatedString {
+    string: String,
+    annotations: Vec<Annotation>,
+}
+
+impl AnnotatedString {
+    pub fn push_str(&mut self, string: &str, annotation_type: Option<AnnotationType>) {
+        let start = self.string.len();
+        self.string.push_str(string);
+        let end = self.string.len();
+        let Some(annotation_type) = annotation_type else {
+            return;
+        };
+        // Extend the previous annotation instead of adding a new one when possible
+        if let Some(last) = self.annotations.last_mut()
+            && last.kind == annotation_type
+            && last.end == start
+        {
+            last.end = end;
+            return;
+        }
+        self.annotations.push(Annotation {
+            kind: annotation_type,
+            start,
+            end,
+        });
+    }
+
+    // Splits the string into parts, each with at most one annotation.
+    // Where annotations overlap, the one added last wins.
+    pub fn parts(&self) -> Vec<AnnotatedStringPart<'_>> {
+        let mut boundaries: Vec<usize> = self
+            .annotations
+            .iter()
+            .flat_map(|annotation| [annotation.start, annotation.end])
+            .chain([0, self.string.len()])
+            .collect();
+        boundaries.sort_unstable();
+        boundaries.dedup();
+        boundaries
+            .windows(2)
+            .filter_map(|window| match window {
+                [start, end] => Some((*start, *end)),
+                _ => None,
+            })
+            .filter_map(|(start, end)| {
+                let string = self.string.get(start..end)?;
+                let annotation_type = self
+                    .annotations
+                    .iter()
+                    .rev()
+                    .find(|annotation| annotation.start <= start && annotation.end >= end)
+                    .map(|annotation| annotation.kind);
+                Some(AnnotatedStringPart {
+                    string,
+                    annotation_type,
+                })
+            })
+            .collect()
+    }
+}
+
+impl Display for AnnotatedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.string)
+    }
+}
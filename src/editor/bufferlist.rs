@@ -0,0 +1,192 @@
+use std::{cell::RefCell, fs, path::Path, rc::Rc};
+
+use super::view::{Buffer, ViewPosition};
+
+// An open buffer. Numbers start at 1 and are never reused.
+pub struct BufferEntry {
+    pub number: usize,
+    pub buffer: Rc<RefCell<Buffer>>,
+    // Where the caret was when the buffer was last shown
+    pub position: ViewPosition,
+    // Whether the buffer has been shown since it was opened
+    pub visited: bool,
+}
+
+// The buffers the editor has open, one of which is shown
+#[derive(Default)]
+pub struct BufferList {
+    entries: Vec<BufferEntry>,
+    current: usize,
+    // The number of the buffer shown before the current one
+    alternate: Option<usize>,
+    last_number: usize,
+}
+
+impl BufferList {
+    // Adds a buffer and returns its number. An empty buffer without a name that
+    // is currently shown is replaced instead, as it was never really used.
+    pub fn add(&mut self, buffer: Buffer) -> usize {
+        let buffer = Rc::new(RefCell::new(buffer));
+        if let Some(entry) = self.entries.get_mut(self.current) {
+            let unused = {
+                let current = entry.buffer.borrow();
+                !current.is_file_loaded() && !current.dirty && current.is_empty()
+            };
+            if unused {
+                entry.buffer = buffer;
+                entry.position = ViewPosition::default();
+                entry.visited = false;
+                return entry.number;
+            }
+        }
+        self.last_number = self.last_number.saturating_add(1);
+        self.entries.push(BufferEntry {
+            number: self.last_number,
+            buffer,
+            position: ViewPosition::default(),
+            visited: false,
+        });
+        self.last_number
+    }
+
+    pub fn current(&self) -> Option<&BufferEntry> {
+        self.entries.get(self.current)
+    }
+
+    pub fn get(&self, number: usize) -> Option<&BufferEntry> {
+        self.entries.iter().find(|entry| entry.number == number)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = &BufferEntry> {
+        self.entries.iter()
+    }
+
+    // The number of the entry holding the buffer
+    pub fn number_of(&self, buffer: &Rc<RefCell<Buffer>>) -> Option<usize> {
+        self.entries
+            .iter()
+            .find(|entry| Rc::ptr_eq(&entry.buffer, buffer))
+            .map(|entry| entry.number)
+    }
+
+    pub const fn alternate(&self) -> Option<usize> {
+        self.alternate
+    }
+
+    // Makes a buffer the current one, remembering where the view was in the
+    // buffer it leaves
+    pub fn select(&mut self, number: usize, position: ViewPosition) -> Option<&mut BufferEntry> {
+        let index = self.index_of(number)?;
+        if index != self.current
+            && let Some(current) = self.entries.get_mut(self.current)
+        {
+            current.position = position;
+            self.alternate = Some(current.number);
+        }
+        self.current = index;
+        self.entries.get_mut(index)
+    }
+
+    // The buffer `count` places after (or before, if backwards) the current one,
+    // wrapping around the end of the list
+    pub fn neighbour(&self, count: usize, backwards: bool) -> Option<usize> {
+        let length = self.entries.len();
+        let mut index = self.current;
+        for _ in 0..count {
+            index = if backwards {
+                index.checked_sub(1).unwrap_or(length.saturating_sub(1))
+            } else if index.saturating_add(1) >= length {
+                0
+            } else {
+                index.saturating_add(1)
+            };
+        }
+        self.entries.get(index).map(|entry| entry.number)
+    }
+
+    // The buffer editing the given file, if one does
+    pub fn find_file(&self, path: &Path) -> Option<usize> {
+        let canonical = fs::canonicalize(path).ok();
+        self.entries
+            .iter()
+            .find(|entry| {
+                entry.buffer.borrow().file_info.get_path().is_some_and(|other| {
+                    other == path
+                        || canonical.is_some() && fs::canonicalize(other).ok() == canonical
+                })
+            })
+            .map(|entry| entry.number)
+    }
+
+    // The buffer whose name contains the text, if exactly one does
+    pub fn find_name(&self, text: &str) -> Option<usize> {
+        let mut matches = self
+            .entries
+            .iter()
+            .filter(|entry| entry.buffer.borrow().file_info.to_string().contains(text));
+        let found = matches.next()?;
+        matches.next().is_none().then_some(found.number)
+    }
+
+    // Removes a buffer from the list. If it was the current one, the alternate
+    // buffer (or else a neighbour) becomes current; the caller has to show it.
+    pub fn remove(&mut self, number: usize) -> Option<BufferEntry> {
+        let index = self.index_of(number)?;
+        let entry = self.entries.remove(index);
+        if self.alternate == Some(number) {
+            self.alternate = None;
+        }
+        if index == self.current {
+            self.current = self
+                .alternate
+                .take()
+                .and_then(|alternate| self.index_of(alternate))
+                .unwrap_or(index.min(self.entries.len().saturating_sub(1)));
+        } else if index < self.current {
+            self.current = self.current.saturating_sub(1);
+        }
+        Some(entry)
+    }
+
+    pub fn modified(&self) -> impl Iterator<Item = &BufferEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.buffer.borrow().dirty)
+    }
+
+    fn index_of(&self, number: usize) -> Option<usize> {
+        self.entries.iter().position(|entry| entry.number == number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_buffer_list() {
+        let mut list = BufferList::default();
+        let position = ViewPosition::default();
+        assert_eq!(list.add(Buffer::default()), 1);
+        // The empty buffer is replaced by the first file
+        assert_eq!(list.add(Buffer::new_file("a.txt")), 1);
+        assert_eq!(list.add(Buffer::new_file("b.txt")), 2);
+        assert_eq!(list.add(Buffer::new_file("c.txt")), 3);
+        assert_eq!(list.neighbour(1, true), Some(3));
+        assert_eq!(list.neighbour(4, false), Some(2));
+
+        list.select(3, position);
+        list.select(2, position);
+        assert_eq!(list.alternate(), Some(3));
+        assert_eq!(list.find_name("c."), Some(3));
+        assert_eq!(list.find_name(".txt"), None);
+        assert_eq!(list.find_file(Path::new("a.txt")), Some(1));
+
+        // Removing the current buffer goes back to the alternate one
+        assert!(list.remove(2).is_some());
+        assert_eq!(list.current().map(|entry| entry.number), Some(3));
+        assert!(list.remove(1).is_some());
+        assert_eq!(list.current().map(|entry| entry.number), Some(3));
+        assert_eq!(list.add(Buffer::new_file("d.txt")), 4);
+    }
+}
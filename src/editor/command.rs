@@ -49,6 +49,123 @@ impl TryFrom<KeyEvent> for Move {
     }
 }
 
+// Vim motions, usable on their own or as the target of an operator
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Motion {
+    Left,
+    Right,
+    Up,
+    Down,
+    // gj and gk move by rows on screen, which differ from lines when wrapping
+    DisplayUp,
+    DisplayDown,
+    WordForward,
+    WordBackward,
+    WordEnd,
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    FileStart,
+    FileEnd,
+    FindForward(char),
+    TillForward(char),
+    MatchingPair,
+    NextMatch,
+    PreviousMatch,
+    // `x goes to where mark x was set, 'x to the first non-blank of its line
+    Mark(char),
+    MarkLine(char),
+}
+
+impl Motion {
+    // Linewise motions make operators act on whole lines
+    pub const fn is_linewise(self) -> bool {
+        matches!(
+            self,
+            Self::Up | Self::Down | Self::FileStart | Self::FileEnd | Self::MarkLine(_)
+        )
+    }
+
+    // Jumps are remembered in the jump list, for Ctrl-O to go back to
+    pub const fn is_jump(self) -> bool {
+        matches!(
+            self,
+            Self::FileStart
+                | Self::FileEnd
+                | Self::MatchingPair
+                | Self::NextMatch
+                | Self::PreviousMatch
+                | Self::Mark(_)
+                | Self::MarkLine(_)
+        )
+    }
+
+    // Inclusive motions make operators include the character under the target
+    pub const fn is_inclusive(self) -> bool {
+        matches!(
+            self,
+            Self::WordEnd | Self::FindForward(_) | Self::TillForward(_) | Self::MatchingPair
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextObjectKind {
+    Word,      // w
+    BigWord,   // W
+    Sentence,  // s
+    Paragraph, // p
+    // ", ' or `
+    Quote(char),
+    // (, [, { or < and the bracket that closes it
+    Pair(char, char),
+    Tag, // t
+}
+
+// Text objects, typed after an operator or in Visual mode: `i` covers what is
+// inside the object, `a` takes its brackets, quotes or surrounding blanks too
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextObject {
+    pub kind: TextObjectKind,
+    pub around: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    ShiftRight,
+    ShiftLeft,
+    Lowercase,
+    Uppercase,
+    ToggleCase,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+// Ctrl-W commands and their ex equivalents. `vertical` means side by side, or
+// the width rather than the height.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowCommand {
+    Split { vertical: bool }, // s, v
+    Focus(Direction),         // h, j, k, l
+    Next { backwards: bool }, // w, W
+    Close,                    // c
+    Quit,                     // q
+    Only,                     // o
+    Resize { vertical: bool, amount: isize }, // +, -, >, <
+    // _, |: the given size, or as large as possible
+    SetSize { vertical: bool, size: Option<usize> },
+    Equalize, // =
+}
+
 #[derive(Clone, Copy)]
 pub enum Edit {
     Insert(char),
@@ -81,6 +198,7 @@ pub enum System {
     Resize(Size),
     Dismiss,
     ShowCommandBar,
+    InsertRegister,
 }
 
 impl TryFrom<KeyEvent> for System {
@@ -90,6 +208,7 @@ impl TryFrom<KeyEvent> for System {
         match (event.code, event.modifiers) {
             (KeyCode::Esc, KeyModifiers::NONE) => Ok(Self::Dismiss),
             (Char(':'), KeyModifiers::NONE | KeyModifiers::SHIFT) => Ok(Self::ShowCommandBar),
+            (Char('r'), KeyModifiers::CONTROL) => Ok(Self::InsertRegister),
             _ => Err(format!(
                 "Unsupported key code {:?} or modifier {:?}",
                 event.code, event.modifiers
@@ -1,7 +1,7 @@
 use std::{cmp::min, io::Error};
 
 use super::{
-    command::Edit, Line, Size, Terminal, UIComponent,
+    command::Edit, Line, Position, Size, Terminal, UIComponent, theme::Scope,
 };
 
 #[derive(Default)]
@@ -22,6 +22,15 @@ impl CommandBar {
         self.set_requires_redraw(true);
     }
 
+    // Appends text, e.g. from a register. Line breaks are turned into spaces.
+    pub fn insert_text(&mut self, text: &str) {
+        for character in text.trim_end_matches('\n').chars() {
+            self.value
+                .append_char(if character == '\n' { ' ' } else { character });
+        }
+        self.set_requires_redraw(true);
+    }
+
     pub fn caret_position_col(&self) -> usize {
         let max_width = self
             .prompt.len()
@@ -48,10 +57,10 @@ impl UIComponent for CommandBar {
     }
 
     fn set_size(&mut self, size: Size) {
-        self.size = size
+        self.size = size;
     }
 
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+    fn draw(&mut self, terminal: &mut dyn Terminal, origin: Position) -> Result<(), Error> {
         let area_for_value = self.size.width.saturating_sub(self.prompt.len());
         let value_end = self.value.width();
         let value_start = value_end.saturating_sub(area_for_value);
@@ -65,6 +74,6 @@ impl UIComponent for CommandBar {
         } else {
             String::new()
         };
-        Terminal::print_row(origin_y, &to_print)
+        terminal.print_styled_at(origin, self.size.width, &to_print, Scope::MessageBar)
     }
 }
\ No newline at end of file
@@ -1,3 +1,16 @@
+use super::{
+    command::{Operator, WindowCommand},
+    keymap::MapCommand,
+    linerange::LineRange,
+};
+
+#[derive(Debug, PartialEq, Eq, Default, Clone, Copy)]
+pub struct SubstituteFlags {
+    pub global: bool,              // g: every match on a line, not just the first
+    pub confirm: bool,             // c: ask before each substitution
+    pub ignore_case: Option<bool>, // i or I; otherwise 'ignorecase' and 'smartcase' decide
+}
+
 #[derive(Debug, PartialEq)]
 pub enum ParsedCommand {
     Write,              // :w
@@ -8,6 +21,44 @@ pub enum ParsedCommand {
     WriteAs(String),    // :w filename
     Unknown(String),
     Help,             // :help
+    Operate(LineRange, Operator), // :d, :y, :>, :<
+    Set(Vec<String>),   // :set option...
+    NoHighlight,        // :nohlsearch
+    Colorscheme(String), // :colorscheme [name]
+    Map {               // :map, :nmap, :inoremap... [keys [mapped keys]]
+        command: MapCommand,
+        lhs: String,
+        rhs: String,
+    },
+    Goto(LineRange),    // :42
+    Substitute {        // :[range]s/pattern/replacement/[flags]
+        range: LineRange,
+        pattern: String,
+        replacement: String,
+        flags: SubstituteFlags,
+    },
+    Edit(String),       // :e filename
+    NextBuffer,         // :bn
+    PreviousBuffer,     // :bp
+    Buffer(String),     // :b N or :b name
+    ListBuffers,        // :ls
+    DeleteBuffer {      // :bd[!] [N]
+        buffer: String,
+        force: bool,
+    },
+    WriteAll,           // :wa
+    WriteAllQuit,       // :wqa or :xa
+    QuitAll,            // :qa
+    ForceQuitAll,       // :qa!
+    Split {             // :split [filename], :vsplit [filename]
+        vertical: bool,
+        file_name: String,
+    },
+    Window(WindowCommand), // :close, :only, :resize
+    Explore {           // :Explore [directory], :Lexplore [directory]
+        pane: bool,
+        directory: String,
+    },
 }
 
 impl ParsedCommand {
@@ -18,11 +69,58 @@ impl ParsedCommand {
             return Self::Unknown(String::new());
         }
         
+        let (range, trimmed) = match LineRange::parse(trimmed) {
+            Some((range, rest)) => (Some(range), rest.trim_start()),
+            None => (None, trimmed),
+        };
+        if trimmed.is_empty()
+            && let Some(range) = range
+        {
+            return Self::Goto(range);
+        }
+        if let Some(substitute) = Self::parse_substitute(range.unwrap_or_default(), trimmed) {
+            return substitute;
+        }
+        let operator = match trimmed {
+            "d" | "delete" => Some(Operator::Delete),
+            "y" | "yank" => Some(Operator::Yank),
+            ">" => Some(Operator::ShiftRight),
+            "<" => Some(Operator::ShiftLeft),
+            _ => None,
+        };
+        if let Some(operator) = operator {
+            return Self::Operate(range.unwrap_or_default(), operator);
+        }
+        if range.is_some() {
+            return Self::Unknown(input.trim().to_string());
+        }
+
         // Split the command and arguments
         let parts: Vec<&str> = trimmed.split_whitespace().collect();
         let command = parts[0];
         let args = &parts[1..];
-        
+        // :b2 and :bd2 are short for :b 2 and :bd 2
+        let (command, buffer) = match command.find(|character: char| character.is_ascii_digit()) {
+            Some(index) if index > 0 && command.starts_with('b') => command.split_at(index),
+            _ => (command, ""),
+        };
+        let argument = if buffer.is_empty() {
+            args.join(" ")
+        } else {
+            buffer.to_string()
+        };
+        if let Some(window_command) = Self::parse_window_command(command, args, &argument) {
+            return window_command;
+        }
+        if let Some(map_command) = MapCommand::from_name(command) {
+            let (lhs, rhs) = args.split_first().unwrap_or((&"", &[]));
+            return Self::Map {
+                command: map_command,
+                lhs: (*lhs).to_string(),
+                rhs: rhs.join(" "),
+            };
+        }
+
         match command {
             "w" | "write" => {
                 if args.is_empty() {
@@ -32,6 +130,34 @@ impl ParsedCommand {
                 }
             }
             "q" | "quit" => Self::Quit,
+            "qa" | "qall" => Self::QuitAll,
+            "qa!" | "qall!" => Self::ForceQuitAll,
+            "wa" | "wall" => Self::WriteAll,
+            "wqa" | "wqall" | "xa" | "xall" => Self::WriteAllQuit,
+            "e" | "edit" => Self::Edit(argument),
+            "bn" | "bnext" => Self::NextBuffer,
+            "bp" | "bprevious" | "bN" | "bNext" => Self::PreviousBuffer,
+            "b" | "buffer" => Self::Buffer(argument),
+            "ls" | "buffers" | "files" => Self::ListBuffers,
+            "E" | "Ex" | "Explore" => Self::Explore {
+                pane: false,
+                directory: argument,
+            },
+            "Lex" | "Lexplore" => Self::Explore {
+                pane: true,
+                directory: argument,
+            },
+            "bd" | "bdelete" => Self::DeleteBuffer {
+                buffer: argument,
+                force: false,
+            },
+            "bd!" | "bdelete!" => Self::DeleteBuffer {
+                buffer: argument,
+                force: true,
+            },
+            "se" | "set" => Self::Set(args.iter().map(ToString::to_string).collect()),
+            "noh" | "nohlsearch" => Self::NoHighlight,
+            "colo" | "colorscheme" => Self::Colorscheme(argument),
             "h" | "help" => Self::Help,
             "q!" | "quit!" => Self::ForceQuit,
             "wq" | "x" => {
@@ -44,11 +170,120 @@ impl ParsedCommand {
             _ => Self::Unknown(trimmed.to_string()),
         }
     }
+
+    // :split, :vsplit, :close, :only, :resize and :vertical
+    fn parse_window_command(command: &str, args: &[&str], argument: &str) -> Option<Self> {
+        Some(match command {
+            "sp" | "split" => Self::Split {
+                vertical: false,
+                file_name: argument.to_string(),
+            },
+            "vs" | "vsplit" => Self::Split {
+                vertical: true,
+                file_name: argument.to_string(),
+            },
+            "clo" | "close" => Self::Window(WindowCommand::Close),
+            "on" | "only" => Self::Window(WindowCommand::Only),
+            "res" | "resize" => Self::parse_resize(false, argument),
+            // Only :vertical resize and :vertical split are supported
+            "vert" | "vertical" => match args.split_first() {
+                Some((&("res" | "resize"), size)) => Self::parse_resize(true, &size.join(" ")),
+                Some((&("sp" | "split"), file_name)) => Self::Split {
+                    vertical: true,
+                    file_name: file_name.join(" "),
+                },
+                _ => Self::Unknown(format!("{command} {}", args.join(" "))),
+            },
+            _ => return None,
+        })
+    }
+
+    // :resize N sets the size, :resize +N and :resize -N change it. Without N
+    // the window becomes as large as possible.
+    fn parse_resize(vertical: bool, argument: &str) -> Self {
+        let amount = argument
+            .strip_prefix('+')
+            .map(|amount| (amount, 1))
+            .or_else(|| argument.strip_prefix('-').map(|amount| (amount, -1)));
+        if let Some((amount, sign)) = amount {
+            return match amount.parse::<isize>() {
+                Ok(amount) => Self::Window(WindowCommand::Resize {
+                    vertical,
+                    amount: amount.saturating_mul(sign),
+                }),
+                Err(_) => Self::Unknown(format!("resize {argument}")),
+            };
+        }
+        if argument.is_empty() {
+            return Self::Window(WindowCommand::SetSize {
+                vertical,
+                size: None,
+            });
+        }
+        match argument.parse() {
+            Ok(size) => Self::Window(WindowCommand::SetSize {
+                vertical,
+                size: Some(size),
+            }),
+            Err(_) => Self::Unknown(format!("resize {argument}")),
+        }
+    }
+
+    // Parses s/pattern/replacement/flags. Any punctuation can stand in for the /,
+    // and a delimiter preceded by a backslash is taken literally.
+    fn parse_substitute(range: LineRange, input: &str) -> Option<Self> {
+        let rest = input
+            .strip_prefix("substitute")
+            .or_else(|| input.strip_prefix('s'))?;
+        let mut characters = rest.chars();
+        let delimiter = characters
+            .next()
+            .filter(|delimiter| delimiter.is_ascii_punctuation() && *delimiter != '\\')?;
+        let mut parts = vec![String::new()];
+        let mut escaped = false;
+        for character in characters {
+            let count = parts.len();
+            let part = parts.last_mut()?;
+            if escaped {
+                if character != delimiter {
+                    part.push('\\');
+                }
+                part.push(character);
+                escaped = false;
+            } else if character == '\\' {
+                escaped = true;
+            } else if character == delimiter && count < 3 {
+                parts.push(String::new());
+            } else {
+                part.push(character);
+            }
+        }
+        let mut parts = parts.into_iter();
+        let pattern = parts.next().unwrap_or_default();
+        let replacement = parts.next().unwrap_or_default();
+        let mut flags = SubstituteFlags::default();
+        for flag in parts.next().unwrap_or_default().chars() {
+            match flag {
+                'g' => flags.global = true,
+                'c' => flags.confirm = true,
+                'i' => flags.ignore_case = Some(true),
+                'I' => flags.ignore_case = Some(false),
+                _ => return Some(Self::Unknown(input.to_string())),
+            }
+        }
+        Some(Self::Substitute {
+            range,
+            pattern,
+            replacement,
+            flags,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::editor::keymap::MapMode;
 
     #[test]
     fn test_parse_commands() {
@@ -67,5 +302,69 @@ mod tests {
             ParsedCommand::parse("wq test.txt"),
             ParsedCommand::WriteAsAndQuit("test.txt".to_string())
         );
+        assert!(matches!(
+            ParsedCommand::parse("'<,'>d"),
+            ParsedCommand::Operate(_, Operator::Delete)
+        ));
+        assert_eq!(
+            ParsedCommand::parse(">"),
+            ParsedCommand::Operate(LineRange::default(), Operator::ShiftRight)
+        );
+        assert_eq!(
+            ParsedCommand::parse("s#a\\#b#c\\1#gI"),
+            ParsedCommand::Substitute {
+                range: LineRange::default(),
+                pattern: "a#b".to_string(),
+                replacement: "c\\1".to_string(),
+                flags: SubstituteFlags {
+                    global: true,
+                    confirm: false,
+                    ignore_case: Some(false),
+                },
+            }
+        );
+        assert!(matches!(
+            ParsedCommand::parse("%s/x/y"),
+            ParsedCommand::Substitute { flags, .. } if flags == SubstituteFlags::default()
+        ));
+        assert!(matches!(ParsedCommand::parse("42"), ParsedCommand::Goto(_)));
+        assert_eq!(
+            ParsedCommand::parse("set noic scs"),
+            ParsedCommand::Set(vec!["noic".to_string(), "scs".to_string()])
+        );
+        assert_eq!(
+            ParsedCommand::parse("e src/main.rs"),
+            ParsedCommand::Edit("src/main.rs".to_string())
+        );
+        assert_eq!(ParsedCommand::parse("b3"), ParsedCommand::Buffer("3".to_string()));
+        assert_eq!(
+            ParsedCommand::parse("colo dusk"),
+            ParsedCommand::Colorscheme("dusk".to_string())
+        );
+        assert_eq!(
+            ParsedCommand::parse("inoremap jk <Esc>"),
+            ParsedCommand::Map {
+                command: MapCommand {
+                    modes: &[MapMode::Insert],
+                    recursive: false,
+                },
+                lhs: "jk".to_string(),
+                rhs: "<Esc>".to_string(),
+            }
+        );
+        assert_eq!(
+            ParsedCommand::parse("vertical resize -5"),
+            ParsedCommand::Window(WindowCommand::Resize {
+                vertical: true,
+                amount: -5,
+            })
+        );
+        assert_eq!(
+            ParsedCommand::parse("bd! 2"),
+            ParsedCommand::DeleteBuffer {
+                buffer: "2".to_string(),
+                force: true,
+            }
+        );
     }
 }
\ No newline at end of file
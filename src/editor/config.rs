@@ -0,0 +1,172 @@
+use std::{
+    fs::read_to_string,
+    io::{Error, ErrorKind},
+    path::PathBuf,
+};
+
+use super::settings::config_dir;
+
+const FILE_NAME: &str = "config.toml";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ConfigValue {
+    Bool(bool),
+    Number(usize),
+    String(String),
+}
+
+// A `key = value` line of the config file
+#[derive(Debug, PartialEq, Eq)]
+pub struct ConfigEntry {
+    // The 1-based line number, for error messages
+    pub line: usize,
+    // The [section] the entry is in, or "" before the first one
+    pub section: String,
+    pub key: String,
+    pub value: ConfigValue,
+}
+
+// The user's config file, $XDG_CONFIG_HOME/amoxcalli/config.toml. Returns None
+// if there is none.
+pub fn load() -> Option<Result<(PathBuf, String), Error>> {
+    let path = config_dir()?.join(FILE_NAME);
+    match read_to_string(&path) {
+        Ok(text) => Some(Ok((path, text))),
+        Err(error) if error.kind() == ErrorKind::NotFound => None,
+        Err(error) => Some(Err(error)),
+    }
+}
+
+// Parses the part of TOML the config file uses: `key = value` lines, optionally
+// under `[section]` headers, where values are booleans, whole numbers or
+// strings. Lines that can't be parsed are returned as errors, so that the rest
+// of the file still applies.
+pub fn parse(text: &str) -> (Vec<ConfigEntry>, Vec<(usize, String)>) {
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+    let mut section = String::new();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index.saturating_add(1);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            match name.split_once(']') {
+                Some((name, rest)) if is_blank(rest) => section = name.trim().to_string(),
+                _ => errors.push((line_number, format!("Invalid section header: {line}"))),
+            }
+            continue;
+        }
+        match parse_entry(line) {
+            Ok((key, value)) => entries.push(ConfigEntry {
+                line: line_number,
+                section: section.clone(),
+                key,
+                value,
+            }),
+            Err(error) => errors.push((line_number, error)),
+        }
+    }
+    (entries, errors)
+}
+
+fn parse_entry(line: &str) -> Result<(String, ConfigValue), String> {
+    let (key, rest) = if line.starts_with(['"', '\'']) {
+        parse_string(line)?
+    } else {
+        let end = line.find(['=', ' ', '\t']).unwrap_or(line.len());
+        let (key, rest) = line.split_at(end);
+        if key.is_empty() {
+            return Err(format!("Missing key: {line}"));
+        }
+        (key.to_string(), rest)
+    };
+    let Some(rest) = rest.trim_start().strip_prefix('=') else {
+        return Err(format!("Expected '=' after {key}"));
+    };
+    let rest = rest.trim_start();
+    let (value, rest) = if rest.starts_with(['"', '\'']) {
+        let (text, rest) = parse_string(rest)?;
+        (ConfigValue::String(text), rest)
+    } else {
+        let end = rest.find([' ', '\t', '#']).unwrap_or(rest.len());
+        let (word, rest) = rest.split_at(end);
+        let value = match word {
+            "true" => ConfigValue::Bool(true),
+            "false" => ConfigValue::Bool(false),
+            // TOML allows underscores between digits
+            _ => word
+                .replace('_', "")
+                .parse()
+                .map(ConfigValue::Number)
+                .map_err(|_| format!("Invalid value for {key}: {word}"))?,
+        };
+        (value, rest)
+    };
+    if !is_blank(rest) {
+        return Err(format!("Unexpected text after the value of {key}: {}", rest.trim()));
+    }
+    Ok((key, value))
+}
+
+// Whether only whitespace and maybe a comment are left on a line
+fn is_blank(rest: &str) -> bool {
+    let rest = rest.trim_start();
+    rest.is_empty() || rest.starts_with('#')
+}
+
+// Parses a "basic" string with escapes or a 'literal' one at the start of
+// `text`, returning it and the text after it
+fn parse_string(text: &str) -> Result<(String, &str), String> {
+    let mut chars = text.char_indices();
+    let quote = chars.next().map(|(_, quote)| quote).unwrap_or_default();
+    let mut result = String::new();
+    while let Some((index, character)) = chars.next() {
+        if character == quote {
+            let rest = text.get(index.saturating_add(1)..).unwrap_or_default();
+            return Ok((result, rest));
+        }
+        if character == '\\' && quote == '"' {
+            let escaped = match chars.next().map(|(_, escaped)| escaped) {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(escaped @ ('"' | '\\')) => escaped,
+                _ => return Err(format!("Invalid escape in {text}")),
+            };
+            result.push(escaped);
+        } else {
+            result.push(character);
+        }
+    }
+    Err(format!("Unterminated string: {text}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_config() {
+        let text = "# Settings\ntabstop = 4\nwrap=true # comment\ntheme = \"dusk\"\n\n\
+                    [section]\n\"a key\" = 'C:\\path'\nbroken\nnumber = yes\nname = \"open\n";
+        let (entries, errors) = parse(text);
+        let entry = |line, section: &str, key: &str, value| ConfigEntry {
+            line,
+            section: section.to_string(),
+            key: key.to_string(),
+            value,
+        };
+        assert_eq!(
+            entries,
+            vec![
+                entry(2, "", "tabstop", ConfigValue::Number(4)),
+                entry(3, "", "wrap", ConfigValue::Bool(true)),
+                entry(4, "", "theme", ConfigValue::String("dusk".to_string())),
+                entry(7, "section", "a key", ConfigValue::String("C:\\path".to_string())),
+            ]
+        );
+        let lines: Vec<usize> = errors.iter().map(|(line, _)| *line).collect();
+        assert_eq!(lines, vec![8, 9, 10]);
+    }
+}
@@ -4,6 +4,8 @@ pub struct DocumentStatus {
     pub current_line_index: usize,
     pub is_modified: bool,
     pub file_name: String,
+    pub file_format: String,
+    pub file_type: String,
 }
 
 impl DocumentStatus {
@@ -16,7 +18,7 @@ impl DocumentStatus {
     }
     pub fn line_count_to_string(&self) -> String {
         let line_count = if self.total_lines == 0 { 1 } else { self.total_lines };
-        format!("{} lines", line_count)
+        format!("{line_count} lines")
     }
     pub fn position_indicator_to_string(&self) -> String {
         let total = if self.total_lines == 0 { 1 } else { self.total_lines };
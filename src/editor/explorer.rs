@@ -0,0 +1,480 @@
+use crossterm::event::{
+    KeyCode::{Char, Down, End, Enter, Esc, Home, Left, PageDown, PageUp, Right, Up},
+    KeyEvent, KeyModifiers,
+};
+use std::{
+    collections::HashSet,
+    env,
+    fs::{self, OpenOptions},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+};
+
+use super::{Line, Position, Size, Terminal, UIComponent, theme::Scope};
+
+mod gitignore;
+use gitignore::Gitignore;
+
+// Columns each level of the tree is indented by
+const INDENT: usize = 2;
+
+// What a key does in the explorer
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ExplorerCommand {
+    Down,
+    Up,
+    PageDown,
+    PageUp,
+    First,
+    Last,
+    // Opens a file, or expands or collapses a directory
+    Open,
+    // Collapses the directory, or the one the entry is in
+    Collapse,
+    // Shows the parent of the root directory
+    Parent,
+    ToggleHidden,
+    Refresh,
+    Create { directory: bool },
+    Rename,
+    Delete,
+    // Goes back to the windows, leaving a side pane open
+    Leave,
+    Close,
+}
+
+impl TryFrom<KeyEvent> for ExplorerCommand {
+    type Error = String;
+
+    fn try_from(event: KeyEvent) -> Result<Self, Self::Error> {
+        let KeyEvent {
+            code, modifiers, ..
+        } = event;
+        if modifiers == KeyModifiers::CONTROL {
+            return match code {
+                Char('l') => Ok(Self::Refresh),
+                Char('d') => Ok(Self::PageDown),
+                Char('u') => Ok(Self::PageUp),
+                _ => Err(format!("Unrecognized explorer key: Ctrl-{code:?}")),
+            };
+        }
+        match code {
+            Char('j') | Down => Ok(Self::Down),
+            Char('k') | Up => Ok(Self::Up),
+            PageDown => Ok(Self::PageDown),
+            PageUp => Ok(Self::PageUp),
+            Char('g') | Home => Ok(Self::First),
+            Char('G') | End => Ok(Self::Last),
+            Char('l' | 'o') | Enter | Right => Ok(Self::Open),
+            Char('h') | Left => Ok(Self::Collapse),
+            Char('-') => Ok(Self::Parent),
+            Char('.') => Ok(Self::ToggleHidden),
+            Char('%') => Ok(Self::Create { directory: false }),
+            Char('d') => Ok(Self::Create { directory: true }),
+            Char('R') => Ok(Self::Rename),
+            Char('D') => Ok(Self::Delete),
+            Esc => Ok(Self::Leave),
+            Char('q') => Ok(Self::Close),
+            _ => Err(format!("Unrecognized explorer key: {code:?}")),
+        }
+    }
+}
+
+// A file or directory shown in the tree
+pub struct Entry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    name: String,
+    depth: usize,
+}
+
+// A tree of the files below a directory, either in a pane left of the windows
+// or in place of a window. Dotfiles and files ignored by .gitignore are hidden
+// unless show_hidden is set.
+#[derive(Default)]
+pub struct Explorer {
+    root: PathBuf,
+    entries: Vec<Entry>,
+    expanded: HashSet<PathBuf>,
+    selected: usize,
+    scroll_offset: usize,
+    show_hidden: bool,
+    pane: bool,
+    size: Size,
+    requires_redraw: bool,
+}
+
+impl Explorer {
+    pub fn new(root: &Path, pane: bool) -> Self {
+        let mut explorer = Self {
+            root: fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf()),
+            pane,
+            ..Self::default()
+        };
+        explorer.refresh();
+        explorer
+    }
+
+    pub const fn is_pane(&self) -> bool {
+        self.pane
+    }
+
+    pub fn selected(&self) -> Option<&Entry> {
+        self.entries.get(self.selected)
+    }
+
+    // Reads the directories again, keeping the selected entry if it still exists
+    pub fn refresh(&mut self) {
+        let selected = self.selected().map(|entry| entry.path.clone());
+        let mut entries = Vec::new();
+        let mut ignores = parent_ignores(&self.root);
+        self.list(&self.root, 0, &mut ignores, &mut entries);
+        self.entries = entries;
+        match selected {
+            Some(path) => self.select_path(&path),
+            None => self.move_selection(0),
+        }
+    }
+
+    fn list(&self, directory: &Path, depth: usize, ignores: &mut Vec<Gitignore>, entries: &mut Vec<Entry>) {
+        let Ok(read_dir) = fs::read_dir(directory) else {
+            return;
+        };
+        let loaded = Gitignore::load(directory).map(|gitignore| ignores.push(gitignore));
+        let mut children: Vec<(String, PathBuf, bool)> = read_dir
+            .filter_map(Result::ok)
+            .map(|child| {
+                let path = child.path();
+                let is_dir = path.is_dir();
+                (child.file_name().to_string_lossy().to_string(), path, is_dir)
+            })
+            .filter(|(name, path, is_dir)| {
+                self.show_hidden
+                    || !name.starts_with('.')
+                        && ignores
+                            .iter()
+                            .rev()
+                            .find_map(|gitignore| gitignore.matched(path, *is_dir))
+                            != Some(true)
+            })
+            .collect();
+        // Directories first, then by name
+        children.sort_by(|(name, _, is_dir), (other_name, _, other_is_dir)| {
+            other_is_dir
+                .cmp(is_dir)
+                .then_with(|| name.to_lowercase().cmp(&other_name.to_lowercase()))
+        });
+        for (name, path, is_dir) in children {
+            let expanded = is_dir && self.expanded.contains(&path);
+            entries.push(Entry {
+                path: path.clone(),
+                is_dir,
+                name,
+                depth,
+            });
+            if expanded {
+                self.list(&path, depth.saturating_add(1), ignores, entries);
+            }
+        }
+        if loaded.is_some() {
+            ignores.pop();
+        }
+    }
+
+    pub fn execute(&mut self, command: ExplorerCommand) {
+        let page = self.visible_rows().max(1);
+        match command {
+            ExplorerCommand::Down => self.move_selection(self.selected.saturating_add(1)),
+            ExplorerCommand::Up => self.move_selection(self.selected.saturating_sub(1)),
+            ExplorerCommand::PageDown => self.move_selection(self.selected.saturating_add(page)),
+            ExplorerCommand::PageUp => self.move_selection(self.selected.saturating_sub(page)),
+            ExplorerCommand::First => self.move_selection(0),
+            ExplorerCommand::Last => self.move_selection(usize::MAX),
+            ExplorerCommand::Open => self.toggle_directory(),
+            ExplorerCommand::Collapse => self.collapse(),
+            ExplorerCommand::Parent => self.show_parent(),
+            ExplorerCommand::ToggleHidden => {
+                self.show_hidden = !self.show_hidden;
+                self.refresh();
+            }
+            ExplorerCommand::Refresh => self.refresh(),
+            // Need a prompt, so the editor handles them
+            ExplorerCommand::Create { .. }
+            | ExplorerCommand::Rename
+            | ExplorerCommand::Delete
+            | ExplorerCommand::Leave
+            | ExplorerCommand::Close => {}
+        }
+    }
+
+    fn move_selection(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+        let rows = self.visible_rows();
+        if self.selected < self.scroll_offset {
+            self.scroll_offset = self.selected;
+        } else if self.selected >= self.scroll_offset.saturating_add(rows) {
+            self.scroll_offset = self.selected.saturating_add(1).saturating_sub(rows);
+        }
+        self.set_requires_redraw(true);
+    }
+
+    pub fn select_path(&mut self, path: &Path) {
+        let index = self.entries.iter().position(|entry| entry.path == path);
+        self.move_selection(index.unwrap_or(self.selected));
+    }
+
+    fn toggle_directory(&mut self) {
+        let Some(entry) = self.selected().filter(|entry| entry.is_dir) else {
+            return;
+        };
+        let path = entry.path.clone();
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+        self.refresh();
+    }
+
+    fn collapse(&mut self) {
+        let Some(entry) = self.selected() else {
+            return;
+        };
+        if entry.is_dir && self.expanded.contains(&entry.path) {
+            let path = entry.path.clone();
+            self.expanded.remove(&path);
+        } else if let Some(parent) = entry.path.parent().filter(|parent| *parent != self.root) {
+            let parent = parent.to_path_buf();
+            self.expanded.remove(&parent);
+            self.select_path(&parent);
+        }
+        self.refresh();
+    }
+
+    fn show_parent(&mut self) {
+        let Some(parent) = self.root.parent() else {
+            return;
+        };
+        let old_root = self.root.clone();
+        self.root = parent.to_path_buf();
+        self.expanded.insert(old_root.clone());
+        self.refresh();
+        self.select_path(&old_root);
+    }
+
+    // New files go into the selected directory, or next to the selected file
+    fn target_directory(&self) -> PathBuf {
+        match self.selected() {
+            Some(entry) if entry.is_dir => entry.path.clone(),
+            Some(entry) => entry.path.parent().unwrap_or(&self.root).to_path_buf(),
+            None => self.root.clone(),
+        }
+    }
+
+    pub fn create(&mut self, name: &str, directory: bool) -> Result<PathBuf, Error> {
+        let target = self.target_directory();
+        let path = target.join(name);
+        if directory {
+            fs::create_dir_all(&path)?;
+        } else {
+            OpenOptions::new().write(true).create_new(true).open(&path)?;
+        }
+        if target != self.root {
+            self.expanded.insert(target);
+        }
+        self.refresh();
+        self.select_path(&path);
+        Ok(path)
+    }
+
+    // Renames the selected entry, within the same directory unless the new name
+    // has a path in it. Returns the old and the new path.
+    pub fn rename(&mut self, new_name: &str) -> Result<(PathBuf, PathBuf), Error> {
+        let Some(entry) = self.selected() else {
+            return Err(Error::new(ErrorKind::NotFound, "Nothing selected"));
+        };
+        let from = entry.path.clone();
+        let to = from.parent().unwrap_or(&self.root).join(new_name);
+        if to.exists() {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("{} already exists", to.display()),
+            ));
+        }
+        fs::rename(&from, &to)?;
+        if self.expanded.remove(&from) {
+            self.expanded.insert(to.clone());
+        }
+        self.refresh();
+        self.select_path(&to);
+        Ok((from, to))
+    }
+
+    // Deletes the selected file, or directory if it is empty
+    pub fn delete(&mut self) -> Result<PathBuf, Error> {
+        let Some(entry) = self.selected() else {
+            return Err(Error::new(ErrorKind::NotFound, "Nothing selected"));
+        };
+        let path = entry.path.clone();
+        if entry.is_dir {
+            fs::remove_dir(&path)?;
+        } else {
+            fs::remove_file(&path)?;
+        }
+        self.expanded.remove(&path);
+        self.refresh();
+        Ok(path)
+    }
+
+    // Where the caret goes: on the name of the selected entry
+    pub fn caret_position(&self) -> Position {
+        let depth = self.selected().map_or(0, |entry| entry.depth);
+        Position {
+            row: self.selected.saturating_sub(self.scroll_offset).saturating_add(1),
+            col: depth.saturating_add(1).saturating_mul(INDENT),
+        }
+    }
+
+    // The first row shows the root directory
+    const fn visible_rows(&self) -> usize {
+        self.size.height.saturating_sub(1)
+    }
+
+    fn entry_text(&self, entry: &Entry) -> String {
+        let marker = match (entry.is_dir, self.expanded.contains(&entry.path)) {
+            (true, true) => "▾ ",
+            (true, false) => "▸ ",
+            (false, _) => "  ",
+        };
+        let slash = if entry.is_dir { "/" } else { "" };
+        format!(
+            "{}{marker}{}{slash}",
+            " ".repeat(entry.depth.saturating_mul(INDENT)),
+            entry.name
+        )
+    }
+}
+
+impl UIComponent for Explorer {
+    fn set_requires_redraw(&mut self, requires_redraw: bool) {
+        self.requires_redraw = requires_redraw;
+    }
+
+    fn requires_redraw(&self) -> bool {
+        self.requires_redraw
+    }
+
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
+        self.move_selection(self.selected);
+    }
+
+    fn draw(&mut self, terminal: &mut dyn Terminal, origin: Position) -> Result<(), Error> {
+        // A pane has a separator in its last column
+        let width = if self.pane {
+            self.size.width.saturating_sub(1)
+        } else {
+            self.size.width
+        };
+        let fit = |text: &str| Line::from(text).get_visible_graphemes(0..width);
+        for row in 0..self.size.height {
+            let at = Position {
+                row: origin.row.saturating_add(row),
+                col: origin.col,
+            };
+            let index = row.saturating_sub(1).saturating_add(self.scroll_offset);
+            if row == 0 {
+                terminal.print_at(at, width, &fit(&format!("{}/", self.root.display())))?;
+            } else if let Some(entry) = self.entries.get(index) {
+                let text = fit(&self.entry_text(entry));
+                if index == self.selected {
+                    terminal.print_styled_at(at, width, &text, Scope::Selection)?;
+                } else {
+                    terminal.print_at(at, width, &text)?;
+                }
+            } else {
+                terminal.print_styled_at(at, width, "~", Scope::Tilde)?;
+            }
+            if self.pane {
+                terminal.print_styled_at(
+                    Position {
+                        col: at.col.saturating_add(width),
+                        ..at
+                    },
+                    1,
+                    "|",
+                    Scope::Border,
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// A path relative to the current directory, if it is below it
+pub fn display_path(path: &Path) -> String {
+    env::current_dir()
+        .ok()
+        .and_then(|current| path.strip_prefix(current).ok().map(Path::to_path_buf))
+        .filter(|relative| !relative.as_os_str().is_empty())
+        .unwrap_or_else(|| path.to_path_buf())
+        .display()
+        .to_string()
+}
+
+// The .gitignore files of the directories above the root, up to the top of the
+// git repository it is in, outermost first
+fn parent_ignores(root: &Path) -> Vec<Gitignore> {
+    let mut ignores = Vec::new();
+    for directory in root.ancestors().skip(1) {
+        ignores.extend(Gitignore::load(directory));
+        if directory.join(".git").exists() {
+            ignores.reverse();
+            return ignores;
+        }
+    }
+    // Not in a git repository
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env::temp_dir, process};
+
+    #[test]
+    fn test_explorer_tree() {
+        let root = temp_dir().join(format!("amoxcalli-explorer-{}", process::id()));
+        fs::create_dir_all(root.join("src/nested")).unwrap();
+        fs::create_dir_all(root.join("target")).unwrap();
+        fs::write(root.join("src/main.rs"), "").unwrap();
+        fs::write(root.join("README.md"), "").unwrap();
+        fs::write(root.join(".hidden"), "").unwrap();
+        fs::write(root.join(".gitignore"), "target/\n").unwrap();
+        let names = |explorer: &Explorer| -> Vec<String> {
+            explorer.entries.iter().map(|entry| explorer.entry_text(entry)).collect()
+        };
+
+        let mut explorer = Explorer::new(&root, true);
+        assert_eq!(names(&explorer), vec!["▸ src/", "  README.md"]);
+        explorer.execute(ExplorerCommand::Open);
+        assert_eq!(
+            names(&explorer),
+            vec!["▾ src/", "  ▸ nested/", "    main.rs", "  README.md"]
+        );
+
+        explorer.execute(ExplorerCommand::Last);
+        explorer.execute(ExplorerCommand::Up);
+        assert!(explorer.create("lib.rs", false).is_ok());
+        assert_eq!(explorer.selected().map(|entry| entry.name.as_str()), Some("lib.rs"));
+        assert!(explorer.rename("mod.rs").is_ok());
+        assert!(root.join("src/mod.rs").exists());
+        explorer.execute(ExplorerCommand::Collapse);
+        assert_eq!(explorer.selected().map(|entry| entry.name.as_str()), Some("src"));
+
+        explorer.execute(ExplorerCommand::ToggleHidden);
+        assert_eq!(
+            names(&explorer),
+            vec!["▸ src/", "▸ target/", "  .gitignore", "  .hidden", "  README.md"]
+        );
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
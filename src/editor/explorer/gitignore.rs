@@ -0,0 +1,186 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+// A line of a .gitignore file
+struct Rule {
+    pattern: Vec<char>,
+    negated: bool,
+    directory_only: bool,
+    // Patterns with a slash match the path from the .gitignore's directory,
+    // others match the name at any depth
+    anchored: bool,
+}
+
+// The rules of one .gitignore file, which apply to the directory it is in
+pub struct Gitignore {
+    base: PathBuf,
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    // Reads the .gitignore in a directory, if there is one
+    pub fn load(directory: &Path) -> Option<Self> {
+        let text = fs::read_to_string(directory.join(".gitignore")).ok()?;
+        Some(Self::parse(directory, &text))
+    }
+
+    pub fn parse(base: &Path, text: &str) -> Self {
+        let rules = text
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_end();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let (directory_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let anchored = line.contains('/');
+                let line = line.strip_prefix('/').unwrap_or(line);
+                Some(Rule {
+                    pattern: line.chars().collect(),
+                    negated,
+                    directory_only,
+                    anchored,
+                })
+            })
+            .collect();
+        Self {
+            base: base.to_path_buf(),
+            rules,
+        }
+    }
+
+    // Whether the path is ignored (Some(true)) or explicitly not ignored by a
+    // `!` rule (Some(false)). None if no rule matches it. The last matching rule wins.
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative: Vec<char> = path
+            .strip_prefix(&self.base)
+            .ok()?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+            .chars()
+            .collect();
+        let name_start = relative
+            .iter()
+            .rposition(|&character| character == '/')
+            .map_or(0, |slash| slash.saturating_add(1));
+        let name = relative.get(name_start..).unwrap_or_default();
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| {
+                (is_dir || !rule.directory_only)
+                    && glob_match(&rule.pattern, if rule.anchored { &relative } else { name })
+            })
+            .map(|rule| !rule.negated)
+    }
+}
+
+// Matches text against a glob: `*` matches anything but a slash, `**` anything
+// at all, `?` one character and `[a-z]` (or `[!a-z]`) one character from a class
+fn glob_match(pattern: &[char], text: &[char]) -> bool {
+    let tail = |skip: usize| text.get(skip..).unwrap_or_default();
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => {
+            if let Some(('*', rest)) = rest.split_first() {
+                // `**/` may also stand for no directory at all
+                if let Some(('/', after)) = rest.split_first()
+                    && glob_match(after, text)
+                {
+                    return true;
+                }
+                (0..=text.len()).any(|skip| glob_match(rest, tail(skip)))
+            } else {
+                let segment_end = text.iter().position(|&character| character == '/');
+                (0..=segment_end.unwrap_or(text.len())).any(|skip| glob_match(rest, tail(skip)))
+            }
+        }
+        Some(('?', rest)) => text
+            .split_first()
+            .is_some_and(|(&character, text)| character != '/' && glob_match(rest, text)),
+        Some(('[', rest)) => {
+            let Some(((matched, rest), (&character, text))) =
+                match_class(rest, text.first().copied()).zip(text.split_first())
+            else {
+                return false;
+            };
+            character != '/' && matched && glob_match(rest, text)
+        }
+        Some((&first, rest)) => {
+            let (literal, rest) = match (first, rest.split_first()) {
+                ('\\', Some((&escaped, after))) => (escaped, after),
+                _ => (first, rest),
+            };
+            text.split_first()
+                .is_some_and(|(&character, text)| character == literal && glob_match(rest, text))
+        }
+    }
+}
+
+// Matches a character against the class that starts the pattern (after the `[`).
+// Returns whether it matched and the rest of the pattern, or None if the class
+// isn't closed.
+fn match_class(pattern: &[char], character: Option<char>) -> Option<(bool, &[char])> {
+    let (negated, pattern) = match pattern.split_first() {
+        Some(('!' | '^', rest)) => (true, rest),
+        _ => (false, pattern),
+    };
+    // A `]` right at the start is part of the class
+    let end = pattern
+        .iter()
+        .skip(1)
+        .position(|&class_character| class_character == ']')?
+        .saturating_add(1);
+    let class = pattern.get(..end)?;
+    let mut matched = false;
+    let mut index = 0;
+    while let Some(&start) = class.get(index) {
+        if let (Some('-'), Some(&last)) = (class.get(index.saturating_add(1)), class.get(index.saturating_add(2))) {
+            matched |= character.is_some_and(|character| (start..=last).contains(&character));
+            index = index.saturating_add(3);
+        } else {
+            matched |= character == Some(start);
+            index = index.saturating_add(1);
+        }
+    }
+    Some((matched != negated, pattern.get(end.saturating_add(1)..)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gitignore_rules() {
+        let base = Path::new("/project");
+        let gitignore = Gitignore::parse(
+            base,
+            "# build output\n/target\n*.log\n!keep.log\nbuild/\ndocs/**/*.tmp\nfile[0-9].txt\n",
+        );
+        let matched = |path: &str, is_dir| gitignore.matched(&base.join(path), is_dir);
+        assert_eq!(matched("target", true), Some(true));
+        // Anchored to the .gitignore's directory
+        assert_eq!(matched("src/target", true), None);
+        assert_eq!(matched("src/debug.log", false), Some(true));
+        assert_eq!(matched("keep.log", false), Some(false));
+        assert_eq!(matched("build", true), Some(true));
+        assert_eq!(matched("build", false), None);
+        assert_eq!(matched("docs/a.tmp", false), Some(true));
+        assert_eq!(matched("docs/a/b/c.tmp", false), Some(true));
+        assert_eq!(matched("docs.tmp", false), None);
+        assert_eq!(matched("file3.txt", false), Some(true));
+        assert_eq!(matched("fileA.txt", false), None);
+        assert_eq!(matched("/elsewhere/x.log", false), None);
+    }
+}
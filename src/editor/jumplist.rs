@@ -0,0 +1,110 @@
+use super::view::Location;
+
+// Older jumps are forgotten past this many
+const MAX_JUMPS: usize = 100;
+
+// A place the caret jumped from: a buffer, by number, and a location in it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Jump {
+    pub buffer: usize,
+    pub location: Location,
+}
+
+impl Jump {
+    const fn same_line(&self, other: &Self) -> bool {
+        self.buffer == other.buffer && self.location.line_index == other.location.line_index
+    }
+}
+
+// The jumps made with search, G, %, marks and the like, oldest first, for
+// Ctrl-O to go back through and Ctrl-I forward again
+#[derive(Default)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    // The jump Ctrl-O or Ctrl-I went to last, or the end of the list if a
+    // jump was made since
+    index: usize,
+}
+
+impl JumpList {
+    // Remembers a jump at the end of the list, dropping an older one from the
+    // same line
+    pub fn push(&mut self, jump: Jump) {
+        self.jumps.retain(|other| !other.same_line(&jump));
+        self.jumps.push(jump);
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+
+    // Ctrl-O: the jump `count` back. Going back from the end of the list
+    // remembers where the caret is, so that Ctrl-I can return to it.
+    pub fn back(&mut self, from: Jump, count: usize) -> Option<Jump> {
+        if self.index >= self.jumps.len() {
+            self.push(from);
+            self.index = self.jumps.len().saturating_sub(1);
+        }
+        let index = self.index.checked_sub(count)?;
+        let jump = *self.jumps.get(index)?;
+        self.index = index;
+        Some(jump)
+    }
+
+    // Ctrl-I: the jump `count` forward
+    pub fn forward(&mut self, count: usize) -> Option<Jump> {
+        let index = self.index.saturating_add(count);
+        let jump = *self.jumps.get(index)?;
+        self.index = index;
+        Some(jump)
+    }
+
+    // Drops the jumps into a buffer that was closed
+    pub fn remove_buffer(&mut self, buffer: usize) {
+        let before = self
+            .jumps
+            .iter()
+            .take(self.index)
+            .filter(|jump| jump.buffer == buffer)
+            .count();
+        self.jumps.retain(|jump| jump.buffer != buffer);
+        self.index = self.index.saturating_sub(before);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump(buffer: usize, line_index: usize) -> Jump {
+        Jump {
+            buffer,
+            location: Location {
+                grapheme_index: 0,
+                line_index,
+            },
+        }
+    }
+
+    #[test]
+    fn test_jump_list() {
+        let mut jumps = JumpList::default();
+        assert_eq!(jumps.back(jump(1, 0), 1), None);
+        jumps.push(jump(1, 10));
+        jumps.push(jump(1, 20));
+        jumps.push(jump(2, 5));
+        // Jumping from a line again moves it to the end
+        jumps.push(jump(1, 10));
+        assert_eq!(jumps.back(jump(1, 30), 1), Some(jump(1, 10)));
+        assert_eq!(jumps.back(jump(1, 10), 2), Some(jump(1, 20)));
+        assert_eq!(jumps.back(jump(1, 20), 5), None);
+        assert_eq!(jumps.forward(2), Some(jump(1, 10)));
+        assert_eq!(jumps.forward(1), Some(jump(1, 30)));
+        assert_eq!(jumps.forward(1), None);
+
+        jumps.remove_buffer(2);
+        assert_eq!(jumps.back(jump(1, 30), 2), Some(jump(1, 20)));
+        jumps.push(jump(1, 40));
+        assert_eq!(jumps.back(jump(1, 50), 1), Some(jump(1, 40)));
+    }
+}
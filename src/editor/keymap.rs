@@ -0,0 +1,431 @@
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+// Mappings may lead to further mappings this many times before giving up, like
+// Vim's 'maxmapdepth'
+const MAX_MAP_DEPTH: usize = 1000;
+
+// The modes mappings are defined for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MapMode {
+    Normal,
+    Insert,
+    Visual,
+    // Typing an ex command or a search
+    Command,
+}
+
+impl MapMode {
+    // The letter :map shows before a mapping
+    const fn letter(self) -> char {
+        match self {
+            Self::Normal => 'n',
+            Self::Insert => 'i',
+            Self::Visual => 'v',
+            Self::Command => 'c',
+        }
+    }
+}
+
+// Which modes a :map command is for, and whether the keys it maps to can be
+// mapped again (only the :noremap ones can't)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapCommand {
+    pub modes: &'static [MapMode],
+    pub recursive: bool,
+}
+
+impl MapCommand {
+    pub fn from_name(name: &str) -> Option<Self> {
+        const NORMAL_VISUAL: &[MapMode] = &[MapMode::Normal, MapMode::Visual];
+        let (modes, recursive): (&'static [MapMode], bool) = match name {
+            "map" => (NORMAL_VISUAL, true),
+            "nm" | "nmap" => (&[MapMode::Normal], true),
+            "im" | "imap" => (&[MapMode::Insert], true),
+            "vm" | "vmap" => (&[MapMode::Visual], true),
+            "cm" | "cmap" => (&[MapMode::Command], true),
+            "no" | "noremap" => (NORMAL_VISUAL, false),
+            "nn" | "nnoremap" => (&[MapMode::Normal], false),
+            "ino" | "inoremap" => (&[MapMode::Insert], false),
+            "vn" | "vnoremap" => (&[MapMode::Visual], false),
+            "cno" | "cnoremap" => (&[MapMode::Command], false),
+            _ => return None,
+        };
+        Some(Self { modes, recursive })
+    }
+}
+
+// A key as mappings compare it: the Shift of a character is part of the
+// character already, so it is dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyPress {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl From<KeyEvent> for KeyPress {
+    fn from(event: KeyEvent) -> Self {
+        let mut modifiers = event.modifiers;
+        if matches!(event.code, KeyCode::Char(_)) {
+            modifiers.remove(KeyModifiers::SHIFT);
+        }
+        Self {
+            code: event.code,
+            modifiers,
+        }
+    }
+}
+
+impl KeyPress {
+    const fn new(code: KeyCode) -> Self {
+        Self {
+            code,
+            modifiers: KeyModifiers::NONE,
+        }
+    }
+
+    pub fn to_event(self) -> KeyEvent {
+        KeyEvent::new(self.code, self.modifiers)
+    }
+}
+
+// Names of keys in <> notation, as :map shows them. Other names are accepted
+// too when parsing.
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("Esc", KeyCode::Esc),
+    ("CR", KeyCode::Enter),
+    ("Tab", KeyCode::Tab),
+    ("BS", KeyCode::Backspace),
+    ("Del", KeyCode::Delete),
+    ("Space", KeyCode::Char(' ')),
+    ("lt", KeyCode::Char('<')),
+    ("Bar", KeyCode::Char('|')),
+    ("Bslash", KeyCode::Char('\\')),
+    ("Up", KeyCode::Up),
+    ("Down", KeyCode::Down),
+    ("Left", KeyCode::Left),
+    ("Right", KeyCode::Right),
+    ("Home", KeyCode::Home),
+    ("End", KeyCode::End),
+    ("PageUp", KeyCode::PageUp),
+    ("PageDown", KeyCode::PageDown),
+    ("Insert", KeyCode::Insert),
+    ("Enter", KeyCode::Enter),
+    ("Return", KeyCode::Enter),
+    ("Escape", KeyCode::Esc),
+    ("Backspace", KeyCode::Backspace),
+    ("Delete", KeyCode::Delete),
+];
+
+// Parses keys in Vim's notation, where `<C-s>`, `<Esc>` or `<leader>` stand
+// for a single key (or the leader's keys) and anything else for itself
+pub fn parse_keys(notation: &str, leader: &str) -> Vec<KeyPress> {
+    let mut keys = Vec::new();
+    let mut rest = notation;
+    while let Some(character) = rest.chars().next() {
+        if character == '<'
+            && let Some(end) = rest.find('>')
+            && let Some(special) = rest.get(1..end).and_then(|name| parse_special(name, leader))
+        {
+            keys.extend(special);
+            rest = rest.get(end.saturating_add(1)..).unwrap_or_default();
+            continue;
+        }
+        keys.push(KeyPress::new(KeyCode::Char(character)));
+        rest = rest.get(character.len_utf8()..).unwrap_or_default();
+    }
+    keys
+}
+
+// The keys a <name> stands for, or None if it isn't a key name, in which case
+// the < is taken literally, as in Vim
+fn parse_special(name: &str, leader: &str) -> Option<Vec<KeyPress>> {
+    if name.eq_ignore_ascii_case("leader") {
+        return Some(parse_keys(leader, ""));
+    }
+    let mut modifiers = KeyModifiers::NONE;
+    let mut key_name = name;
+    // C-, S-, A- and M- prefixes, but not a final - as in <C-->
+    while let Some((prefix, rest)) = key_name.split_once('-').filter(|(_, rest)| !rest.is_empty()) {
+        modifiers |= match prefix.to_ascii_uppercase().as_str() {
+            "C" => KeyModifiers::CONTROL,
+            "S" => KeyModifiers::SHIFT,
+            "A" | "M" => KeyModifiers::ALT,
+            _ => return None,
+        };
+        key_name = rest;
+    }
+    let mut chars = key_name.chars();
+    let code = match (chars.next(), chars.next()) {
+        (Some(character), None) => KeyCode::Char(character),
+        _ => KEY_NAMES
+            .iter()
+            .find(|(known, _)| known.eq_ignore_ascii_case(key_name))
+            .map(|(_, code)| *code)
+            .or_else(|| {
+                let number = key_name.strip_prefix(['F', 'f'])?.parse().ok()?;
+                (1..=12).contains(&number).then_some(KeyCode::F(number))
+            })?,
+    };
+    if modifiers.is_empty() && matches!(code, KeyCode::Char(_)) && key_name.chars().count() == 1 {
+        // Only names and modified keys are special: <a> is just those three keys
+        return None;
+    }
+    let code = match code {
+        // The terminal reports Shift with letters as uppercase letters, and
+        // Ctrl with letters as lowercase ones
+        KeyCode::Char(character) if modifiers.contains(KeyModifiers::SHIFT) => {
+            modifiers.remove(KeyModifiers::SHIFT);
+            KeyCode::Char(character.to_ascii_uppercase())
+        }
+        KeyCode::Char(character) if modifiers.contains(KeyModifiers::CONTROL) => {
+            KeyCode::Char(character.to_ascii_lowercase())
+        }
+        KeyCode::Tab if modifiers == KeyModifiers::SHIFT => {
+            modifiers = KeyModifiers::NONE;
+            KeyCode::BackTab
+        }
+        code => code,
+    };
+    Some(vec![KeyPress { code, modifiers }])
+}
+
+// Shows keys in the notation parse_keys reads
+pub fn format_keys(keys: &[KeyPress]) -> String {
+    keys.iter()
+        .map(|key| {
+            let name = KEY_NAMES
+                .iter()
+                .find(|(_, code)| *code == key.code)
+                .map(|(name, _)| (*name).to_string());
+            let name = match key.code {
+                KeyCode::Char(character) if name.is_none() => character.to_string(),
+                KeyCode::F(number) => format!("F{number}"),
+                KeyCode::BackTab => "S-Tab".to_string(),
+                code => name.unwrap_or_else(|| format!("{code:?}")),
+            };
+            let mut prefix = String::new();
+            if key.modifiers.contains(KeyModifiers::CONTROL) {
+                prefix.push_str("C-");
+            }
+            if key.modifiers.contains(KeyModifiers::ALT) {
+                prefix.push_str("A-");
+            }
+            if key.modifiers.contains(KeyModifiers::SHIFT) {
+                prefix.push_str("S-");
+            }
+            if prefix.is_empty() && name.chars().count() == 1 {
+                name
+            } else {
+                format!("<{prefix}{name}>")
+            }
+        })
+        .collect()
+}
+
+struct Mapping {
+    mode: MapMode,
+    lhs: Vec<KeyPress>,
+    rhs: Vec<KeyPress>,
+    recursive: bool,
+}
+
+// What to do next with the keys that have come in
+pub enum Next {
+    // Handle this key
+    Key(KeyEvent),
+    // Nothing to handle until more keys come in or the timeout passes
+    Wait,
+    // The mappings kept expanding to each other, so the keys were dropped
+    Recursive,
+}
+
+// The user's key mappings, and the keys waiting to be handled: those typed
+// that may still turn out to be a mapping, and those mappings expanded to
+#[derive(Default)]
+pub struct Keymap {
+    mappings: Vec<Mapping>,
+    // Each with whether it may be mapped, which keys from :noremap may not
+    pending: VecDeque<(KeyPress, bool)>,
+    last_typed: Option<Instant>,
+    // How many mappings have expanded since a key was last typed
+    depth: usize,
+}
+
+impl Keymap {
+    // Maps the keys `lhs` to `rhs`, replacing any mapping of the same keys in
+    // the same modes
+    pub fn map(&mut self, command: MapCommand, lhs: &str, rhs: &str, leader: &str) -> Result<(), String> {
+        let lhs = parse_keys(lhs, leader);
+        let rhs = parse_keys(rhs, leader);
+        if lhs.is_empty() || rhs.is_empty() {
+            return Err("Argument required".to_string());
+        }
+        for &mode in command.modes {
+            self.mappings
+                .retain(|mapping| mapping.mode != mode || mapping.lhs != lhs);
+            self.mappings.push(Mapping {
+                mode,
+                lhs: lhs.clone(),
+                rhs: rhs.clone(),
+                recursive: command.recursive,
+            });
+        }
+        Ok(())
+    }
+
+    // The mappings for the given modes whose keys start with `prefix`, the way
+    // :map lists them. Mappings that aren't mapped again are marked with *.
+    pub fn list(&self, modes: &[MapMode], prefix: &str, leader: &str) -> Vec<String> {
+        let prefix = parse_keys(prefix, leader);
+        self.mappings
+            .iter()
+            .filter(|mapping| modes.contains(&mapping.mode) && mapping.lhs.starts_with(&prefix))
+            .map(|mapping| {
+                let star = if mapping.recursive { "" } else { "*" };
+                format!(
+                    "{} {} {star}{}",
+                    mapping.mode.letter(),
+                    format_keys(&mapping.lhs),
+                    format_keys(&mapping.rhs)
+                )
+            })
+            .collect()
+    }
+
+    pub fn type_key(&mut self, event: KeyEvent) {
+        self.pending.push_back((KeyPress::from(event), true));
+        self.last_typed = Some(Instant::now());
+        self.depth = 0;
+    }
+
+    // How much longer the typed keys wait for the rest of a mapping, if they do
+    pub fn time_left(&self, timeout: Duration) -> Option<Duration> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        let waited = self.last_typed.map_or(timeout, |time| time.elapsed());
+        Some(timeout.saturating_sub(waited))
+    }
+
+    // The next key to handle in the given mode (None for keys that are never
+    // mapped), expanding mappings along the way. Keys that are the start of a
+    // longer mapping wait, unless `timed_out`.
+    pub fn next(&mut self, mode: Option<MapMode>, timed_out: bool) -> Next {
+        loop {
+            let Some(&(first, remap)) = self.pending.front() else {
+                return Next::Wait;
+            };
+            let Some(mode) = mode.filter(|_| remap) else {
+                self.pending.pop_front();
+                return Next::Key(first.to_event());
+            };
+            let keys: Vec<KeyPress> = self
+                .pending
+                .iter()
+                .take_while(|(_, remap)| *remap)
+                .map(|(key, _)| *key)
+                .collect();
+            let mappings = || self.mappings.iter().filter(|mapping| mapping.mode == mode);
+            let may_continue = mappings()
+                .any(|mapping| mapping.lhs.len() > keys.len() && mapping.lhs.starts_with(&keys));
+            if may_continue && !timed_out {
+                return Next::Wait;
+            }
+            let Some(mapping) = mappings()
+                .filter(|mapping| keys.starts_with(&mapping.lhs))
+                .max_by_key(|mapping| mapping.lhs.len())
+            else {
+                self.pending.pop_front();
+                return Next::Key(first.to_event());
+            };
+            self.depth = self.depth.saturating_add(1);
+            if self.depth > MAX_MAP_DEPTH {
+                self.pending.clear();
+                return Next::Recursive;
+            }
+            // As in Vim, when the keys start with the mapped keys, those aren't
+            // mapped again, so `nmap j jzz` doesn't loop
+            let starts_with_lhs = mapping.rhs.starts_with(&mapping.lhs);
+            let lhs_length = mapping.lhs.len();
+            let expansion: Vec<(KeyPress, bool)> = mapping
+                .rhs
+                .iter()
+                .enumerate()
+                .map(|(index, key)| {
+                    let remap = mapping.recursive && !(starts_with_lhs && index < lhs_length);
+                    (*key, remap)
+                })
+                .collect();
+            self.pending.drain(..lhs_length);
+            for key in expansion.into_iter().rev() {
+                self.pending.push_front(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(keymap: &mut Keymap, keys: &str, mode: MapMode, timed_out: bool) -> String {
+        for key in parse_keys(keys, "") {
+            keymap.type_key(key.to_event());
+        }
+        let mut handled = Vec::new();
+        while let Next::Key(event) = keymap.next(Some(mode), timed_out) {
+            handled.push(KeyPress::from(event));
+        }
+        format_keys(&handled)
+    }
+
+    #[test]
+    fn test_mappings() {
+        let command = |name| MapCommand::from_name(name).unwrap_or(MapCommand {
+            modes: &[],
+            recursive: false,
+        });
+        let mut keymap = Keymap::default();
+        assert_eq!(keymap.map(command("imap"), "jk", "<Esc>", ","), Ok(()));
+        assert_eq!(keymap.map(command("nmap"), "<leader>w", ":w<CR>", ","), Ok(()));
+        assert_eq!(keymap.map(command("nnoremap"), "j", "gj", ","), Ok(()));
+        assert_eq!(keymap.map(command("nmap"), "Q", "jj", ","), Ok(()));
+        assert_eq!(keymap.map(command("nmap"), "a", "b", ","), Ok(()));
+        assert_eq!(keymap.map(command("nmap"), "b", "a", ","), Ok(()));
+
+        // jk waits for the k, then turns into Esc; a j on its own waits for the timeout
+        assert_eq!(typed(&mut keymap, "xj", MapMode::Insert, false), "x");
+        assert_eq!(typed(&mut keymap, "k", MapMode::Insert, false), "<Esc>");
+        assert_eq!(typed(&mut keymap, "j", MapMode::Insert, false), "");
+        assert_eq!(typed(&mut keymap, "", MapMode::Insert, true), "j");
+        assert_eq!(typed(&mut keymap, "jx", MapMode::Insert, false), "jx");
+
+        assert_eq!(typed(&mut keymap, ",w", MapMode::Normal, false), ":w<CR>");
+        // The j of gj isn't mapped again, but those Q maps to are
+        assert_eq!(typed(&mut keymap, "Q", MapMode::Normal, false), "gjgj");
+        assert!(matches!(
+            {
+                keymap.type_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE));
+                keymap.next(Some(MapMode::Normal), false)
+            },
+            Next::Recursive
+        ));
+        assert_eq!(
+            keymap.list(&[MapMode::Insert], "", ","),
+            vec!["i jk <Esc>".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_key_notation() {
+        let keys = parse_keys("<C-S><S-x><A-Left><lt><Space><F5><x>a", "\\");
+        // <x> isn't a key name, so it stays three keys
+        assert_eq!(format_keys(&keys), "<C-s>X<A-Left><lt><Space><F5><lt>x>a");
+        assert_eq!(parse_keys("<leader>", "<Space>"), vec![KeyPress::new(KeyCode::Char(' '))]);
+        assert_eq!(format_keys(&parse_keys("<S-Tab><CR>", "")), "<S-Tab><CR>");
+    }
+}
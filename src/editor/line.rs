@@ -1,53 +1,67 @@
 use std::{fmt, ops::Range};
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
-#[derive(Copy, Clone)]
-enum GraphemeWidth {
-    Half,
-    Full,
+
+use super::annotatedstring::{AnnotatedString, Annotation, AnnotationType};
+
+// Where tabs stop when nothing else is set, as in Vim
+pub const DEFAULT_TAB_STOP: usize = 8;
+
+struct TextFragment {
+    grapheme: String,
+    // The columns the grapheme takes on screen. For a tab, this depends on
+    // how far it is from the next tab stop.
+    rendered_width: usize,
+    replacement: Option<char>,
 }
-impl GraphemeWidth {
-    const fn saturating_add(self, other: usize) -> usize {
-        match self {
-            Self::Half => other.saturating_add(1),
-            Self::Full => other.saturating_add(2),
+
+impl TextFragment {
+    // Adds the fragment as it is shown on screen
+    fn push_to(&self, result: &mut AnnotatedString, annotation_type: Option<AnnotationType>) {
+        if let Some(char) = self.replacement {
+            // A tab is drawn as a space for each column it takes
+            let count = if self.grapheme == "\t" { self.rendered_width } else { 1 };
+            result.push_str(&char.to_string().repeat(count), annotation_type);
+        } else {
+            result.push_str(&self.grapheme, annotation_type);
         }
     }
 }
 
-struct TextFragment {
-    grapheme: String,
-    rendered_width: GraphemeWidth,
-    replacement: Option<char>,
+// The annotation a grapheme has. Later annotations take precedence.
+fn annotation_type_at(annotations: &[Annotation], index: usize) -> Option<AnnotationType> {
+    annotations
+        .iter()
+        .rev()
+        .find(|annotation| annotation.start <= index && index < annotation.end)
+        .map(|annotation| annotation.kind)
 }
 
 #[derive(Default)]
 pub struct Line {
     fragments: Vec<TextFragment>,
+    tab_stop: usize,
 }
 
 impl Line {
     pub fn from(line_str: &str) -> Self {
-        let fragments = Self::str_to_fragments(line_str);
-        Self { fragments }
+        Self::with_tab_stop(line_str, DEFAULT_TAB_STOP)
     }
 
-    fn str_to_fragments(line_str: &str) -> Vec<TextFragment> {
+    // A line whose tabs reach to the next multiple of `tab_stop` columns
+    pub fn with_tab_stop(line_str: &str, tab_stop: usize) -> Self {
+        let fragments = Self::str_to_fragments(line_str, tab_stop);
+        Self { fragments, tab_stop }
+    }
+
+    fn str_to_fragments(line_str: &str, tab_stop: usize) -> Vec<TextFragment> {
+        let mut column: usize = 0;
         line_str
             .graphemes(true)
             .map(|grapheme| {
-                let (replacement, rendered_width) = Self::replacement_character(grapheme)
-                    .map_or_else(
-                        || {
-                            let unicode_width = grapheme.width();
-                            let rendered_width = match unicode_width {
-                                0 | 1 => GraphemeWidth::Half,
-                                _ => GraphemeWidth::Full,
-                            };
-                            (None, rendered_width)
-                        },
-                        |replacement| (Some(replacement), GraphemeWidth::Half),
-                    );
+                let replacement = Self::replacement_character(grapheme);
+                let rendered_width = Self::rendered_width(grapheme, replacement, column, tab_stop);
+                column = column.saturating_add(rendered_width);
 
                 TextFragment {
                     grapheme: grapheme.to_string(),
@@ -58,7 +72,41 @@ impl Line {
             .collect()
     }
 
+    // The columns a grapheme takes when it starts at the given column
+    fn rendered_width(
+        grapheme: &str,
+        replacement: Option<char>,
+        column: usize,
+        tab_stop: usize,
+    ) -> usize {
+        let tab_stop = tab_stop.max(1);
+        if grapheme == "\t" {
+            tab_stop.saturating_sub(column.checked_rem(tab_stop).unwrap_or(0))
+        } else if grapheme.len() == 1 || replacement.is_some() || grapheme.width() <= 1 {
+            1
+        } else {
+            2
+        }
+    }
+
+    // The width of the first graphemes of a line, like width_until but without
+    // splitting the whole line into fragments first
+    pub fn prefix_width(line_str: &str, grapheme_index: usize, tab_stop: usize) -> usize {
+        line_str
+            .graphemes(true)
+            .take(grapheme_index)
+            .fold(0, |column: usize, grapheme| {
+                let replacement = Self::replacement_character(grapheme);
+                column.saturating_add(Self::rendered_width(grapheme, replacement, column, tab_stop))
+            })
+    }
+
     fn replacement_character(for_str: &str) -> Option<char> {
+        if let [byte] = for_str.as_bytes()
+            && byte.is_ascii_graphic()
+        {
+            return None;
+        }
         let width = for_str.width();
         match for_str {
             " " => None,
@@ -77,47 +125,203 @@ impl Line {
         }
     }
 
+    pub fn graphemes(&self) -> impl Iterator<Item = &str> {
+        self.fragments.iter().map(|fragment| fragment.grapheme.as_str())
+    }
+
     pub fn grapheme_count(&self) -> usize {
         self.fragments.len()
     }
 
-    pub fn width_until(&self, grapheme_index: usize) -> usize {
+    // Number of chars that precede the given grapheme
+    pub fn char_index(&self, grapheme_index: usize) -> usize {
         self.fragments
             .iter()
             .take(grapheme_index)
-            .map(|fragment| match fragment.rendered_width {
-                GraphemeWidth::Half => 1,
-                GraphemeWidth::Full => 2,
+            .map(|fragment| fragment.grapheme.chars().count())
+            .sum()
+    }
+
+    // Index of the grapheme that starts at or after the given char
+    pub fn grapheme_index(&self, char_index: usize) -> usize {
+        let mut chars_so_far = 0;
+        for (grapheme_index, fragment) in self.fragments.iter().enumerate() {
+            if chars_so_far >= char_index {
+                return grapheme_index;
+            }
+            chars_so_far = chars_so_far.saturating_add(fragment.grapheme.chars().count());
+        }
+        self.grapheme_count()
+    }
+
+    // The grapheme ranges where the query matches, including overlapping ones.
+    // Matches start and end on grapheme boundaries, so a query never matches
+    // only part of a grapheme.
+    pub fn find_all(&self, query: &str, ignore_case: bool) -> Vec<Range<usize>> {
+        let fold = |text: &str| {
+            if ignore_case {
+                text.to_lowercase()
+            } else {
+                text.to_string()
+            }
+        };
+        let query = fold(query);
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let graphemes: Vec<String> = self
+            .fragments
+            .iter()
+            .map(|fragment| fold(&fragment.grapheme))
+            .collect();
+        (0..graphemes.len())
+            .filter_map(|start| {
+                let mut candidate = String::new();
+                for (index, grapheme) in graphemes.iter().enumerate().skip(start) {
+                    candidate.push_str(grapheme);
+                    if candidate.len() >= query.len() {
+                        return (candidate == query).then(|| start..index.saturating_add(1));
+                    }
+                    if !query.starts_with(&candidate) {
+                        return None;
+                    }
+                }
+                None
+            })
+            .collect()
+    }
+
+    // The grapheme ranges between the line's Unicode word boundaries: words,
+    // and the blanks and punctuation around them
+    pub fn word_bounds(&self) -> Vec<Range<usize>> {
+        let mut start: usize = 0;
+        self.to_string()
+            .split_word_bounds()
+            .map(|segment| {
+                let end = start.saturating_add(segment.graphemes(true).count());
+                let range = start..end;
+                start = end;
+                range
             })
+            .collect()
+    }
+
+    pub fn width_until(&self, grapheme_index: usize) -> usize {
+        self.fragments
+            .iter()
+            .take(grapheme_index)
+            .map(|fragment| fragment.rendered_width)
+            .sum()
+    }
+
+    // Splits the line into rows of at most `width` columns for soft wrapping and
+    // returns the graphemes on each row. A wide grapheme that doesn't fit at the
+    // end of a row moves to the next one. With `line_break`, rows end after
+    // whitespace rather than in the middle of a word, where possible.
+    pub fn wrap(&self, width: usize, line_break: bool) -> Vec<Range<usize>> {
+        let mut rows = Vec::new();
+        let mut start = 0;
+        let mut index = 0;
+        // The width of the graphemes from start up to index
+        let mut column: usize = 0;
+        while let Some(fragment) = self.fragments.get(index) {
+            let end = fragment.rendered_width.saturating_add(column);
+            // Every row gets at least one grapheme, however narrow the window
+            if end <= width || index == start {
+                index = index.saturating_add(1);
+                column = end;
+                continue;
+            }
+            let word_start = line_break
+                .then(|| {
+                    self.fragments
+                        .get(start..index)?
+                        .iter()
+                        .rposition(|fragment| fragment.grapheme.trim().is_empty())
+                        .map(|position| start.saturating_add(position).saturating_add(1))
+                })
+                .flatten();
+            let row_end = word_start.unwrap_or(index);
+            rows.push(start..row_end);
+            column = self.width_between(row_end, index);
+            start = row_end;
+        }
+        rows.push(start..self.grapheme_count());
+        rows
+    }
+
+    // The width of the graphemes from start up to (not including) end
+    pub fn width_between(&self, start: usize, end: usize) -> usize {
+        self.fragments
+            .iter()
+            .take(end)
+            .skip(start)
+            .map(|fragment| fragment.rendered_width)
             .sum()
     }
 
     pub fn get_visible_graphemes(&self, range: Range<usize>) -> String {
+        self.get_annotated_visible_graphemes(range, &[]).to_string()
+    }
+
+    // Like get_visible_graphemes, but carries over the given annotations, whose
+    // start and end are grapheme indices. Later annotations take precedence.
+    pub fn get_annotated_visible_graphemes(
+        &self,
+        range: Range<usize>,
+        annotations: &[Annotation],
+    ) -> AnnotatedString {
+        let mut result = AnnotatedString::default();
         if range.start >= range.end {
-            return String::new();
+            return result;
         }
 
-        let mut result = String::new();
         let mut current_pos = 0;
 
-        for fragment in &self.fragments {
+        for (index, fragment) in self.fragments.iter().enumerate() {
             let fragment_end = fragment.rendered_width.saturating_add(current_pos);
             if current_pos >= range.end {
                 break;
             }
             if fragment_end > range.start {
-                if fragment_end > range.end || current_pos < range.start {
-                    result.push_str("...");
-                } else if let Some(char) = fragment.replacement {
-                    result.push(char);
+                let annotation_type = annotation_type_at(annotations, index);
+                if fragment.grapheme == "\t" {
+                    // A tab cut off at either end still shows as blank columns
+                    let visible = fragment_end
+                        .min(range.end)
+                        .saturating_sub(current_pos.max(range.start));
+                    result.push_str(&" ".repeat(visible), annotation_type);
+                } else if fragment_end > range.end || current_pos < range.start {
+                    result.push_str("...", annotation_type);
                 } else {
-                    result.push_str(&fragment.grapheme);
+                    fragment.push_to(&mut result, annotation_type);
                 }
             }
             current_pos = fragment_end;
         }
         result
     }
+
+    // The graphemes in a range of grapheme indices (such as a wrapped row), with
+    // the given annotations
+    pub fn get_annotated_graphemes(
+        &self,
+        range: Range<usize>,
+        annotations: &[Annotation],
+    ) -> AnnotatedString {
+        let mut result = AnnotatedString::default();
+        for (index, fragment) in self
+            .fragments
+            .iter()
+            .enumerate()
+            .take(range.end)
+            .skip(range.start)
+        {
+            fragment.push_to(&mut result, annotation_type_at(annotations, index));
+        }
+        result
+    }
+
     pub fn append_char(&mut self, character: char) {
         self.insert_char(character, self.grapheme_count());
     }
@@ -136,7 +340,7 @@ impl Line {
         if at >= self.fragments.len() {
             result.push(character);
         }
-        self.fragments = Self::str_to_fragments(&result);
+        self.fragments = Self::str_to_fragments(&result, self.tab_stop);
     }
 
     pub fn delete(&mut self, at: usize) {
@@ -147,28 +351,12 @@ impl Line {
                 result.push_str(&fragment.grapheme);
             }
         }
-        self.fragments = Self::str_to_fragments(&result);
+        self.fragments = Self::str_to_fragments(&result, self.tab_stop);
     }
 
     pub fn delete_last(&mut self) {
         self.delete(self.grapheme_count().saturating_sub(1));
     }
-    
-    pub fn append(&mut self, other: &Line) {
-        let mut concat = self.to_string();
-        concat.push_str(&other.to_string());
-        self.fragments = Self::str_to_fragments(&concat);
-    }
-
-    pub fn split(&mut self, at: usize) -> Self {
-        if at > self.fragments.len() {
-            return Self::default();
-        }
-        let remainder = self.fragments.split_off(at);
-        Self {
-            fragments: remainder,
-        }
-    }
 }
 
 impl fmt::Display for Line {
@@ -181,3 +369,48 @@ impl fmt::Display for Line {
         write!(f, "{result}")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        let line = Line::from("one two three");
+        assert_eq!(line.wrap(5, false), vec![0..5, 5..10, 10..13]);
+        assert_eq!(line.wrap(5, true), vec![0..4, 4..8, 8..13]);
+        assert_eq!(line.wrap(20, true), vec![0..13]);
+        // A wide grapheme that would stick out moves to the next row
+        let line = Line::from("abc漢字");
+        assert_eq!(line.wrap(4, false), vec![0..3, 3..5]);
+        assert_eq!(line.wrap(1, false), vec![0..1, 1..2, 2..3, 3..4, 4..5]);
+        assert_eq!(Line::from("").wrap(4, false), vec![0..0]);
+        assert_eq!(
+            line.get_annotated_graphemes(2..4, &[]).to_string(),
+            "c漢"
+        );
+    }
+
+    #[test]
+    fn test_wrap_long_line() {
+        // Rows break after the last blank that fits, all the way along
+        let line = Line::from(&"abcd ".repeat(40_000));
+        let rows = line.wrap(80, true);
+        assert_eq!(rows.len(), 2500);
+        assert_eq!(rows.get(1), Some(&(80..160)));
+        assert_eq!(rows.last(), Some(&(199_920..200_000)));
+    }
+
+    #[test]
+    fn test_tab_width() {
+        // Tabs reach to the next tab stop, so their width depends on where they start
+        let line = Line::with_tab_stop("\tab\tc", 4);
+        assert_eq!(line.width_until(1), 4);
+        assert_eq!(line.width_until(4), 8);
+        assert_eq!(line.width(), 9);
+        assert_eq!(line.get_visible_graphemes(0..9), "    ab  c");
+        assert_eq!(line.get_visible_graphemes(2..7), "  ab ");
+        assert_eq!(Line::from("a\t").width(), 8);
+        assert_eq!(Line::from("\u{7}").width(), 1);
+    }
+}
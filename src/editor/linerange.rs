@@ -0,0 +1,165 @@
+// A line an ex command refers to, before it is resolved against the buffer
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Address {
+    CurrentLine,    // .
+    LastLine,       // $
+    Number(usize),  // 42, counting from 1
+    SelectionStart, // '<
+    SelectionEnd,   // '>
+}
+
+// An address followed by relative offsets, e.g. `.+3` or `$-1`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LineAddress {
+    pub address: Address,
+    pub offset: isize,
+}
+
+impl LineAddress {
+    const fn new(address: Address) -> Self {
+        Self { address, offset: 0 }
+    }
+
+    // Parses an address at the start of the input and returns it with the rest
+    // of the input
+    fn parse(input: &str) -> Option<(Self, &str)> {
+        let (address, rest) = if let Some(rest) = input.strip_prefix('.') {
+            (Address::CurrentLine, rest)
+        } else if let Some(rest) = input.strip_prefix('$') {
+            (Address::LastLine, rest)
+        } else if let Some(rest) = input.strip_prefix("'<") {
+            (Address::SelectionStart, rest)
+        } else if let Some(rest) = input.strip_prefix("'>") {
+            (Address::SelectionEnd, rest)
+        } else if let Some((number, rest)) = Self::parse_number(input) {
+            (Address::Number(number), rest)
+        } else if input.starts_with(['+', '-']) {
+            // A bare offset is relative to the current line
+            (Address::CurrentLine, input)
+        } else {
+            return None;
+        };
+        let mut line_address = Self::new(address);
+        let mut rest = rest;
+        while let Some(sign) = rest.chars().next().filter(|sign| matches!(sign, '+' | '-')) {
+            let (amount, after) = Self::parse_number(&rest[1..]).unwrap_or((1, &rest[1..]));
+            let amount = isize::try_from(amount).unwrap_or(isize::MAX);
+            line_address.offset = if sign == '+' {
+                line_address.offset.saturating_add(amount)
+            } else {
+                line_address.offset.saturating_sub(amount)
+            };
+            rest = after;
+        }
+        Some((line_address, rest))
+    }
+
+    fn parse_number(input: &str) -> Option<(usize, &str)> {
+        let digits = input
+            .find(|character: char| !character.is_ascii_digit())
+            .unwrap_or(input.len());
+        let number = input.get(..digits)?.parse().ok()?;
+        Some((number, input.get(digits..)?))
+    }
+
+    fn resolve(self, lines: &LineContext) -> Result<usize, String> {
+        let line_index = match self.address {
+            Address::CurrentLine => lines.current,
+            Address::LastLine => lines.count.saturating_sub(1),
+            Address::Number(number) => number.saturating_sub(1),
+            Address::SelectionStart | Address::SelectionEnd => {
+                let (first, last) = lines.selection.ok_or("Mark not set")?;
+                if self.address == Address::SelectionStart {
+                    first
+                } else {
+                    last
+                }
+            }
+        };
+        line_index
+            .checked_add_signed(self.offset)
+            .filter(|&line_index| line_index < lines.count)
+            .ok_or_else(|| "Invalid range".to_string())
+    }
+}
+
+// What addresses are resolved against
+pub struct LineContext {
+    pub current: usize,
+    pub count: usize,
+    pub selection: Option<(usize, usize)>,
+}
+
+// The lines an ex command acts upon, e.g. `%`, `'<,'>` or `.,$-1`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LineRange {
+    pub start: LineAddress,
+    pub end: LineAddress,
+}
+
+impl Default for LineRange {
+    // Commands without a range act on the current line
+    fn default() -> Self {
+        Self {
+            start: LineAddress::new(Address::CurrentLine),
+            end: LineAddress::new(Address::CurrentLine),
+        }
+    }
+}
+
+impl LineRange {
+    // Parses a range at the start of the input and returns it with the rest of
+    // the input, or None if the input doesn't start with a range
+    pub fn parse(input: &str) -> Option<(Self, &str)> {
+        if let Some(rest) = input.strip_prefix('%') {
+            let range = Self {
+                start: LineAddress::new(Address::Number(1)),
+                end: LineAddress::new(Address::LastLine),
+            };
+            return Some((range, rest));
+        }
+        let (start, rest) = LineAddress::parse(input)?;
+        let Some(rest) = rest.strip_prefix(',') else {
+            return Some((Self { start, end: start }, rest));
+        };
+        let (end, rest) = LineAddress::parse(rest).unwrap_or((start, rest));
+        Some((Self { start, end }, rest))
+    }
+
+    // Returns the first and last line index of the range
+    pub fn resolve(self, lines: &LineContext) -> Result<(usize, usize), String> {
+        let start = self.start.resolve(lines)?;
+        let end = self.end.resolve(lines)?;
+        Ok((start.min(end), start.max(end)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(input: &str) -> Result<(usize, usize), String> {
+        let lines = LineContext {
+            current: 4,
+            count: 10,
+            selection: Some((1, 2)),
+        };
+        let (range, rest) = LineRange::parse(input).ok_or("no range")?;
+        assert_eq!(rest, "s");
+        range.resolve(&lines)
+    }
+
+    #[test]
+    fn test_resolve_ranges() {
+        assert_eq!(resolve("%s"), Ok((0, 9)));
+        assert_eq!(resolve(".s"), Ok((4, 4)));
+        assert_eq!(resolve("3,$s"), Ok((2, 9)));
+        assert_eq!(resolve("'<,'>s"), Ok((1, 2)));
+        assert_eq!(resolve(".,+2s"), Ok((4, 6)));
+        assert_eq!(resolve("$-2,$s"), Ok((7, 9)));
+        assert_eq!(resolve("-s"), Ok((3, 3)));
+        assert_eq!(resolve("5,2s"), Ok((1, 4)));
+        assert_eq!(resolve("11s"), Err("Invalid range".to_string()));
+        assert_eq!(LineRange::parse("s/a/b/"), None);
+    }
+}
@@ -0,0 +1,179 @@
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyEventKind};
+use std::collections::VecDeque;
+
+use super::keymap::{KeyPress, format_keys, parse_keys};
+
+// A macro may play further macros this many times before giving up, like
+// MAX_MAP_DEPTH for mappings, so that one that plays itself ends
+const MAX_MACRO_DEPTH: usize = 1000;
+
+// Keys typed into a register with q{reg}...q and played back with @{reg}. The
+// register holds them as text in key notation, so that they can be edited.
+#[derive(Default)]
+pub struct Macros {
+    // The register being recorded into, and the keys typed so far
+    recording: Option<(char, Vec<KeyPress>)>,
+    // Keys of the macros being played that are yet to be typed
+    queue: VecDeque<KeyEvent>,
+    playing: bool,
+    // How many macros the one being played has queued so far
+    depth: usize,
+    // The register @@ plays
+    last_played: Option<char>,
+}
+
+impl Macros {
+    pub fn start_recording(&mut self, register: char) {
+        self.recording = Some((register, Vec::new()));
+    }
+
+    // The register being recorded into
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|(register, _)| *register)
+    }
+
+    // Keeps a key typed while recording
+    pub fn record(&mut self, event: &Event) {
+        if let Some((_, keys)) = &mut self.recording
+            && let Event::Key(key_event) = event
+            && key_event.kind == KeyEventKind::Press
+        {
+            keys.push(KeyPress::from(*key_event));
+        }
+    }
+
+    // Stops recording, returning the register and the keys typed into it,
+    // without the q that stopped it
+    pub fn stop_recording(&mut self) -> Option<(char, String)> {
+        let (register, mut keys) = self.recording.take()?;
+        if keys.last().is_some_and(|key| key.to_event().code == KeyCode::Char('q')) {
+            keys.pop();
+        }
+        Some((register, format_keys(&keys)))
+    }
+
+    pub const fn last_played(&self) -> Option<char> {
+        self.last_played
+    }
+
+    // Queues the keys in a register's text to be typed `count` times, ahead of
+    // what is left of the macro that plays it, if any. A line break in the
+    // text is Enter. Returns false, dropping all the keys left, if the macro
+    // being played has played too many others.
+    pub fn queue(&mut self, register: char, text: &str, leader: &str, count: usize) -> bool {
+        if self.playing {
+            self.depth = self.depth.saturating_add(1);
+            if self.depth > MAX_MACRO_DEPTH {
+                self.abort();
+                return false;
+            }
+        }
+        let keys: Vec<KeyEvent> = parse_keys(text, leader)
+            .into_iter()
+            .map(|key| {
+                let event = key.to_event();
+                if event.code == KeyCode::Char('\n') {
+                    KeyEvent::from(KeyCode::Enter)
+                } else {
+                    event
+                }
+            })
+            .collect();
+        for _ in 0..count {
+            for key in keys.iter().rev() {
+                self.queue.push_front(*key);
+            }
+        }
+        self.last_played = Some(register);
+        true
+    }
+
+    // Whether the caller should type the queued keys: false if a macro is
+    // already being played, as its keys are typed by whoever started it
+    pub fn begin_playing(&mut self) -> bool {
+        let begun = !std::mem::replace(&mut self.playing, true);
+        if begun {
+            self.depth = 0;
+        }
+        begun
+    }
+
+    // The next key to type, or None once all the macros are played
+    pub fn next_key(&mut self) -> Option<KeyEvent> {
+        let key = self.queue.pop_front();
+        self.playing = key.is_some();
+        key
+    }
+
+    // Drops the keys left, when something in a macro fails
+    pub fn abort(&mut self) {
+        self.queue.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crossterm::event::KeyModifiers;
+
+    fn key(code: KeyCode) -> Event {
+        Event::Key(KeyEvent::new(code, KeyModifiers::NONE))
+    }
+
+    fn typed(macros: &mut Macros) -> Vec<KeyCode> {
+        let mut codes = Vec::new();
+        while let Some(key) = macros.next_key() {
+            codes.push(key.code);
+        }
+        codes
+    }
+
+    #[test]
+    fn test_record_and_play() {
+        let mut macros = Macros::default();
+        macros.record(&key(KeyCode::Char('x')));
+        macros.start_recording('a');
+        assert_eq!(macros.recording(), Some('a'));
+        for code in [KeyCode::Char('i'), KeyCode::Char('<'), KeyCode::Esc, KeyCode::Char('q')] {
+            macros.record(&key(code));
+        }
+        assert_eq!(macros.stop_recording(), Some(('a', "i<lt><Esc>".to_string())));
+        assert_eq!(macros.recording(), None);
+
+        assert!(macros.begin_playing());
+        macros.queue('a', "x\n", "", 2);
+        assert_eq!(macros.next_key().map(|key| key.code), Some(KeyCode::Char('x')));
+        // A macro played from another goes before the rest of it
+        assert!(!macros.begin_playing());
+        macros.queue('b', "<C-a>", "", 1);
+        assert_eq!(macros.last_played(), Some('b'));
+        assert_eq!(
+            typed(&mut macros),
+            [KeyCode::Char('a'), KeyCode::Enter, KeyCode::Char('x'), KeyCode::Enter]
+        );
+        assert!(macros.begin_playing());
+        macros.queue('a', "abc", "", 1);
+        macros.abort();
+        assert!(typed(&mut macros).is_empty());
+    }
+
+    #[test]
+    fn test_recursion_limit() {
+        let mut macros = Macros::default();
+        assert!(macros.queue('a', "x@a", "", 1));
+        assert!(macros.begin_playing());
+        let mut typed = 0;
+        while let Some(key) = macros.next_key() {
+            typed += 1;
+            if key.code == KeyCode::Char('a') && !macros.queue('a', "x@a", "", 1) {
+                break;
+            }
+        }
+        assert_eq!(typed, (MAX_MACRO_DEPTH + 1) * 3);
+        assert_eq!(macros.next_key(), None);
+        // Playing again starts counting afresh
+        assert!(macros.queue('a', "x", "", 1));
+        assert!(macros.begin_playing());
+        assert!(macros.queue('b', "y", "", 1));
+    }
+}
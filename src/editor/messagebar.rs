@@ -1,12 +1,14 @@
 use std::{io::Error, time::{Duration, Instant}};
 
-use super:: {Size, Terminal,UIComponent};
+use super:: {Position, Size, Terminal,UIComponent, theme::Scope};
 
 const DEFAULT_DURATION: Duration = Duration::from_secs(5);
 
 struct Message {
     text: String,
     time: Instant,
+    // Questions waiting for an answer stay until they are replaced
+    expires: bool,
 }
 
 impl Default for Message {
@@ -14,31 +16,58 @@ impl Default for Message {
         Self {
             text: String::new(),
             time: Instant::now(),   
+            expires: true,
         }
     }
 }
 
 impl Message {
-    fn is_expired(&self) -> bool {
-        Instant::now().duration_since(self.time) > DEFAULT_DURATION
+    fn is_expired(&self, timeout: Duration) -> bool {
+        self.expires && Instant::now().duration_since(self.time) > timeout
     }
 }
-#[derive(Default)]
 pub struct MessageBar {
     current_message: Message,
     requires_redraw: bool,
     cleared_after_expiry: bool,
+    size: Size,
+    // How long messages stay ('messagetimeout')
+    timeout: Duration,
+}
+
+impl Default for MessageBar {
+    fn default() -> Self {
+        Self {
+            current_message: Message::default(),
+            requires_redraw: false,
+            cleared_after_expiry: false,
+            size: Size::default(),
+            timeout: DEFAULT_DURATION,
+        }
+    }
 }
 
 impl MessageBar{
+    pub const fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+
     pub fn update_message(&mut self, new_message: &str) {
         self.current_message = Message {
             text: new_message.to_string(),
             time: Instant::now(),
+            expires: true,
         };
         self.cleared_after_expiry = false;
         self.requires_redraw = true;
     }
+
+    // Shows a question that stays until the next message
+    pub fn update_prompt(&mut self, prompt: &str) {
+        self.update_message(prompt);
+        self.current_message.expires = false;
+    }
 }
 
 impl UIComponent for MessageBar {
@@ -47,22 +76,22 @@ impl UIComponent for MessageBar {
     }
 
     fn requires_redraw(&self) -> bool {
-        (!self.cleared_after_expiry && self.current_message.is_expired()) || self.requires_redraw
+        (!self.cleared_after_expiry && self.current_message.is_expired(self.timeout)) || self.requires_redraw
     }
 
-    fn set_size(&mut self, _: Size) {
-       
+    fn set_size(&mut self, size: Size) {
+        self.size = size;
     }
 
-    fn draw(&mut self, origin: usize) -> Result<(), Error>  {
-        if self.current_message.is_expired() {
+    fn draw(&mut self, terminal: &mut dyn Terminal, origin: Position) -> Result<(), Error>  {
+        if self.current_message.is_expired(self.timeout) {
             self.cleared_after_expiry = true;
         }
-        let message = if self.current_message.is_expired(){
+        let message = if self.current_message.is_expired(self.timeout){
             ""
         } else {
             &self.current_message.text
         };
-        Terminal::print_row(origin, message)
+        terminal.print_styled_at(origin, self.size.width, message, Scope::MessageBar)
     }
 }
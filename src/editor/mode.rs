@@ -1,16 +1,59 @@
+use super::view::SelectionKind;
+
 #[derive(Default, PartialEq, Clone, Copy)]
 pub enum Mode {
     #[default]
     Normal,
     Insert,
+    Visual,
+    VisualLine,
+    VisualBlock,
+    // The file explorer has the focus
+    Explorer,
 }
 
 impl Mode {
-    pub fn is_normal(&self) -> bool {
+    pub fn is_normal(self) -> bool {
         matches!(self, Mode::Normal)
     }
 
-    pub fn is_insert(&self) -> bool {
+    pub fn is_insert(self) -> bool {
         matches!(self, Mode::Insert)
     }
-}
\ No newline at end of file
+
+    pub fn is_explorer(self) -> bool {
+        matches!(self, Mode::Explorer)
+    }
+
+    pub fn is_visual(self) -> bool {
+        self.selection_kind().is_some()
+    }
+
+    pub const fn selection_kind(self) -> Option<SelectionKind> {
+        match self {
+            Mode::Visual => Some(SelectionKind::Chars),
+            Mode::VisualLine => Some(SelectionKind::Lines),
+            Mode::VisualBlock => Some(SelectionKind::Block),
+            Mode::Normal | Mode::Insert | Mode::Explorer => None,
+        }
+    }
+
+    pub const fn visual(kind: SelectionKind) -> Self {
+        match kind {
+            SelectionKind::Chars => Mode::Visual,
+            SelectionKind::Lines => Mode::VisualLine,
+            SelectionKind::Block => Mode::VisualBlock,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Mode::Normal => "NORMAL",
+            Mode::Insert => "INSERT",
+            Mode::Visual => "VISUAL",
+            Mode::VisualLine => "VISUAL LINE",
+            Mode::VisualBlock => "VISUAL BLOCK",
+            Mode::Explorer => "EXPLORER",
+        }
+    }
+}
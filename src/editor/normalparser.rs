@@ -0,0 +1,780 @@
+use crossterm::event::{
+    KeyCode::{Char, Tab},
+    KeyEvent, KeyModifiers,
+};
+
+use super::{
+    command::{Direction, Motion, Operator, TextObject, TextObjectKind, WindowCommand},
+    registers::Registers,
+    view::{SearchDirection, SelectionKind},
+};
+
+// A key as seen by the Normal mode grammar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    Char(char),
+    Ctrl(char),
+}
+
+impl TryFrom<KeyEvent> for Key {
+    type Error = String;
+
+    fn try_from(event: KeyEvent) -> Result<Self, Self::Error> {
+        match (event.code, event.modifiers) {
+            (Char(character), KeyModifiers::NONE | KeyModifiers::SHIFT) => Ok(Self::Char(character)),
+            (Char(character), KeyModifiers::CONTROL) => Ok(Self::Ctrl(character)),
+            // Terminals send Ctrl-I as Tab
+            (Tab, KeyModifiers::NONE) => Ok(Self::Ctrl('i')),
+            _ => Err(format!(
+                "Unsupported key code {:?} with modifiers {:?}",
+                event.code, event.modifiers
+            )),
+        }
+    }
+}
+
+// What an operator acts upon: the text covered by a motion, whole lines (dd,
+// yy, >>...) or a text object (diw, ca")
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Motion(Motion),
+    Lines,
+    Object(TextObject),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InsertPosition {
+    BeforeCaret, // i
+    LineStart,   // I
+    AfterCaret,  // a
+    LineEnd,     // A
+    LineBelow,   // o
+    LineAbove,   // O
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalCommand {
+    Move {
+        motion: Motion,
+        count: Option<usize>,
+    },
+    Operate {
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+        register: Option<char>,
+    },
+    Put {
+        before: bool,
+        count: usize,
+        register: Option<char>,
+    },
+    // i, a, o... with how many times the text typed goes in
+    Insert {
+        position: InsertPosition,
+        count: usize,
+    },
+    Visual(SelectionKind),
+    Search(SearchDirection),
+    Undo(usize),
+    Redo(usize),
+    // `.`, with the count typed before it
+    Repeat(Option<usize>),
+    // q{reg}
+    Record(char),
+    // @{reg}, or @@ (None) for the register played last
+    Play {
+        register: Option<char>,
+        count: usize,
+    },
+    // m{a-zA-Z}
+    SetMark(char),
+    // Ctrl-O goes back through the jump list, Ctrl-I forward
+    Jump {
+        back: bool,
+        count: usize,
+    },
+    Window(WindowCommand),
+}
+
+// Commands typed while a selection is active; operators act on the selection
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VisualCommand {
+    Move {
+        motion: Motion,
+        count: Option<usize>,
+    },
+    Operate {
+        operator: Operator,
+        register: Option<char>,
+    },
+    SwapEnds,
+    Select(SelectionKind),
+    // Selects a text object, e.g. vi(
+    Object {
+        object: TextObject,
+        count: Option<usize>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseResult<C = NormalCommand> {
+    Pending,
+    Invalid,
+    Complete(C),
+}
+
+enum Failure {
+    Pending,
+    Invalid,
+}
+
+struct Input<'a> {
+    keys: &'a [Key],
+    position: usize,
+}
+
+impl Input<'_> {
+    fn peek_at(&self, offset: usize) -> Option<Key> {
+        self.keys.get(self.position.saturating_add(offset)).copied()
+    }
+
+    fn next(&mut self) -> Result<Key, Failure> {
+        let key = self.peek_at(0).ok_or(Failure::Pending)?;
+        self.position = self.position.saturating_add(1);
+        Ok(key)
+    }
+
+    fn multiplied_count(&mut self, count: Option<usize>) -> Option<usize> {
+        match (count, self.count()) {
+            (None, None) => None,
+            (count, other) => Some(count.unwrap_or(1).saturating_mul(other.unwrap_or(1))),
+        }
+    }
+
+    fn count(&mut self) -> Option<usize> {
+        let mut count: Option<usize> = None;
+        while let Some(Key::Char(digit @ '0'..='9')) = self.peek_at(0) {
+            // A leading 0 is the motion to the start of the line, not a count
+            if count.is_none() && digit == '0' {
+                break;
+            }
+            let value = digit.to_digit(10).and_then(|value| usize::try_from(value).ok());
+            count = Some(
+                count
+                    .unwrap_or(0)
+                    .saturating_mul(10)
+                    .saturating_add(value.unwrap_or(0)),
+            );
+            self.position = self.position.saturating_add(1);
+        }
+        count
+    }
+}
+
+// Accumulates keys typed in Normal mode until they form a complete command
+#[derive(Default)]
+pub struct NormalParser {
+    keys: Vec<Key>,
+}
+
+impl NormalParser {
+    pub fn push(&mut self, key: Key) -> ParseResult {
+        self.keys.push(key);
+        let result = Self::parse(&self.keys);
+        if result != ParseResult::Pending {
+            self.keys.clear();
+        }
+        result
+    }
+
+    pub fn push_visual(&mut self, key: Key) -> ParseResult<VisualCommand> {
+        self.keys.push(key);
+        let result = Self::parse_visual(&self.keys);
+        if result != ParseResult::Pending {
+            self.keys.clear();
+        }
+        result
+    }
+
+    pub fn reset(&mut self) {
+        self.keys.clear();
+    }
+
+    pub fn is_pending(&self) -> bool {
+        !self.keys.is_empty()
+    }
+
+    // Mappings apply at the start of a command, or after its count
+    pub fn accepts_mappings(&self) -> bool {
+        self.keys
+            .iter()
+            .all(|key| matches!(key, Key::Char('0'..='9')))
+    }
+
+    pub fn parse(keys: &[Key]) -> ParseResult {
+        Self::finish(Self::parse_command(&mut Input { keys, position: 0 }))
+    }
+
+    pub fn parse_visual(keys: &[Key]) -> ParseResult<VisualCommand> {
+        Self::finish(Self::parse_visual_command(&mut Input { keys, position: 0 }))
+    }
+
+    fn finish<C>(result: Result<C, Failure>) -> ParseResult<C> {
+        match result {
+            Ok(command) => ParseResult::Complete(command),
+            Err(Failure::Pending) => ParseResult::Pending,
+            Err(Failure::Invalid) => ParseResult::Invalid,
+        }
+    }
+
+    // An optional count and register prefix, e.g. 2"a3
+    fn parse_prefix(input: &mut Input) -> Result<(Option<usize>, Option<char>), Failure> {
+        let mut count = input.count();
+        let mut register = None;
+        if input.peek_at(0) == Some(Key::Char('"')) {
+            input.next()?;
+            match input.next()? {
+                Key::Char(name) if Registers::is_valid_name(name) => register = Some(name),
+                _ => return Err(Failure::Invalid),
+            }
+            count = input.multiplied_count(count);
+        }
+        Ok((count, register))
+    }
+
+    fn parse_visual_command(input: &mut Input) -> Result<VisualCommand, Failure> {
+        let (count, register) = Self::parse_prefix(input)?;
+        let operator = match input.peek_at(0).ok_or(Failure::Pending)? {
+            Key::Char('v') => return Ok(VisualCommand::Select(SelectionKind::Chars)),
+            Key::Char('V') => return Ok(VisualCommand::Select(SelectionKind::Lines)),
+            Key::Ctrl('v') => return Ok(VisualCommand::Select(SelectionKind::Block)),
+            Key::Char('o') => return Ok(VisualCommand::SwapEnds),
+            Key::Char('d' | 'x') => Operator::Delete,
+            Key::Char('c' | 's') => Operator::Change,
+            Key::Char('y') => Operator::Yank,
+            Key::Char('>') => Operator::ShiftRight,
+            Key::Char('<') => Operator::ShiftLeft,
+            Key::Char('u') => Operator::Lowercase,
+            Key::Char('U') => Operator::Uppercase,
+            Key::Char('~') => Operator::ToggleCase,
+            Key::Char('i' | 'a') => {
+                return Ok(VisualCommand::Object {
+                    object: Self::parse_object(input)?,
+                    count,
+                });
+            }
+            _ => {
+                return Ok(VisualCommand::Move {
+                    motion: Self::parse_motion(input)?,
+                    count,
+                });
+            }
+        };
+        Ok(VisualCommand::Operate { operator, register })
+    }
+
+    fn parse_command(input: &mut Input) -> Result<NormalCommand, Failure> {
+        let (count, register) = Self::parse_prefix(input)?;
+        let operate = |operator, target| NormalCommand::Operate {
+            operator,
+            target,
+            count,
+            register,
+        };
+        let insert = |position| NormalCommand::Insert {
+            position,
+            count: count.unwrap_or(1),
+        };
+        let command = match input.peek_at(0).ok_or(Failure::Pending)? {
+            Key::Char('i') => insert(InsertPosition::BeforeCaret),
+            Key::Char('I') => insert(InsertPosition::LineStart),
+            Key::Char('a') => insert(InsertPosition::AfterCaret),
+            Key::Char('A') => insert(InsertPosition::LineEnd),
+            Key::Char('o') => insert(InsertPosition::LineBelow),
+            Key::Char('O') => insert(InsertPosition::LineAbove),
+            Key::Char('v') => NormalCommand::Visual(SelectionKind::Chars),
+            Key::Char('V') => NormalCommand::Visual(SelectionKind::Lines),
+            Key::Ctrl('v') => NormalCommand::Visual(SelectionKind::Block),
+            Key::Char('/') => NormalCommand::Search(SearchDirection::Forward),
+            Key::Char('?') => NormalCommand::Search(SearchDirection::Backward),
+            Key::Char('u') => NormalCommand::Undo(count.unwrap_or(1)),
+            Key::Ctrl('r') => NormalCommand::Redo(count.unwrap_or(1)),
+            Key::Char('.') => NormalCommand::Repeat(count),
+            Key::Char(key @ ('q' | '@')) => {
+                input.next()?;
+                let register = match input.next()? {
+                    Key::Char('@') if key == '@' => None,
+                    Key::Char(name) if Registers::is_valid_name(name) => Some(name),
+                    _ => return Err(Failure::Invalid),
+                };
+                return Ok(match register {
+                    Some(name) if key == 'q' => NormalCommand::Record(name),
+                    register => NormalCommand::Play {
+                        register,
+                        count: count.unwrap_or(1),
+                    },
+                });
+            }
+            Key::Char('m') => {
+                input.next()?;
+                let name = Self::mark_name(input.next()?).ok_or(Failure::Invalid)?;
+                return Ok(NormalCommand::SetMark(name));
+            }
+            Key::Ctrl(key @ ('o' | 'i')) => NormalCommand::Jump {
+                back: key == 'o',
+                count: count.unwrap_or(1),
+            },
+            Key::Ctrl('w') => {
+                input.next()?;
+                return Self::parse_window_command(input, count).map(NormalCommand::Window);
+            }
+            Key::Char('x') => operate(Operator::Delete, Target::Motion(Motion::Right)),
+            Key::Char('X') => operate(Operator::Delete, Target::Motion(Motion::Left)),
+            Key::Char('D') => operate(Operator::Delete, Target::Motion(Motion::LineEnd)),
+            Key::Char('C') => operate(Operator::Change, Target::Motion(Motion::LineEnd)),
+            Key::Char('s') => operate(Operator::Change, Target::Motion(Motion::Right)),
+            Key::Char('S') => operate(Operator::Change, Target::Lines),
+            Key::Char('Y') => operate(Operator::Yank, Target::Lines),
+            Key::Char(key @ ('p' | 'P')) => NormalCommand::Put {
+                before: key == 'P',
+                count: count.unwrap_or(1),
+                register,
+            },
+            _ => {
+                return match Self::parse_operator(input)? {
+                    Some(operator) => {
+                        Self::parse_operator_target(input, operator, count, register)
+                    }
+                    None => Ok(NormalCommand::Move {
+                        motion: Self::parse_motion(input)?,
+                        count,
+                    }),
+                };
+            }
+        };
+        Ok(command)
+    }
+
+    // The key after Ctrl-W. Control keys work as well, e.g. Ctrl-W Ctrl-V.
+    fn parse_window_command(
+        input: &mut Input,
+        count: Option<usize>,
+    ) -> Result<WindowCommand, Failure> {
+        let amount = isize::try_from(count.unwrap_or(1)).unwrap_or(isize::MAX);
+        let command = match input.next()? {
+            Key::Char('s' | 'S') | Key::Ctrl('s') => WindowCommand::Split { vertical: false },
+            Key::Char('v') | Key::Ctrl('v') => WindowCommand::Split { vertical: true },
+            Key::Char('h') | Key::Ctrl('h') => WindowCommand::Focus(Direction::Left),
+            Key::Char('j') | Key::Ctrl('j') => WindowCommand::Focus(Direction::Down),
+            Key::Char('k') | Key::Ctrl('k') => WindowCommand::Focus(Direction::Up),
+            Key::Char('l') | Key::Ctrl('l') => WindowCommand::Focus(Direction::Right),
+            Key::Char('w') | Key::Ctrl('w') => WindowCommand::Next { backwards: false },
+            Key::Char('W') => WindowCommand::Next { backwards: true },
+            Key::Char('c') => WindowCommand::Close,
+            Key::Char('q') | Key::Ctrl('q') => WindowCommand::Quit,
+            Key::Char('o') | Key::Ctrl('o') => WindowCommand::Only,
+            Key::Char(key @ ('+' | '-' | '>' | '<')) => WindowCommand::Resize {
+                vertical: matches!(key, '>' | '<'),
+                amount: if matches!(key, '+' | '>') {
+                    amount
+                } else {
+                    amount.saturating_neg()
+                },
+            },
+            Key::Char('_') | Key::Ctrl('_') => WindowCommand::SetSize {
+                vertical: false,
+                size: count,
+            },
+            Key::Char('|') => WindowCommand::SetSize {
+                vertical: true,
+                size: count,
+            },
+            Key::Char('=') => WindowCommand::Equalize,
+            _ => return Err(Failure::Invalid),
+        };
+        Ok(command)
+    }
+
+    fn parse_operator(input: &mut Input) -> Result<Option<Operator>, Failure> {
+        let (operator, length) = match input.peek_at(0) {
+            Some(Key::Char('d')) => (Operator::Delete, 1),
+            Some(Key::Char('c')) => (Operator::Change, 1),
+            Some(Key::Char('y')) => (Operator::Yank, 1),
+            Some(Key::Char('>')) => (Operator::ShiftRight, 1),
+            Some(Key::Char('<')) => (Operator::ShiftLeft, 1),
+            Some(Key::Char('g')) => match input.peek_at(1) {
+                None => return Err(Failure::Pending),
+                Some(Key::Char('u')) => (Operator::Lowercase, 2),
+                Some(Key::Char('U')) => (Operator::Uppercase, 2),
+                Some(Key::Char('~')) => (Operator::ToggleCase, 2),
+                Some(_) => return Ok(None),
+            },
+            _ => return Ok(None),
+        };
+        input.position = input.position.saturating_add(length);
+        Ok(Some(operator))
+    }
+
+    fn parse_operator_target(
+        input: &mut Input,
+        operator: Operator,
+        count: Option<usize>,
+        register: Option<char>,
+    ) -> Result<NormalCommand, Failure> {
+        let count = input.multiplied_count(count);
+        let doubled = match (operator, input.peek_at(0), input.peek_at(1)) {
+            (Operator::Delete, Some(Key::Char('d')), _)
+            | (Operator::Change, Some(Key::Char('c')), _)
+            | (Operator::Yank, Some(Key::Char('y')), _)
+            | (Operator::ShiftRight, Some(Key::Char('>')), _)
+            | (Operator::ShiftLeft, Some(Key::Char('<')), _)
+            | (Operator::Lowercase, Some(Key::Char('u')), _)
+            | (Operator::Uppercase, Some(Key::Char('U')), _)
+            | (Operator::ToggleCase, Some(Key::Char('~')), _) => Some(1),
+            (Operator::Lowercase, Some(Key::Char('g')), Some(Key::Char('u')))
+            | (Operator::Uppercase, Some(Key::Char('g')), Some(Key::Char('U')))
+            | (Operator::ToggleCase, Some(Key::Char('g')), Some(Key::Char('~'))) => Some(2),
+            (
+                Operator::Lowercase | Operator::Uppercase | Operator::ToggleCase,
+                Some(Key::Char('g')),
+                None,
+            ) => {
+                return Err(Failure::Pending);
+            }
+            _ => None,
+        };
+        let target = if let Some(length) = doubled {
+            input.position = input.position.saturating_add(length);
+            Target::Lines
+        } else if matches!(input.peek_at(0), Some(Key::Char('i' | 'a'))) {
+            Target::Object(Self::parse_object(input)?)
+        } else {
+            Target::Motion(Self::parse_motion(input)?)
+        };
+        Ok(NormalCommand::Operate {
+            operator,
+            target,
+            count,
+            register,
+        })
+    }
+
+    fn parse_motion(input: &mut Input) -> Result<Motion, Failure> {
+        let motion = match input.next()? {
+            Key::Char('h') => Motion::Left,
+            Key::Char('l' | ' ') => Motion::Right,
+            Key::Char('j') => Motion::Down,
+            Key::Char('k') => Motion::Up,
+            Key::Char('w') => Motion::WordForward,
+            Key::Char('b') => Motion::WordBackward,
+            Key::Char('e') => Motion::WordEnd,
+            Key::Char('0') => Motion::LineStart,
+            Key::Char('^') => Motion::FirstNonBlank,
+            Key::Char('$') => Motion::LineEnd,
+            Key::Char('G') => Motion::FileEnd,
+            Key::Char('%') => Motion::MatchingPair,
+            Key::Char('n') => Motion::NextMatch,
+            Key::Char('N') => Motion::PreviousMatch,
+            Key::Char('g') => match input.next()? {
+                Key::Char('g') => Motion::FileStart,
+                Key::Char('j') => Motion::DisplayDown,
+                Key::Char('k') => Motion::DisplayUp,
+                _ => return Err(Failure::Invalid),
+            },
+            Key::Char('f') => match input.next()? {
+                Key::Char(character) => Motion::FindForward(character),
+                Key::Ctrl(_) => return Err(Failure::Invalid),
+            },
+            Key::Char('t') => match input.next()? {
+                Key::Char(character) => Motion::TillForward(character),
+                Key::Ctrl(_) => return Err(Failure::Invalid),
+            },
+            Key::Char(quote @ ('\'' | '`')) => {
+                let name = Self::mark_name(input.next()?).ok_or(Failure::Invalid)?;
+                if quote == '`' {
+                    Motion::Mark(name)
+                } else {
+                    Motion::MarkLine(name)
+                }
+            }
+            _ => return Err(Failure::Invalid),
+        };
+        Ok(motion)
+    }
+
+    // i or a, and the kind of object
+    fn parse_object(input: &mut Input) -> Result<TextObject, Failure> {
+        let around = input.next()? == Key::Char('a');
+        let kind = match input.next()? {
+            Key::Char('w') => TextObjectKind::Word,
+            Key::Char('W') => TextObjectKind::BigWord,
+            Key::Char('s') => TextObjectKind::Sentence,
+            Key::Char('p') => TextObjectKind::Paragraph,
+            Key::Char(quote @ ('"' | '\'' | '`')) => TextObjectKind::Quote(quote),
+            Key::Char('(' | ')' | 'b') => TextObjectKind::Pair('(', ')'),
+            Key::Char('[' | ']') => TextObjectKind::Pair('[', ']'),
+            Key::Char('{' | '}' | 'B') => TextObjectKind::Pair('{', '}'),
+            Key::Char('<' | '>') => TextObjectKind::Pair('<', '>'),
+            Key::Char('t') => TextObjectKind::Tag,
+            _ => return Err(Failure::Invalid),
+        };
+        Ok(TextObject { kind, around })
+    }
+
+    // A letter, or ' (also typed as `) for where the last jump was made from
+    const fn mark_name(key: Key) -> Option<char> {
+        match key {
+            Key::Char(name @ ('a'..='z' | 'A'..='Z')) => Some(name),
+            Key::Char('\'' | '`') => Some('\''),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(keys: &str) -> ParseResult {
+        let keys: Vec<Key> = keys.chars().map(Key::Char).collect();
+        NormalParser::parse(&keys)
+    }
+
+    #[test]
+    fn test_parse_normal_commands() {
+        assert_eq!(
+            parse("3dw"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Delete,
+                target: Target::Motion(Motion::WordForward),
+                count: Some(3),
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("2d3w"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Delete,
+                target: Target::Motion(Motion::WordForward),
+                count: Some(6),
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("c$"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Change,
+                target: Target::Motion(Motion::LineEnd),
+                count: None,
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("gUgU"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Uppercase,
+                target: Target::Lines,
+                count: None,
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("0"),
+            ParseResult::Complete(NormalCommand::Move {
+                motion: Motion::LineStart,
+                count: None,
+            })
+        );
+        assert_eq!(
+            parse("10G"),
+            ParseResult::Complete(NormalCommand::Move {
+                motion: Motion::FileEnd,
+                count: Some(10),
+            })
+        );
+        assert_eq!(
+            parse("dtx"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Delete,
+                target: Target::Motion(Motion::TillForward('x')),
+                count: None,
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("2\"a3yy"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Yank,
+                target: Target::Lines,
+                count: Some(6),
+                register: Some('a'),
+            })
+        );
+        assert_eq!(
+            parse("\"_3P"),
+            ParseResult::Complete(NormalCommand::Put {
+                before: true,
+                count: 3,
+                register: Some('_'),
+            })
+        );
+        assert_eq!(
+            parse("g~~"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::ToggleCase,
+                target: Target::Lines,
+                count: None,
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("V"),
+            ParseResult::Complete(NormalCommand::Visual(SelectionKind::Lines))
+        );
+        assert_eq!(parse("d"), ParseResult::Pending);
+        assert_eq!(parse("g"), ParseResult::Pending);
+        assert_eq!(parse("3f"), ParseResult::Pending);
+        assert_eq!(parse("gx"), ParseResult::Invalid);
+        assert_eq!(parse("dq"), ParseResult::Invalid);
+    }
+
+    #[test]
+    fn test_parse_repeat_commands() {
+        assert_eq!(parse("3."), ParseResult::Complete(NormalCommand::Repeat(Some(3))));
+        assert_eq!(
+            parse("3o"),
+            ParseResult::Complete(NormalCommand::Insert {
+                position: InsertPosition::LineBelow,
+                count: 3,
+            })
+        );
+        assert_eq!(parse("qa"), ParseResult::Complete(NormalCommand::Record('a')));
+        assert_eq!(
+            parse("2@@"),
+            ParseResult::Complete(NormalCommand::Play {
+                register: None,
+                count: 2
+            })
+        );
+        assert_eq!(parse("@"), ParseResult::Pending);
+        assert_eq!(parse("q@"), ParseResult::Invalid);
+    }
+
+    #[test]
+    fn test_parse_mark_commands() {
+        assert_eq!(parse("ma"), ParseResult::Complete(NormalCommand::SetMark('a')));
+        assert_eq!(parse("m"), ParseResult::Pending);
+        assert_eq!(parse("m1"), ParseResult::Invalid);
+        assert_eq!(
+            parse("d'a"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Delete,
+                target: Target::Motion(Motion::MarkLine('a')),
+                count: None,
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("``"),
+            ParseResult::Complete(NormalCommand::Move {
+                motion: Motion::Mark('\''),
+                count: None,
+            })
+        );
+        let keys = [Key::Char('3'), Key::Ctrl('o')];
+        assert_eq!(
+            NormalParser::parse(&keys),
+            ParseResult::Complete(NormalCommand::Jump { back: true, count: 3 })
+        );
+        let tab = KeyEvent::new(Tab, KeyModifiers::NONE);
+        assert_eq!(Key::try_from(tab), Ok(Key::Ctrl('i')));
+    }
+
+    #[test]
+    fn test_parse_text_objects() {
+        let object = |kind, around| Target::Object(TextObject { kind, around });
+        assert_eq!(
+            parse("d2i("),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Delete,
+                target: object(TextObjectKind::Pair('(', ')'), false),
+                count: Some(2),
+                register: None,
+            })
+        );
+        assert_eq!(
+            parse("caB"),
+            ParseResult::Complete(NormalCommand::Operate {
+                operator: Operator::Change,
+                target: object(TextObjectKind::Pair('{', '}'), true),
+                count: None,
+                register: None,
+            })
+        );
+        assert_eq!(parse("di"), ParseResult::Pending);
+        assert_eq!(parse("diz"), ParseResult::Invalid);
+        let keys: Vec<Key> = "2at".chars().map(Key::Char).collect();
+        assert_eq!(
+            NormalParser::parse_visual(&keys),
+            ParseResult::Complete(VisualCommand::Object {
+                object: TextObject {
+                    kind: TextObjectKind::Tag,
+                    around: true,
+                },
+                count: Some(2),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_window_commands() {
+        assert_eq!(
+            NormalParser::parse(&[Key::Char('3'), Key::Ctrl('w'), Key::Char('<')]),
+            ParseResult::Complete(NormalCommand::Window(WindowCommand::Resize {
+                vertical: true,
+                amount: -3,
+            }))
+        );
+        assert_eq!(
+            NormalParser::parse(&[Key::Ctrl('w'), Key::Ctrl('l')]),
+            ParseResult::Complete(NormalCommand::Window(WindowCommand::Focus(Direction::Right)))
+        );
+        assert_eq!(NormalParser::parse(&[Key::Ctrl('w')]), ParseResult::Pending);
+    }
+
+    #[test]
+    fn test_parse_visual_commands() {
+        let parse = |keys: &str| {
+            let keys: Vec<Key> = keys.chars().map(Key::Char).collect();
+            NormalParser::parse_visual(&keys)
+        };
+        assert_eq!(
+            parse("3w"),
+            ParseResult::Complete(VisualCommand::Move {
+                motion: Motion::WordForward,
+                count: Some(3),
+            })
+        );
+        assert_eq!(
+            parse("\"ay"),
+            ParseResult::Complete(VisualCommand::Operate {
+                operator: Operator::Yank,
+                register: Some('a'),
+            })
+        );
+        assert_eq!(
+            parse("~"),
+            ParseResult::Complete(VisualCommand::Operate {
+                operator: Operator::ToggleCase,
+                register: None,
+            })
+        );
+        assert_eq!(parse("o"), ParseResult::Complete(VisualCommand::SwapEnds));
+        assert_eq!(
+            NormalParser::parse_visual(&[Key::Ctrl('v')]),
+            ParseResult::Complete(VisualCommand::Select(SelectionKind::Block))
+        );
+        assert_eq!(parse("\""), ParseResult::Pending);
+    }
+}
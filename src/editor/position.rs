@@ -1,10 +1,17 @@
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone, Default, Debug, PartialEq, Eq)]
 pub struct Position {
     pub col: usize,
     pub row: usize,
 }
 
 impl Position{
+    pub const fn saturating_add(self, other: Self) -> Self {
+        Self {
+            row: self.row.saturating_add(other.row),
+            col: self.col.saturating_add(other.col),
+        }
+    }
+
     pub const fn saturating_sub(self, other: Self) -> Self {
         Self {
             row: self.row.saturating_sub(other.row),
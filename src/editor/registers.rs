@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RegisterKind {
+    Charwise,
+    // Whole lines; the text ends with a newline
+    Linewise,
+    // A rectangular block; one line of text per line of the block
+    Blockwise,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Register {
+    pub text: String,
+    pub kind: RegisterKind,
+}
+
+impl Register {
+    pub fn new(text: String, kind: RegisterKind) -> Self {
+        Self { text, kind }
+    }
+
+    fn append(&mut self, other: &Self) {
+        if self.kind == RegisterKind::Charwise && other.kind == RegisterKind::Linewise {
+            // Appending lines to a charwise register makes it linewise
+            self.text.push('\n');
+            self.kind = RegisterKind::Linewise;
+        }
+        self.text.push_str(&other.text);
+    }
+}
+
+const NUMBERED_REGISTERS: usize = 10;
+
+// Vim-style registers:
+// - the unnamed register `"` always holds the last yanked or deleted text
+// - `0` holds the last yank, `1`-`9` the last deletes, most recent first
+// - `a`-`z` are set explicitly; writing to `A`-`Z` appends to them instead
+// - `_` is the black hole: writing to it discards the text
+#[derive(Default)]
+pub struct Registers {
+    unnamed: Option<Register>,
+    numbered: [Option<Register>; NUMBERED_REGISTERS],
+    named: HashMap<char, Register>,
+}
+
+impl Registers {
+    pub fn is_valid_name(name: char) -> bool {
+        name == '"' || name == '_' || name.is_ascii_digit() || name.is_ascii_alphabetic()
+    }
+
+    pub fn get(&self, name: Option<char>) -> Option<&Register> {
+        match name {
+            None | Some('"') => self.unnamed.as_ref(),
+            Some(digit @ '0'..='9') => digit
+                .to_digit(10)
+                .and_then(|index| usize::try_from(index).ok())
+                .and_then(|index| self.numbered.get(index))
+                .and_then(Option::as_ref),
+            Some(letter) if letter.is_ascii_alphabetic() => {
+                self.named.get(&letter.to_ascii_lowercase())
+            }
+            Some(_) => None,
+        }
+    }
+
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        match name {
+            None | Some('"') => {
+                self.set_numbered(0, register.clone());
+                self.unnamed = Some(register);
+            }
+            Some(name) => self.store(name, register),
+        }
+    }
+
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        match name {
+            None | Some('"') => {
+                // Shift the delete history down by one to make room
+                self.numbered[1..].rotate_right(1);
+                self.set_numbered(1, register.clone());
+                self.unnamed = Some(register);
+            }
+            Some(name) => self.store(name, register),
+        }
+    }
+
+    // Keeps keys recorded with q{name}, leaving the unnamed register alone
+    pub fn record(&mut self, name: char, text: String) {
+        let unnamed = self.unnamed.take();
+        self.store(name, Register::new(text, RegisterKind::Charwise));
+        self.unnamed = unnamed;
+    }
+
+    fn store(&mut self, name: char, register: Register) {
+        if name.is_ascii_uppercase() {
+            let entry = self
+                .named
+                .entry(name.to_ascii_lowercase())
+                .or_insert_with(|| Register::new(String::new(), register.kind));
+            entry.append(&register);
+            self.unnamed = Some(entry.clone());
+        } else if name.is_ascii_lowercase() {
+            self.named.insert(name, register.clone());
+            self.unnamed = Some(register);
+        } else if let Some(index) = name.to_digit(10).and_then(|index| usize::try_from(index).ok())
+        {
+            self.set_numbered(index, register.clone());
+            self.unnamed = Some(register);
+        }
+    }
+
+    fn set_numbered(&mut self, index: usize, register: Register) {
+        if let Some(slot) = self.numbered.get_mut(index) {
+            *slot = Some(register);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn charwise(text: &str) -> Register {
+        Register::new(text.to_string(), RegisterKind::Charwise)
+    }
+
+    #[test]
+    fn test_register_semantics() {
+        let mut registers = Registers::default();
+        registers.yank(None, charwise("yanked"));
+        registers.delete(None, charwise("first"));
+        registers.delete(None, charwise("second"));
+        assert_eq!(registers.get(Some('0')), Some(&charwise("yanked")));
+        assert_eq!(registers.get(Some('1')), Some(&charwise("second")));
+        assert_eq!(registers.get(Some('2')), Some(&charwise("first")));
+        assert_eq!(registers.get(None), Some(&charwise("second")));
+
+        registers.yank(Some('a'), charwise("foo"));
+        registers.yank(Some('A'), charwise("bar"));
+        assert_eq!(registers.get(Some('a')), Some(&charwise("foobar")));
+        registers.yank(
+            Some('A'),
+            Register::new("line\n".to_string(), RegisterKind::Linewise),
+        );
+        assert_eq!(
+            registers.get(Some('a')),
+            Some(&Register::new(
+                "foobar\nline\n".to_string(),
+                RegisterKind::Linewise
+            ))
+        );
+
+        registers.delete(Some('_'), charwise("gone"));
+        assert_eq!(registers.get(Some('_')), None);
+        assert_eq!(registers.get(Some('1')), Some(&charwise("second")));
+    }
+}
@@ -0,0 +1,165 @@
+use super::{
+    command::{Command, Edit, Operator},
+    normalparser::{InsertPosition, NormalCommand},
+};
+
+// What started a change
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChangeCommand {
+    Normal(NormalCommand),
+    // An ex command such as :s, run again as it was typed
+    Ex(String),
+}
+
+// A change that `.` can make again: the command that started it, and what was
+// typed in Insert mode after it if it entered Insert mode
+#[derive(Clone)]
+pub struct Change {
+    pub command: ChangeCommand,
+    pub typed: Vec<Command>,
+}
+
+impl Change {
+    pub const fn new(command: ChangeCommand) -> Self {
+        Self {
+            command,
+            typed: Vec::new(),
+        }
+    }
+
+    // Whether a Normal mode command changes the text, and so is repeated by `.`
+    pub const fn is_change(command: &NormalCommand) -> bool {
+        match command {
+            NormalCommand::Operate { operator, .. } => !matches!(operator, Operator::Yank),
+            NormalCommand::Put { .. } | NormalCommand::Insert { .. } => true,
+            _ => false,
+        }
+    }
+
+    // Gives the change the count typed before `.`, which replaces its own
+    pub fn set_count(&mut self, new_count: usize) {
+        match &mut self.command {
+            ChangeCommand::Normal(NormalCommand::Operate { count, .. }) => *count = Some(new_count),
+            ChangeCommand::Normal(
+                NormalCommand::Put { count, .. } | NormalCommand::Insert { count, .. },
+            ) => *count = new_count,
+            ChangeCommand::Normal(_) | ChangeCommand::Ex(_) => {}
+        }
+    }
+
+    // The commands that type the text the other times it goes in, after it was
+    // typed once in Insert mode. Text typed after o or O goes on a line of its
+    // own each time.
+    pub fn repeated_commands(&self) -> Vec<Command> {
+        let (times, opens_lines) = match self.command {
+            ChangeCommand::Normal(NormalCommand::Insert { position, count }) => (
+                count,
+                matches!(position, InsertPosition::LineBelow | InsertPosition::LineAbove),
+            ),
+            _ => (1, false),
+        };
+        let mut commands = Vec::new();
+        for _ in 1..times {
+            if opens_lines {
+                commands.push(Command::Edit(Edit::InsertNewLine));
+            }
+            commands.extend_from_slice(&self.typed);
+        }
+        commands
+    }
+}
+
+// Keeps the last change for `.`
+#[derive(Default)]
+pub struct DotRepeat {
+    last: Option<Change>,
+    // The change being made while the text it inserts is typed
+    pending: Option<Change>,
+}
+
+impl DotRepeat {
+    pub const fn last(&self) -> Option<&Change> {
+        self.last.as_ref()
+    }
+
+    pub fn set_last(&mut self, change: Change) {
+        self.last = Some(change);
+    }
+
+    // Starts a change that goes on in Insert mode, until `finish`
+    pub fn begin(&mut self, change: Change) {
+        self.pending = Some(change);
+    }
+
+    // The change being made in Insert mode, if any
+    pub const fn pending(&self) -> Option<&Change> {
+        self.pending.as_ref()
+    }
+
+    // Keeps a command typed in Insert mode as part of the pending change
+    pub fn record(&mut self, command: Command) {
+        if let Some(change) = &mut self.pending {
+            change.typed.push(command);
+        }
+    }
+
+    // Insert mode was left: the pending change is complete
+    pub fn finish(&mut self) {
+        if let Some(change) = self.pending.take() {
+            self.last = Some(change);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::command::Motion;
+    use crate::editor::normalparser::Target;
+
+    fn texts(commands: &[Command]) -> String {
+        commands
+            .iter()
+            .map(|command| match command {
+                Command::Edit(Edit::Insert(character)) => *character,
+                Command::Edit(Edit::InsertNewLine) => '\n',
+                _ => '?',
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_repeat_counts() {
+        let mut repeat = DotRepeat::default();
+        let open = NormalCommand::Insert {
+            position: InsertPosition::LineBelow,
+            count: 2,
+        };
+        repeat.begin(Change::new(ChangeCommand::Normal(open)));
+        repeat.record(Command::Edit(Edit::Insert('a')));
+        repeat.record(Command::Edit(Edit::Insert('b')));
+        assert!(repeat.last().is_none());
+        repeat.finish();
+
+        let mut change = repeat.last().cloned().unwrap();
+        assert_eq!(texts(&change.repeated_commands()), "\nab");
+        change.set_count(3);
+        assert_eq!(texts(&change.repeated_commands()), "\nab\nab");
+
+        let delete = NormalCommand::Operate {
+            operator: Operator::Delete,
+            target: Target::Motion(Motion::WordForward),
+            count: Some(2),
+            register: None,
+        };
+        assert!(Change::is_change(&delete));
+        let mut change = Change::new(ChangeCommand::Normal(delete));
+        change.set_count(5);
+        assert!(matches!(
+            change.command,
+            ChangeCommand::Normal(NormalCommand::Operate { count: Some(5), .. })
+        ));
+        assert!(change.repeated_commands().is_empty());
+        assert!(!Change::is_change(&NormalCommand::Undo(1)));
+    }
+}
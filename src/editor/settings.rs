@@ -0,0 +1,290 @@
+use std::{env, path::PathBuf};
+
+use super::{
+    NAME,
+    view::{Indentation, WindowOptions},
+};
+
+// Where the value of an option is kept
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionScope {
+    Global,
+    // Each buffer has its own value
+    Buffer,
+    // Each window has its own value
+    Window,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OptionKind {
+    Bool,
+    Number,
+    String,
+}
+
+pub struct OptionInfo {
+    pub name: &'static str,
+    pub short_name: &'static str,
+    pub kind: OptionKind,
+    pub scope: OptionScope,
+    // The smallest value a number option can have
+    pub minimum: usize,
+}
+
+const fn option(name: &'static str, short_name: &'static str, kind: OptionKind, scope: OptionScope) -> OptionInfo {
+    OptionInfo {
+        name,
+        short_name,
+        kind,
+        scope,
+        minimum: 0,
+    }
+}
+
+// Every option that :set and the config file know about
+pub const OPTIONS: &[OptionInfo] = &[
+    option("ignorecase", "ic", OptionKind::Bool, OptionScope::Global),
+    option("smartcase", "scs", OptionKind::Bool, OptionScope::Global),
+    option("backup", "bk", OptionKind::Bool, OptionScope::Global),
+    option("theme", "", OptionKind::String, OptionScope::Global),
+    OptionInfo {
+        minimum: 1,
+        ..option("messagetimeout", "mto", OptionKind::Number, OptionScope::Global)
+    },
+    option("mapleader", "", OptionKind::String, OptionScope::Global),
+    option("timeoutlen", "tm", OptionKind::Number, OptionScope::Global),
+    option("fileformat", "ff", OptionKind::String, OptionScope::Buffer),
+    option("filetype", "ft", OptionKind::String, OptionScope::Buffer),
+    OptionInfo {
+        minimum: 1,
+        ..option("tabstop", "ts", OptionKind::Number, OptionScope::Buffer)
+    },
+    option("shiftwidth", "sw", OptionKind::Number, OptionScope::Buffer),
+    option("softtabstop", "sts", OptionKind::Number, OptionScope::Buffer),
+    option("expandtab", "et", OptionKind::Bool, OptionScope::Buffer),
+    option("number", "nu", OptionKind::Bool, OptionScope::Window),
+    option("relativenumber", "rnu", OptionKind::Bool, OptionScope::Window),
+    option("wrap", "", OptionKind::Bool, OptionScope::Window),
+    option("linebreak", "lbr", OptionKind::Bool, OptionScope::Window),
+    option("scrolloff", "so", OptionKind::Number, OptionScope::Window),
+];
+
+pub fn find_option(name: &str) -> Option<&'static OptionInfo> {
+    OPTIONS
+        .iter()
+        .find(|option| option.name == name || !option.short_name.is_empty() && option.short_name == name)
+}
+
+// The global options, and the values new buffers and windows start with
+pub struct Settings {
+    pub ignore_case: bool,
+    pub smart_case: bool,
+    pub backup: bool,
+    // Seconds a message stays in the message bar
+    pub message_timeout: usize,
+    // What <leader> stands for in mappings
+    pub map_leader: String,
+    // Milliseconds to wait for the rest of a mapping
+    pub timeout_len: usize,
+    pub indentation: Indentation,
+    pub window: WindowOptions,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            ignore_case: true,
+            smart_case: true,
+            backup: false,
+            message_timeout: 5,
+            map_leader: "\\".to_string(),
+            timeout_len: 1000,
+            indentation: Indentation::default(),
+            window: WindowOptions::default(),
+        }
+    }
+}
+
+impl Settings {
+    // Searches ignore case if 'ignorecase' is set, unless 'smartcase' is set too
+    // and the pattern contains an uppercase letter
+    pub fn search_ignores_case(&self, pattern: &str) -> bool {
+        self.ignore_case && !(self.smart_case && pattern.chars().any(char::is_uppercase))
+    }
+
+    // A global option kept here, or the value new buffers and windows get for
+    // one of theirs
+    pub fn field(&mut self, name: &str) -> Option<Field<'_>> {
+        match name {
+            "ignorecase" => Some(Field::Bool(&mut self.ignore_case)),
+            "smartcase" => Some(Field::Bool(&mut self.smart_case)),
+            "backup" => Some(Field::Bool(&mut self.backup)),
+            "messagetimeout" => Some(Field::Number(&mut self.message_timeout)),
+            "mapleader" => Some(Field::String(&mut self.map_leader)),
+            "timeoutlen" => Some(Field::Number(&mut self.timeout_len)),
+            name => indentation_field(&mut self.indentation, name)
+                .or_else(|| window_field(&mut self.window, name)),
+        }
+    }
+}
+
+// Where the value of an option is, so it can be shown and changed the same way
+// whatever the option
+pub enum Field<'a> {
+    Bool(&'a mut bool),
+    Number(&'a mut usize),
+    String(&'a mut String),
+}
+
+pub fn indentation_field<'a>(indentation: &'a mut Indentation, name: &str) -> Option<Field<'a>> {
+    match name {
+        "tabstop" => Some(Field::Number(&mut indentation.tab_stop)),
+        "shiftwidth" => Some(Field::Number(&mut indentation.shift_width)),
+        "softtabstop" => Some(Field::Number(&mut indentation.soft_tab_stop)),
+        "expandtab" => Some(Field::Bool(&mut indentation.expand_tab)),
+        _ => None,
+    }
+}
+
+pub fn window_field<'a>(window: &'a mut WindowOptions, name: &str) -> Option<Field<'a>> {
+    match name {
+        "number" => Some(Field::Bool(&mut window.line_numbers.absolute)),
+        "relativenumber" => Some(Field::Bool(&mut window.line_numbers.relative)),
+        "wrap" => Some(Field::Bool(&mut window.line_wrap.wrap)),
+        "linebreak" => Some(Field::Bool(&mut window.line_wrap.line_break)),
+        "scrolloff" => Some(Field::Number(&mut window.scroll_off)),
+        _ => None,
+    }
+}
+
+// What to do with an option
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SetAction {
+    Show,
+    Toggle(Toggle),
+    Assign(String),
+}
+
+// Parses a :set argument: `name` turns an on/off option on (or shows any other
+// option), `noname` turns it off, `invname` or `name!` toggles it, `name?`
+// shows it and `name=value` sets it
+pub fn parse_set(argument: &str) -> Result<(&'static OptionInfo, SetAction), String> {
+    let unknown = || format!("Unknown option: {argument}");
+    if let Some((name, value)) = argument.split_once('=') {
+        let option = find_option(name).ok_or_else(unknown)?;
+        return Ok((option, SetAction::Assign(value.to_string())));
+    }
+    let is_flag = |name: &str| find_option(name).is_some_and(|option| option.kind == OptionKind::Bool);
+    let (name, toggle) = parse_flag(argument, is_flag);
+    let option = find_option(name).ok_or_else(unknown)?;
+    let action = match toggle {
+        Some(toggle) if option.kind == OptionKind::Bool => SetAction::Toggle(toggle),
+        None | Some(Toggle::On) => SetAction::Show,
+        Some(_) => return Err(format!("Invalid argument: {argument}")),
+    };
+    Ok((option, action))
+}
+
+fn parse_flag(argument: &str, is_flag: impl Fn(&str) -> bool) -> (&str, Option<Toggle>) {
+    if let Some(name) = argument.strip_suffix('?') {
+        (name, None)
+    } else if let Some(name) = argument.strip_suffix('!') {
+        (name, Some(Toggle::Invert))
+    } else if let Some(name) = argument.strip_prefix("inv").filter(|name| is_flag(name)) {
+        (name, Some(Toggle::Invert))
+    } else if let Some(name) = argument.strip_prefix("no").filter(|name| is_flag(name)) {
+        (name, Some(Toggle::Off))
+    } else {
+        (argument, Some(Toggle::On))
+    }
+}
+
+// Does what parse_set found to an option, returning the message to show if any
+pub fn apply(field: Field, option: &OptionInfo, action: SetAction) -> Result<Option<String>, String> {
+    let name = option.name;
+    match (field, action) {
+        (Field::Bool(value), SetAction::Show) => {
+            let prefix = if *value { "" } else { "no" };
+            Ok(Some(format!("{prefix}{name}")))
+        }
+        (Field::Bool(value), SetAction::Toggle(toggle)) => {
+            *value = match toggle {
+                Toggle::On => true,
+                Toggle::Off => false,
+                Toggle::Invert => !*value,
+            };
+            Ok(None)
+        }
+        (Field::Number(value), SetAction::Show) => Ok(Some(format!("{name}={value}"))),
+        (Field::Number(value), SetAction::Assign(text)) => {
+            let number: usize = text
+                .parse()
+                .map_err(|_| format!("Number required after =: {name}={text}"))?;
+            if number < option.minimum {
+                return Err(format!("Argument must be at least {}: {name}={text}", option.minimum));
+            }
+            *value = number;
+            Ok(None)
+        }
+        (Field::String(value), SetAction::Show) => Ok(Some(format!("{name}={value}"))),
+        (Field::String(value), SetAction::Assign(text)) => {
+            *value = text;
+            Ok(None)
+        }
+        _ => Err(format!("Invalid argument: {name}")),
+    }
+}
+
+// Where the user's configuration lives: $XDG_CONFIG_HOME/amoxcalli, falling
+// back to ~/.config/amoxcalli (or %APPDATA%\amoxcalli on Windows)
+pub fn config_dir() -> Option<PathBuf> {
+    let base = env::var_os("XDG_CONFIG_HOME")
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(base.join(NAME))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Toggle {
+    On,
+    Off,
+    Invert,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(settings: &mut Settings, argument: &str) -> Result<Option<String>, String> {
+        let (option, action) = parse_set(argument)?;
+        let field = settings.field(option.name).ok_or("Not a global option")?;
+        apply(field, option, action)
+    }
+
+    #[test]
+    fn test_set_options() {
+        let mut settings = Settings::default();
+        assert_eq!(set(&mut settings, "noic"), Ok(None));
+        assert!(!settings.ignore_case);
+        assert_eq!(set(&mut settings, "ic?"), Ok(Some("noignorecase".to_string())));
+        assert_eq!(set(&mut settings, "invignorecase"), Ok(None));
+        assert!(settings.ignore_case);
+        assert!(settings.search_ignores_case("foo"));
+        assert!(!settings.search_ignores_case("Foo"));
+        assert_eq!(set(&mut settings, "scs!"), Ok(None));
+        assert!(settings.search_ignores_case("Foo"));
+        assert!(set(&mut settings, "nosuchoption").is_err());
+
+        assert_eq!(set(&mut settings, "ts=4"), Ok(None));
+        assert_eq!(settings.indentation.tab_stop, 4);
+        assert_eq!(set(&mut settings, "ts"), Ok(Some("tabstop=4".to_string())));
+        assert!(set(&mut settings, "ts=0").is_err());
+        assert!(set(&mut settings, "ts=four").is_err());
+        assert!(set(&mut settings, "nots").is_err());
+        assert!(set(&mut settings, "wrap=yes").is_err());
+        assert_eq!(set(&mut settings, "so=3"), Ok(None));
+        assert_eq!(settings.window.scroll_off, 3);
+    }
+}
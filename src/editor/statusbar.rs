@@ -1,6 +1,6 @@
 use std::io::Error;
 
-use super::{ DocumentStatus, Size, Terminal, UIComponent,};
+use super::{DocumentStatus, Position, Size, Terminal, UIComponent, theme::Scope};
 
 #[derive(Default)]
 pub struct StatusBar {
@@ -31,7 +31,7 @@ impl UIComponent for StatusBar {
         self.size = size;
     }
 
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+    fn draw(&mut self, terminal: &mut dyn Terminal, origin: Position) -> Result<(), Error> {
         let line_count = self.current_status.line_count_to_string();
         let modified_indicator = self.current_status.modified_indicator_to_string();
         let beginning = format!(
@@ -39,7 +39,12 @@ impl UIComponent for StatusBar {
             self.current_status.file_name
         );
         // Assemble the whole status bar, with the position indicator at the back
-        let position_indicator = self.current_status.position_indicator_to_string();
+        let position_indicator = format!(
+            "{} | {} | {}",
+            self.current_status.file_type,
+            self.current_status.file_format,
+            self.current_status.position_indicator_to_string()
+        );
         let remainder_len = self.size.width.saturating_sub(beginning.len());
         let status = format!("{beginning}{position_indicator:>remainder_len$}");
 
@@ -49,7 +54,7 @@ impl UIComponent for StatusBar {
         } else {
             String::new()
         };
-        Terminal::print_inverted_row(origin_y, &to_print)?;
+        terminal.print_styled_at(origin, self.size.width, &to_print, Scope::StatusBar)?;
 
         Ok(())
     }
@@ -1,127 +1,126 @@
-use crossterm::cursor::{Hide, MoveTo, Show};
-use crossterm::style::{Attribute, Print};
-use crossterm::terminal::{
-  disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
-    EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+use crossterm::event::Event;
+use crossterm::style::ContentStyle;
+use std::io::Error;
+use std::sync::{LazyLock, PoisonError, RwLock};
+use std::time::Duration;
+use unicode_width::UnicodeWidthStr;
+use super::{
+    AnnotatedString, Position, Size,
+    theme::{ColorSupport, Scope, Theme, layer},
 };
-use crossterm::{queue, Command};
-use std::io::{stdout, Error, Write};
-use super::{Position, Size};
 
-
-
-pub struct Terminal;
-
-impl Terminal {
-    pub fn terminate() -> Result<(), Error> {
-        Self::leave_alternate_screen()?;
-        Self::enable_line_wrap()?;
-        Self::show_caret()?;
-        Self::execute()?;
-        disable_raw_mode()?;
-        Ok(())
-    }
-
-    pub fn initialize() -> Result<(), Error> {
-        enable_raw_mode()?;
-        Self::enter_alternate_screen()?;
-        Self::disable_line_wrap()?;
-        Self::clear_screen()?;
-        Self::execute()?;
-        Ok(())
-    }
-
-    pub fn disable_line_wrap() -> Result<(), Error> {
-        Self::queue_command(DisableLineWrap)?;
-        Ok(())
-    }
-    pub fn enable_line_wrap() -> Result<(), Error> {
-        Self::queue_command(EnableLineWrap)?;
-        Ok(())
-    }
-    pub fn set_title(title: &str) -> Result<(), Error> {
-        Self::queue_command(SetTitle(title))?;
-        Ok(())
-    }
-
-    pub fn clear_screen() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::All))?;
-        Ok(())
-    }
-
-    pub fn clear_line() -> Result<(), Error> {
-        Self::queue_command(Clear(ClearType::CurrentLine))?;
-        Ok(())
-    }
-    
-    pub fn move_caret_to(position: Position) -> Result<(), Error> {
-
-        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
-        Self::queue_command(MoveTo(position.col as u16, position.row as u16))?;
-        Ok(())
-    }
-
-    pub fn hide_caret() -> Result<(), Error> {
-        Self::queue_command(Hide)?;
-        Ok(())
-    }
-
-    pub fn show_caret() -> Result<(), Error> {
-        Self::queue_command(Show)?;
-        Ok(())
-    }
-
-     pub fn enter_alternate_screen() -> Result<(), Error> {
-        Self::queue_command(EnterAlternateScreen)?;
-        Ok(())
-    }
-
-    pub fn leave_alternate_screen() -> Result<(), Error> {
-        Self::queue_command(LeaveAlternateScreen)?;
-        Ok(())
-    }
-    
-    pub fn print(string: &str) -> Result<(), Error> {
-        Self::queue_command(Print(string))?;
-        Ok(())
-    }
-
-    pub fn size() -> Result<Size, Error> {
-        let (width_u16, height_u16) = size()?;
-        #[allow(clippy::as_conversions)]
-        let height = height_u16 as usize;
-        #[allow(clippy::as_conversions)]
-        let width = width_u16 as usize;
-        Ok(Size { height, width })
-    }
-    
-    pub fn execute() -> Result<(), Error> {
-        stdout().flush()?;
-        Ok(())
-    }
-
-    fn queue_command<T: Command>(command: T) -> Result<(), Error> {
-        queue!(stdout(), command)?;
-        Ok(())
-    }
-
-    pub fn print_row(row: usize, line_text: &str) -> Result<(), Error> {
-        Self::move_caret_to(Position { row, col: 0 })?;
-        Self::clear_line()?;
-        Self::print(line_text)?;
-        Ok(())
-    }
-
-     pub fn print_inverted_row(row: usize, line_text: &str) -> Result<(), Error> {
-        let width = Self::size()?.width;
-        Self::print_row(
-            row,
-            &format!(
-                "{}{:width$.width$}{}",
-                Attribute::Reverse,
-                line_text,
-                Attribute::Reset
-            ),
-        )
-    }
-}
\ No newline at end of file
+mod crosstermterminal;
+pub use crosstermterminal::CrosstermTerminal;
+#[cfg(test)]
+mod headlessterminal;
+#[cfg(test)]
+pub use headlessterminal::HeadlessTerminal;
+
+// The theme everything is drawn with
+static THEME: LazyLock<RwLock<Theme>> = LazyLock::new(|| RwLock::new(Theme::default()));
+
+// What waiting for the next event came up with
+pub enum Input {
+    Event(Event),
+    // Nothing happened before the timeout
+    Timeout,
+    // No more events will come, as when a scripted terminal runs out of them.
+    // A real terminal's input never ends.
+    #[cfg_attr(not(test), allow(dead_code))]
+    End,
+}
+
+// Where the editor is drawn and its events come from: the real terminal, or a
+// grid of cells in memory for tests
+pub trait Terminal {
+    fn initialize(&mut self) -> Result<(), Error>;
+
+    fn terminate(&mut self) -> Result<(), Error>;
+
+    fn size(&self) -> Result<Size, Error>;
+
+    fn set_title(&mut self, title: &str) -> Result<(), Error>;
+
+    fn clear_screen(&mut self) -> Result<(), Error>;
+
+    fn move_caret_to(&mut self, position: Position) -> Result<(), Error>;
+
+    fn hide_caret(&mut self) -> Result<(), Error>;
+
+    fn show_caret(&mut self) -> Result<(), Error>;
+
+    // Prints text at the caret, moving the caret past it
+    fn print_styled(&mut self, text: &str, style: ContentStyle) -> Result<(), Error>;
+
+    // Shows everything printed since the last call
+    fn execute(&mut self) -> Result<(), Error>;
+
+    // Waits at most `timeout` for the next event
+    fn read_event(&mut self, timeout: Duration) -> Result<Input, Error>;
+
+    fn print(&mut self, string: &str) -> Result<(), Error> {
+        self.print_styled(string, ContentStyle::default())
+    }
+
+    // Prints text at a position, padded with spaces to the given width
+    fn print_at(&mut self, origin: Position, width: usize, text: &str) -> Result<(), Error> {
+        self.print_styled_at(origin, width, text, Scope::Text)
+    }
+
+    // Like print_at, but in the theme's style for the scope
+    fn print_styled_at(
+        &mut self,
+        origin: Position,
+        width: usize,
+        text: &str,
+        scope: Scope,
+    ) -> Result<(), Error> {
+        let style = style_for(Scope::Text, scope);
+        self.move_caret_to(origin)?;
+        self.print_styled(text, style)?;
+        self.print_styled(&" ".repeat(width.saturating_sub(text.width())), style)
+    }
+
+    // Prints annotated text on top of the style of a scope (like the cursor line)
+    fn print_annotated_at(
+        &mut self,
+        origin: Position,
+        width: usize,
+        annotated_string: &AnnotatedString,
+        scope: Scope,
+    ) -> Result<(), Error> {
+        let base = style_for(Scope::Text, scope);
+        self.move_caret_to(origin)?;
+        let mut printed = 0;
+        for part in annotated_string.parts() {
+            printed = part.string.width().saturating_add(printed);
+            let style = part
+                .annotation_type
+                .map_or(base, |annotation_type| style_for(scope, annotation_type.into()));
+            self.print_styled(part.string, style)?;
+        }
+        self.print_styled(&" ".repeat(width.saturating_sub(printed)), base)
+    }
+}
+
+pub fn set_theme(theme: Theme) {
+    *THEME.write().unwrap_or_else(PoisonError::into_inner) =
+        theme.with_color_support(ColorSupport::detect());
+}
+
+pub fn theme_name() -> String {
+    THEME
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .name()
+        .to_string()
+}
+
+// The style of a scope drawn on top of another, and both on top of plain text
+fn style_for(below: Scope, scope: Scope) -> ContentStyle {
+    let theme = THEME.read().unwrap_or_else(PoisonError::into_inner);
+    layer(
+        layer(theme.style(Scope::Text), theme.style(below)),
+        theme.style(scope),
+    )
+}
@@ -0,0 +1,96 @@
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{poll, read};
+use crossterm::style::{ContentStyle, Print, PrintStyledContent};
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, size, Clear, ClearType, DisableLineWrap, EnableLineWrap,
+    EnterAlternateScreen, LeaveAlternateScreen, SetTitle,
+};
+use crossterm::{queue, Command};
+use std::io::{stdout, Error, Write};
+use std::time::Duration;
+
+use super::{Input, Position, Size, Terminal};
+
+// The terminal the editor runs in, driven through crossterm
+#[derive(Clone, Copy, Default)]
+pub struct CrosstermTerminal;
+
+impl CrosstermTerminal {
+    fn queue_command<T: Command>(command: T) -> Result<(), Error> {
+        queue!(stdout(), command)?;
+        Ok(())
+    }
+}
+
+impl Terminal for CrosstermTerminal {
+    fn initialize(&mut self) -> Result<(), Error> {
+        enable_raw_mode()?;
+        Self::queue_command(EnterAlternateScreen)?;
+        Self::queue_command(DisableLineWrap)?;
+        self.clear_screen()?;
+        self.execute()?;
+        Ok(())
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        Self::queue_command(LeaveAlternateScreen)?;
+        Self::queue_command(EnableLineWrap)?;
+        self.show_caret()?;
+        self.execute()?;
+        disable_raw_mode()?;
+        Ok(())
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        let (width_u16, height_u16) = size()?;
+        #[allow(clippy::as_conversions)]
+        let height = height_u16 as usize;
+        #[allow(clippy::as_conversions)]
+        let width = width_u16 as usize;
+        Ok(Size { height, width })
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        Self::queue_command(SetTitle(title))
+    }
+
+    fn clear_screen(&mut self) -> Result<(), Error> {
+        Self::queue_command(Clear(ClearType::All))
+    }
+
+    fn move_caret_to(&mut self, position: Position) -> Result<(), Error> {
+        #[allow(clippy::as_conversions, clippy::cast_possible_truncation)]
+        Self::queue_command(MoveTo(position.col as u16, position.row as u16))
+    }
+
+    fn hide_caret(&mut self) -> Result<(), Error> {
+        Self::queue_command(Hide)
+    }
+
+    fn show_caret(&mut self) -> Result<(), Error> {
+        Self::queue_command(Show)
+    }
+
+    fn print_styled(&mut self, text: &str, style: ContentStyle) -> Result<(), Error> {
+        if text.is_empty() {
+            Ok(())
+        } else if style == ContentStyle::default() {
+            Self::queue_command(Print(text))
+        } else {
+            Self::queue_command(PrintStyledContent(style.apply(text)))
+        }
+    }
+
+    fn execute(&mut self) -> Result<(), Error> {
+        stdout().flush()?;
+        Ok(())
+    }
+
+    fn read_event(&mut self, timeout: Duration) -> Result<Input, Error> {
+        if poll(timeout)? {
+            read().map(Input::Event)
+        } else {
+            Ok(Input::Timeout)
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crossterm::event::Event;
+use crossterm::style::ContentStyle;
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    io::Error,
+    rc::Rc,
+    time::Duration,
+};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use super::{Input, Position, Size, Terminal};
+
+// What is shown in one column of a row. The column after a wide grapheme
+// holds an empty one.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cell {
+    pub grapheme: String,
+    pub style: ContentStyle,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Self {
+            grapheme: " ".to_string(),
+            style: ContentStyle::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Screen {
+    size: Size,
+    cells: Vec<Vec<Cell>>,
+    caret: Position,
+    caret_hidden: bool,
+    title: String,
+    events: VecDeque<Event>,
+}
+
+impl Screen {
+    fn clear(&mut self) {
+        self.cells = vec![vec![Cell::default(); self.size.width]; self.size.height];
+    }
+}
+
+// A terminal that is a grid of cells in memory, for tests: they script the
+// events the editor reads and look at what it drew. Clones share the grid.
+#[derive(Clone, Default)]
+pub struct HeadlessTerminal {
+    screen: Rc<RefCell<Screen>>,
+}
+
+impl HeadlessTerminal {
+    pub fn new(size: Size) -> Self {
+        let mut screen = Screen {
+            size,
+            ..Screen::default()
+        };
+        screen.clear();
+        Self {
+            screen: Rc::new(RefCell::new(screen)),
+        }
+    }
+
+    // Adds events for read_event to return. Once they run out, it returns End.
+    pub fn push_events(&self, events: impl IntoIterator<Item = Event>) {
+        self.screen.borrow_mut().events.extend(events);
+    }
+
+    // The text on a row, without the blanks at its end
+    pub fn row(&self, row: usize) -> String {
+        self.screen.borrow().cells.get(row).map_or_else(String::new, |cells| {
+            let text: String = cells.iter().map(|cell| cell.grapheme.as_str()).collect();
+            text.trim_end().to_string()
+        })
+    }
+
+    pub fn cell(&self, position: Position) -> Option<Cell> {
+        self.screen
+            .borrow()
+            .cells
+            .get(position.row)
+            .and_then(|cells| cells.get(position.col))
+            .cloned()
+    }
+
+    // Where the caret is, or None while it is hidden
+    pub fn caret(&self) -> Option<Position> {
+        let screen = self.screen.borrow();
+        (!screen.caret_hidden).then_some(screen.caret)
+    }
+
+    pub fn title(&self) -> String {
+        self.screen.borrow().title.clone()
+    }
+}
+
+impl Terminal for HeadlessTerminal {
+    fn initialize(&mut self) -> Result<(), Error> {
+        self.clear_screen()
+    }
+
+    fn terminate(&mut self) -> Result<(), Error> {
+        self.show_caret()
+    }
+
+    fn size(&self) -> Result<Size, Error> {
+        Ok(self.screen.borrow().size)
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), Error> {
+        title.clone_into(&mut self.screen.borrow_mut().title);
+        Ok(())
+    }
+
+    fn clear_screen(&mut self) -> Result<(), Error> {
+        self.screen.borrow_mut().clear();
+        Ok(())
+    }
+
+    fn move_caret_to(&mut self, position: Position) -> Result<(), Error> {
+        self.screen.borrow_mut().caret = position;
+        Ok(())
+    }
+
+    fn hide_caret(&mut self) -> Result<(), Error> {
+        self.screen.borrow_mut().caret_hidden = true;
+        Ok(())
+    }
+
+    fn show_caret(&mut self) -> Result<(), Error> {
+        self.screen.borrow_mut().caret_hidden = false;
+        Ok(())
+    }
+
+    // Like a terminal without line wrap, text past the right edge is dropped
+    fn print_styled(&mut self, text: &str, style: ContentStyle) -> Result<(), Error> {
+        let mut screen = self.screen.borrow_mut();
+        let Position { row, mut col } = screen.caret;
+        for grapheme in text.graphemes(true) {
+            let width = grapheme.width().max(1);
+            if let Some(cells) = screen.cells.get_mut(row)
+                && col.saturating_add(width) <= cells.len()
+            {
+                for (offset, cell) in cells.iter_mut().skip(col).take(width).enumerate() {
+                    cell.grapheme = if offset == 0 { grapheme.to_string() } else { String::new() };
+                    cell.style = style;
+                }
+            }
+            col = col.saturating_add(width);
+        }
+        screen.caret.col = col;
+        Ok(())
+    }
+
+    fn execute(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Returns the next scripted event without waiting. A resize event resizes
+    // the grid too, as the terminal would have been.
+    fn read_event(&mut self, _timeout: Duration) -> Result<Input, Error> {
+        let mut screen = self.screen.borrow_mut();
+        let Some(event) = screen.events.pop_front() else {
+            return Ok(Input::End);
+        };
+        if let Event::Resize(width, height) = event {
+            screen.size = Size {
+                height: usize::from(height),
+                width: usize::from(width),
+            };
+            screen.clear();
+        }
+        Ok(Input::Event(event))
+    }
+}
@@ -0,0 +1,388 @@
+use std::{collections::HashMap, env, fs};
+
+use crossterm::style::{Attribute, Color, ContentStyle};
+
+use super::{AnnotationType, settings::config_dir};
+
+// The parts of the screen a theme can style
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    Text,
+    StatusBar,
+    MessageBar,
+    LineNumber,
+    CursorLine,
+    Tilde,
+    // Separators between windows and next to the explorer pane
+    Border,
+    Selection,
+    Match,
+    SelectedMatch,
+    Keyword,
+    Type,
+    Literal,
+    String,
+    Number,
+    Comment,
+    Heading,
+}
+
+impl Scope {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(Self::Text),
+            "statusbar" => Some(Self::StatusBar),
+            "messagebar" => Some(Self::MessageBar),
+            "linenumber" => Some(Self::LineNumber),
+            "cursorline" => Some(Self::CursorLine),
+            "tilde" => Some(Self::Tilde),
+            "border" => Some(Self::Border),
+            "selection" => Some(Self::Selection),
+            "match" => Some(Self::Match),
+            "selectedmatch" => Some(Self::SelectedMatch),
+            "keyword" => Some(Self::Keyword),
+            "type" => Some(Self::Type),
+            "literal" => Some(Self::Literal),
+            "string" => Some(Self::String),
+            "number" => Some(Self::Number),
+            "comment" => Some(Self::Comment),
+            "heading" => Some(Self::Heading),
+            _ => None,
+        }
+    }
+}
+
+impl From<AnnotationType> for Scope {
+    fn from(annotation_type: AnnotationType) -> Self {
+        match annotation_type {
+            AnnotationType::Selection => Self::Selection,
+            AnnotationType::Match => Self::Match,
+            AnnotationType::SelectedMatch => Self::SelectedMatch,
+            AnnotationType::Keyword => Self::Keyword,
+            AnnotationType::Type => Self::Type,
+            AnnotationType::Literal => Self::Literal,
+            AnnotationType::String => Self::String,
+            AnnotationType::Number => Self::Number,
+            AnnotationType::Comment => Self::Comment,
+            AnnotationType::Heading => Self::Heading,
+        }
+    }
+}
+
+// How many colours the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ColorSupport {
+    TrueColor,
+    Ansi256,
+    Ansi16,
+}
+
+impl ColorSupport {
+    pub fn detect() -> Self {
+        Self::from_env(
+            env::var("COLORTERM").ok().as_deref(),
+            env::var("TERM").ok().as_deref(),
+        )
+    }
+
+    fn from_env(colorterm: Option<&str>, term: Option<&str>) -> Self {
+        if matches!(colorterm, Some("truecolor" | "24bit")) {
+            Self::TrueColor
+        } else if term.is_some_and(|term| term.contains("256")) {
+            Self::Ansi256
+        } else {
+            Self::Ansi16
+        }
+    }
+}
+
+// Maps scopes to colours and attributes. Scopes a theme leaves out are drawn
+// like the default theme draws them.
+pub struct Theme {
+    name: String,
+    styles: HashMap<Scope, ContentStyle>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let mut theme = Self {
+            name: "default".to_string(),
+            styles: HashMap::new(),
+        };
+        let _ = theme.apply(DEFAULT);
+        theme
+    }
+}
+
+impl Theme {
+    // Loads <config dir>/themes/<name>.theme, or one of the built-in themes
+    pub fn load(name: &str) -> Result<Self, String> {
+        let text = config_dir()
+            .and_then(|directory| {
+                fs::read_to_string(directory.join("themes").join(format!("{name}.theme"))).ok()
+            })
+            .or_else(|| {
+                BUILT_IN
+                    .iter()
+                    .find(|(built_in, _)| *built_in == name)
+                    .map(|(_, text)| (*text).to_string())
+            })
+            .ok_or_else(|| format!("Cannot find color scheme '{name}'"))?;
+        let mut theme = Self {
+            name: name.to_string(),
+            ..Self::default()
+        };
+        theme
+            .apply(&text)
+            .map_err(|error| format!("Error in color scheme '{name}': {error}"))?;
+        Ok(theme)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn style(&self, scope: Scope) -> ContentStyle {
+        self.styles.get(&scope).copied().unwrap_or_default()
+    }
+
+    // Replaces colours the terminal can't show with the closest ones it can
+    pub fn with_color_support(mut self, support: ColorSupport) -> Self {
+        for style in self.styles.values_mut() {
+            style.foreground_color = style.foreground_color.map(|color| fit_color(color, support));
+            style.background_color = style.background_color.map(|color| fit_color(color, support));
+        }
+        self
+    }
+
+    // Reads lines like `keyword fg=#c678dd bg=236 bold`. Lines starting with
+    // `#` are comments.
+    fn apply(&mut self, text: &str) -> Result<(), String> {
+        for (index, line) in text.lines().enumerate() {
+            let mut words = line.split_whitespace();
+            let Some(scope_name) = words.next().filter(|word| !word.starts_with('#')) else {
+                continue;
+            };
+            let line_number = index.saturating_add(1);
+            let scope = Scope::from_name(scope_name)
+                .ok_or_else(|| format!("line {line_number}: unknown scope {scope_name}"))?;
+            let mut style = ContentStyle::new();
+            for word in words {
+                let invalid = || format!("line {line_number}: invalid value {word}");
+                if let Some(color) = word.strip_prefix("fg=") {
+                    style.foreground_color = Some(parse_color(color).ok_or_else(invalid)?);
+                } else if let Some(color) = word.strip_prefix("bg=") {
+                    style.background_color = Some(parse_color(color).ok_or_else(invalid)?);
+                } else {
+                    style.attributes.set(parse_attribute(word).ok_or_else(invalid)?);
+                }
+            }
+            self.styles.insert(scope, style);
+        }
+        Ok(())
+    }
+}
+
+// Draws one style on top of another: colours the top one sets replace those
+// below, and attributes add up
+pub fn layer(below: ContentStyle, above: ContentStyle) -> ContentStyle {
+    let mut style = below;
+    style.foreground_color = above.foreground_color.or(below.foreground_color);
+    style.background_color = above.background_color.or(below.background_color);
+    style.attributes.extend(above.attributes);
+    style
+}
+
+const DEFAULT: &str = "\
+statusbar reverse
+border reverse
+linenumber fg=darkgrey
+selection reverse
+match fg=black bg=darkyellow
+selectedmatch fg=black bg=yellow
+keyword fg=magenta
+type fg=cyan
+literal fg=darkyellow
+number fg=darkyellow
+string fg=green
+comment fg=darkgrey
+heading fg=blue bold
+";
+
+// A dark theme in 24-bit colour
+const DUSK: &str = "\
+text fg=#c5c8c6 bg=#1d1f21
+statusbar fg=#1d1f21 bg=#81a2be
+messagebar fg=#c5c8c6 bg=#1d1f21
+linenumber fg=#5c6370 bg=#1d1f21
+cursorline bg=#282a2e
+tilde fg=#373b41
+border fg=#373b41 bg=#373b41
+selection bg=#373b41
+match fg=#1d1f21 bg=#de935f
+selectedmatch fg=#1d1f21 bg=#f0c674
+keyword fg=#b294bb
+type fg=#8abeb7
+literal fg=#de935f
+number fg=#de935f
+string fg=#b5bd68
+comment fg=#969896 italic
+heading fg=#81a2be bold
+";
+
+const BUILT_IN: [(&str, &str); 2] = [("default", DEFAULT), ("dusk", DUSK)];
+
+// `#rrggbb`, a number from the 256-colour palette or one of the 16 named colours
+fn parse_color(text: &str) -> Option<Color> {
+    if let Some(hex) = text.strip_prefix('#') {
+        let channel = |range| hex.get(range).and_then(|digits| u8::from_str_radix(digits, 16).ok());
+        return (hex.len() == 6).then_some(Color::Rgb {
+            r: channel(0..2)?,
+            g: channel(2..4)?,
+            b: channel(4..6)?,
+        });
+    }
+    if let Ok(index) = text.parse::<u8>() {
+        return Some(Color::AnsiValue(index));
+    }
+    let name = text.replace('_', "").to_lowercase();
+    NAMED_COLORS
+        .iter()
+        .find(|(named, _, _)| *named == name)
+        .map(|(_, color, _)| *color)
+}
+
+fn parse_attribute(text: &str) -> Option<Attribute> {
+    match text {
+        "bold" => Some(Attribute::Bold),
+        "dim" => Some(Attribute::Dim),
+        "italic" => Some(Attribute::Italic),
+        "underline" => Some(Attribute::Underlined),
+        "reverse" => Some(Attribute::Reverse),
+        _ => None,
+    }
+}
+
+// The 16 named colours in palette order, with their usual (xterm) values
+const NAMED_COLORS: [(&str, Color, (u8, u8, u8)); 16] = [
+    ("black", Color::Black, (0, 0, 0)),
+    ("darkred", Color::DarkRed, (205, 0, 0)),
+    ("darkgreen", Color::DarkGreen, (0, 205, 0)),
+    ("darkyellow", Color::DarkYellow, (205, 205, 0)),
+    ("darkblue", Color::DarkBlue, (0, 0, 238)),
+    ("darkmagenta", Color::DarkMagenta, (205, 0, 205)),
+    ("darkcyan", Color::DarkCyan, (0, 205, 205)),
+    ("grey", Color::Grey, (229, 229, 229)),
+    ("darkgrey", Color::DarkGrey, (127, 127, 127)),
+    ("red", Color::Red, (255, 0, 0)),
+    ("green", Color::Green, (0, 255, 0)),
+    ("yellow", Color::Yellow, (255, 255, 0)),
+    ("blue", Color::Blue, (92, 92, 255)),
+    ("magenta", Color::Magenta, (255, 0, 255)),
+    ("cyan", Color::Cyan, (0, 255, 255)),
+    ("white", Color::White, (255, 255, 255)),
+];
+
+// The levels of each channel in the 6x6x6 colour cube of the 256-colour palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn fit_color(color: Color, support: ColorSupport) -> Color {
+    match (support, color) {
+        (ColorSupport::Ansi256, Color::Rgb { r, g, b }) => Color::AnsiValue(nearest_ansi_value((r, g, b))),
+        (ColorSupport::Ansi16, Color::Rgb { r, g, b }) => nearest_named((r, g, b)),
+        (ColorSupport::Ansi16, Color::AnsiValue(index)) => {
+            NAMED_COLORS
+                .get(usize::from(index))
+                .map_or_else(|| nearest_named(ansi_value_rgb(index)), |(_, color, _)| *color)
+        }
+        _ => color,
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    [a.0.abs_diff(b.0), a.1.abs_diff(b.1), a.2.abs_diff(b.2)]
+        .into_iter()
+        .map(|difference| u32::from(difference).saturating_mul(u32::from(difference)))
+        .fold(0, u32::saturating_add)
+}
+
+fn nearest_named(rgb: (u8, u8, u8)) -> Color {
+    NAMED_COLORS
+        .iter()
+        .min_by_key(|(_, _, named)| distance(rgb, *named))
+        .map_or(Color::Reset, |(_, color, _)| *color)
+}
+
+// The closest colour of the cube (16-231) or the grey ramp (232-255)
+fn nearest_ansi_value(rgb: (u8, u8, u8)) -> u8 {
+    let level = |channel: u8| {
+        CUBE_LEVELS
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, level)| level.abs_diff(channel))
+            .map_or(0, |(index, _)| index)
+    };
+    let cube = level(rgb.0)
+        .saturating_mul(36)
+        .saturating_add(level(rgb.1).saturating_mul(6))
+        .saturating_add(level(rgb.2))
+        .saturating_add(16);
+    (232..=255)
+        .chain(u8::try_from(cube))
+        .min_by_key(|&index| distance(rgb, ansi_value_rgb(index)))
+        .unwrap_or(0)
+}
+
+fn ansi_value_rgb(index: u8) -> (u8, u8, u8) {
+    if let Some((_, _, rgb)) = NAMED_COLORS.get(usize::from(index)) {
+        return *rgb;
+    }
+    if index >= 232 {
+        let grey = index.saturating_sub(232).saturating_mul(10).saturating_add(8);
+        return (grey, grey, grey);
+    }
+    let cube = usize::from(index.saturating_sub(16));
+    let level = |digit: Option<usize>| {
+        digit
+            .and_then(|digit| digit.checked_rem(6))
+            .and_then(|digit| CUBE_LEVELS.get(digit))
+            .copied()
+            .unwrap_or(0)
+    };
+    (
+        level(cube.checked_div(36)),
+        level(cube.checked_div(6)),
+        level(Some(cube)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_theme_colors() {
+        let mut theme = Theme::default();
+        assert!(theme.apply("# comment\nkeyword fg=#ff0000 bg=236 bold\n").is_ok());
+        let keyword = theme.style(Scope::Keyword);
+        assert_eq!(keyword.foreground_color, Some(Color::Rgb { r: 255, g: 0, b: 0 }));
+        assert_eq!(keyword.background_color, Some(Color::AnsiValue(236)));
+        assert!(keyword.attributes.has(Attribute::Bold));
+        // Scopes the theme leaves out keep their default style
+        assert_eq!(theme.style(Scope::String).foreground_color, Some(Color::Green));
+        assert!(theme.apply("keyword fg=#12345").is_err());
+        assert!(theme.apply("nosuchscope bold").is_err());
+
+        let theme = theme.with_color_support(ColorSupport::Ansi256);
+        assert_eq!(theme.style(Scope::Keyword).foreground_color, Some(Color::AnsiValue(196)));
+        let theme = theme.with_color_support(ColorSupport::Ansi16);
+        let keyword = theme.style(Scope::Keyword);
+        assert_eq!(keyword.foreground_color, Some(Color::Red));
+        assert_eq!(keyword.background_color, Some(Color::Black));
+        assert_eq!(fit_color(Color::Rgb { r: 8, g: 8, b: 8 }, ColorSupport::Ansi256), Color::AnsiValue(232));
+
+        assert_eq!(ColorSupport::from_env(Some("truecolor"), Some("xterm")), ColorSupport::TrueColor);
+        assert_eq!(ColorSupport::from_env(None, Some("xterm-256color")), ColorSupport::Ansi256);
+        assert_eq!(ColorSupport::from_env(None, Some("linux")), ColorSupport::Ansi16);
+    }
+}
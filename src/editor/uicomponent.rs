@@ -1,6 +1,6 @@
 use std::io::Error;
 
-use super::Size;
+use super::{Position, Size, Terminal};
 
 pub trait UIComponent {
     // Marks this UI component as in need of redrawing (or not)
@@ -19,9 +19,9 @@ pub trait UIComponent {
     fn set_size(&mut self, size: Size);
 
     // Draw this component if it's visible and in need of redrawing
-    fn render(&mut self, origin_y: usize){
+    fn render(&mut self, terminal: &mut dyn Terminal, origin: Position){
         if self.requires_redraw() {
-            match self.draw(origin_y) {
+            match self.draw(terminal, origin) {
                 Ok(()) => self.set_requires_redraw(false),
                 Err(err) => {
                     #[cfg(debug_assertions)]
@@ -32,8 +32,8 @@ pub trait UIComponent {
             }
         }
     }
-    // Draw this component at the given y-origin. Must be implemented by the component.
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error>;
+    // Draw this component with its top left corner at the given origin. Must be implemented by the component.
+    fn draw(&mut self, terminal: &mut dyn Terminal, origin: Position) -> Result<(), Error>;
 
 
 
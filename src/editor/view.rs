@@ -1,37 +1,107 @@
-use std::{cmp::min, io::Error};
+use std::{cell::RefCell, cmp::min, io::Error, mem, ops::Range, rc::Rc};
 
 use crate::editor::documentstatus::DocumentStatus;
 
 use super::{
     Line, NAME, Position, Size, Terminal, UIComponent, VERSION,
-    command::{Edit, Move},
+    annotatedstring::{AnnotatedString, Annotation, AnnotationType},
+    command::{Edit, Motion, Move, Operator, TextObject, TextObjectKind},
+    normalparser::{InsertPosition, Target},
+    registers::{Register, RegisterKind},
+    theme::Scope,
 };
 
 mod buffer;
-use buffer::Buffer;
+pub use buffer::Buffer;
 mod fileinfo;
+pub use fileinfo::LineEnding;
 use fileinfo::FileInfo;
-
-#[derive(Copy, Clone, Default)]
+mod filetype;
+pub use filetype::FileType;
+mod gutter;
+use gutter::Gutter;
+pub use gutter::LineNumbers;
+mod highlighter;
+use highlighter::Highlighter;
+mod history;
+use history::{History, Operation};
+mod indentation;
+pub use indentation::Indentation;
+mod marks;
+pub use marks::GlobalMark;
+use marks::Marks;
+mod motion;
+use motion::CharClass;
+mod operator;
+mod safewrite;
+use safewrite::write_atomically;
+mod swapfile;
+pub use swapfile::SwapFile;
+mod textobject;
+use operator::TextRange;
+mod search;
+pub use search::SearchDirection;
+use search::{IncrementalSearch, Search};
+mod selection;
+pub use selection::SelectionKind;
+mod substitute;
+pub use substitute::Substitution;
+mod wrap;
+pub use wrap::LineWrap;
+use selection::{BlockInsert, Selection};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Location {
     pub grapheme_index: usize,
     pub line_index: usize,
 }
+
+// Where a view was in a buffer, to return to when the buffer is shown again
+#[derive(Copy, Clone, Default)]
+pub struct ViewPosition {
+    pub text_location: Location,
+    pub scroll_offset: Position,
+}
+// The options each window has its own values of
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct WindowOptions {
+    pub line_numbers: LineNumbers,
+    pub line_wrap: LineWrap,
+    // Lines kept visible above and below the caret ('scrolloff')
+    pub scroll_off: usize,
+}
+
 #[derive(Default)]
 pub struct View {
-    buffer: Buffer,
+    buffer: Rc<RefCell<Buffer>>,
     requires_redraw: bool,
     size: Size,
     text_location: Location,
     scroll_offset: Position,
+    selection: Option<Selection>,
+    last_selected_lines: Option<(usize, usize)>,
+    block_insert: Option<BlockInsert>,
+    search: Option<Search>,
+    incremental_search: Option<IncrementalSearch>,
+    search_wrapped: Option<SearchDirection>,
+    substitution: Option<Substitution>,
+    // The revision of the buffer when it was last drawn
+    rendered_revision: usize,
+    // The line the caret was on when last drawn, which has the cursor line style
+    rendered_caret_line: usize,
+    gutter: Gutter,
+    line_wrap: LineWrap,
+    // When wrapping, the first row of the top line that is shown
+    top_row: usize,
+    scroll_off: usize,
 }
 
 impl View {
-    pub const fn is_file_loaded(&self) -> bool {
-        self.buffer.is_file_loaded()
+    pub fn is_file_loaded(&self) -> bool {
+        self.buffer.borrow().is_file_loaded()
     }
-    pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
-        self.buffer.save_as(file_name)
+    pub fn save_as(&mut self, file_name: &str, backup: bool) -> Result<(), Error> {
+        self.buffer.borrow_mut().save_as(file_name, backup)
     }
     pub fn handle_edit_command(&mut self, command: Edit) {
         match command {
@@ -57,45 +127,147 @@ impl View {
         self.scroll_text_location_into_view();
     }
 
-    pub fn load(&mut self, file_name: &str) -> Result<(), Error> {
-        // we escape here since we want to keep the original file name in case does not exist
-        // and the user wants to create it later
-        let buffer = Buffer::load(file_name)?;
+    pub fn buffer(&self) -> Rc<RefCell<Buffer>> {
+        Rc::clone(&self.buffer)
+    }
+
+    pub const fn position(&self) -> ViewPosition {
+        ViewPosition {
+            text_location: self.text_location,
+            scroll_offset: self.scroll_offset,
+        }
+    }
+
+    // Switches to another buffer, dropping whatever was going on in this one
+    pub fn show_buffer(&mut self, buffer: Rc<RefCell<Buffer>>, position: ViewPosition) {
         self.buffer = buffer;
+        self.selection = None;
+        self.block_insert = None;
+        self.incremental_search = None;
+        self.substitution = None;
+        self.scroll_offset = position.scroll_offset;
+        self.top_row = 0;
+        self.restore_location(position.text_location);
+    }
+
+    pub fn save(&mut self, backup: bool) -> Result<(), Error> {
+        self.buffer.borrow_mut().save(backup)
+    }
+
+    // The swap file left behind for the loaded file, if there is one
+    pub fn stale_swap_file(&self) -> Option<std::path::PathBuf> {
+        let path = SwapFile::path_for(self.buffer.borrow().file_info.get_path()?);
+        path.exists().then_some(path)
+    }
+
+    pub fn take_swap_error(&self) -> Option<Error> {
+        self.buffer.borrow_mut().take_swap_error()
+    }
+
+    pub fn recover(&mut self) -> Result<usize, Error> {
+        let count = self.buffer.borrow_mut().recover()?;
+        self.restore_location(Location::default());
+        Ok(count)
+    }
+
+    pub fn set_read_only(&mut self, read_only: bool) {
+        self.buffer.borrow_mut().read_only = read_only;
+    }
+
+    pub fn begin_change(&mut self) {
+        self.buffer.borrow_mut().begin_change(self.text_location);
+    }
+
+    pub fn end_change(&mut self) {
+        self.finish_block_insert();
+        self.buffer.borrow_mut().end_change();
+    }
+
+    // Returns false if there was nothing to undo
+    pub fn undo(&mut self) -> bool {
+        let Some(location) = self.buffer.borrow_mut().undo() else {
+            return false;
+        };
+        self.restore_location(location);
+        true
+    }
+
+    // Returns false if there was nothing to redo
+    pub fn redo(&mut self) -> bool {
+        let Some(location) = self.buffer.borrow_mut().redo() else {
+            return false;
+        };
+        self.restore_location(location);
+        true
+    }
+
+    // Inserts the text at the caret as if it had been typed
+    pub fn insert_text(&mut self, text: &str) {
+        self.text_location = self.buffer.borrow_mut().insert_text(self.text_location, text);
+        self.scroll_text_location_into_view();
         self.set_requires_redraw(true);
-        Ok(())
     }
 
-    pub fn save(&mut self) -> Result<(), Error> {
-        self.buffer.save()
+    // Moves the caret to where Insert mode should start, opening a new line if needed
+    pub fn prepare_insert(&mut self, position: InsertPosition) {
+        match position {
+            InsertPosition::BeforeCaret => {}
+            InsertPosition::LineStart => self.handle_move_command(Move::StartOfLine),
+            InsertPosition::AfterCaret => {
+                if self.text_location.grapheme_index
+                    < self.buffer.borrow().grapheme_count(self.text_location.line_index)
+                {
+                    self.handle_move_command(Move::Right);
+                }
+            }
+            InsertPosition::LineEnd => self.handle_move_command(Move::EndOfLine),
+            InsertPosition::LineBelow => {
+                self.handle_move_command(Move::EndOfLine);
+                self.handle_edit_command(Edit::InsertNewLine);
+            }
+            InsertPosition::LineAbove => {
+                self.handle_move_command(Move::StartOfLine);
+                self.handle_edit_command(Edit::InsertNewLine);
+                self.handle_move_command(Move::Up);
+            }
+        }
+    }
+
+    // Puts the caret at a location, or as close to it as the text allows
+    pub fn restore_location(&mut self, location: Location) {
+        self.text_location = location;
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
     }
 
     fn delete_backwards(&mut self) {
-        if self.text_location.line_index != 0 || self.text_location.grapheme_index != 0 {
+        if self.delete_soft_tab() {
+            self.scroll_text_location_into_view();
+            self.set_requires_redraw(true);
+        } else if self.text_location.line_index != 0 || self.text_location.grapheme_index != 0 {
             self.handle_move_command(Move::Left);
             self.delete();
         }
     }
 
     fn delete(&mut self) {
-        self.buffer.delete(self.text_location);
+        self.buffer.borrow_mut().delete(self.text_location);
         self.set_requires_redraw(true);
     }
 
     fn insert_char(&mut self, character: char) {
-        let old_len = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
-
-        self.buffer.insert_char(character, self.text_location);
-
-        let new_len = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+        if character == '\t' && self.insert_tab() {
+            self.scroll_text_location_into_view();
+            self.set_requires_redraw(true);
+            return;
+        }
+        let old_len = self.buffer.borrow().grapheme_count(self.text_location.line_index);
+
+        self.buffer.borrow_mut().insert_char(character, self.text_location);
+
+        let new_len = self.buffer.borrow().grapheme_count(self.text_location.line_index);
         let grapheme_delta = new_len.saturating_sub(old_len);
         if grapheme_delta > 0 {
             self.handle_move_command(Move::Right);
@@ -104,13 +276,115 @@ impl View {
     }
 
     fn insert_newline(&mut self) {
-        self.buffer.insert_newline(self.text_location);
+        self.buffer.borrow_mut().insert_newline(self.text_location);
         self.handle_move_command(Move::Right);
         self.set_requires_redraw(true);
     }
 
-    fn render_line(at: usize, line_text: &str) -> Result<(), Error> {
-        Terminal::print_row(at, line_text)
+    fn render_line(&self, terminal: &mut dyn Terminal, at: Position, line_text: &str) -> Result<(), Error> {
+        terminal.print_at(at, self.size.width, line_text)
+    }
+
+    // The columns taken by the gutter, which grows with the number of lines
+    fn gutter_width(&self) -> usize {
+        self.gutter
+            .width(self.buffer.borrow().height())
+            .min(self.size.width)
+    }
+
+    // The columns left for the text
+    fn text_width(&self) -> usize {
+        self.size.width.saturating_sub(self.gutter_width())
+    }
+
+    pub const fn window_options(&self) -> WindowOptions {
+        WindowOptions {
+            line_numbers: self.gutter.line_numbers,
+            line_wrap: self.line_wrap,
+            scroll_off: self.scroll_off,
+        }
+    }
+
+    pub fn set_window_options(&mut self, options: WindowOptions) {
+        if self.window_options() == options {
+            return;
+        }
+        self.gutter.line_numbers = options.line_numbers;
+        self.scroll_off = options.scroll_off;
+        self.set_line_wrap(options.line_wrap);
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+    }
+
+    // The lines to keep above and below the caret, at most half the window
+    fn scroll_margin(&self) -> usize {
+        self.scroll_off
+            .min(self.size.height.saturating_sub(1).checked_div(2).unwrap_or(0))
+    }
+
+    // Draws a line on as many rows as it takes (one unless wrapping), but only
+    // the ones in `rows`, counting from its first row. Returns the number of
+    // rows drawn.
+    fn render_annotated_line(
+        &self,
+        terminal: &mut dyn Terminal,
+        at: Position,
+        line_index: usize,
+        line: &Line,
+        mut annotations: Vec<Annotation>,
+        rows: Range<usize>,
+    ) -> Result<usize, Error> {
+        let gutter_width = self.gutter_width();
+        let text_width = self.size.width.saturating_sub(gutter_width);
+        // Search matches are drawn on top of syntax highlighting, and the selection on top of both
+        annotations.extend(self.match_annotations(line_index, line));
+        annotations.extend(self.selection_annotation(line_index));
+        annotations.extend(self.substitution_annotation(line_index));
+        let scope = if line_index == self.text_location.line_index {
+            Scope::CursorLine
+        } else {
+            Scope::Text
+        };
+        let skip_rows = rows.start;
+        let rows: Vec<AnnotatedString> = if self.line_wrap.wrap {
+            line.wrap(text_width, self.line_wrap.line_break)
+                .into_iter()
+                .skip(rows.start)
+                .take(rows.len())
+                .map(|range| line.get_annotated_graphemes(range, &annotations))
+                .collect()
+        } else {
+            let left = self.scroll_offset.col;
+            let right = left.saturating_add(text_width);
+            vec![line.get_annotated_visible_graphemes(left..right, &annotations)]
+        };
+        for (offset, text) in rows.iter().enumerate() {
+            let at = Position {
+                row: at.row.saturating_add(offset),
+                ..at
+            };
+            if gutter_width > 0 {
+                // Rows that continue a wrapped line have no number
+                let gutter = if offset == 0 && skip_rows == 0 {
+                    let line_count = self.buffer.borrow().height();
+                    self.gutter
+                        .text(line_index, self.text_location.line_index, line_count)
+                } else {
+                    String::new()
+                };
+                terminal.print_styled_at(at, gutter_width, &gutter, Scope::LineNumber)?;
+            }
+            terminal.print_annotated_at(
+                Position {
+                    col: at.col.saturating_add(gutter_width),
+                    ..at
+                },
+                text_width,
+                text,
+                scope,
+            )?;
+        }
+        Ok(rows.len())
     }
 
     fn build_welcome_message(width: usize) -> String {
@@ -129,11 +403,22 @@ impl View {
 
     fn scroll_vertically(&mut self, to: usize) {
         let Size { height, .. } = self.size;
-        let offset_changed = if to < self.scroll_offset.row {
-            self.scroll_offset.row = to;
+        // A view that isn't laid out yet has nowhere to scroll to
+        if height == 0 {
+            return;
+        }
+        let margin = self.scroll_margin();
+        let first = to.saturating_sub(margin);
+        // The margin below doesn't reach past the end of the buffer
+        let last = to
+            .saturating_add(margin)
+            .min(self.buffer.borrow().height().saturating_sub(1))
+            .max(to);
+        let offset_changed = if first < self.scroll_offset.row {
+            self.scroll_offset.row = first;
             true
-        } else if to >= self.scroll_offset.row.saturating_add(height) {
-            self.scroll_offset.row = to.saturating_sub(height).saturating_add(1);
+        } else if last >= self.scroll_offset.row.saturating_add(height) {
+            self.scroll_offset.row = last.saturating_sub(height).saturating_add(1);
             true
         } else {
             false
@@ -144,7 +429,10 @@ impl View {
     }
 
     fn scroll_horizontally(&mut self, to: usize) {
-        let Size { width, .. } = self.size;
+        let width = self.text_width();
+        if width == 0 {
+            return;
+        }
         let offset_changed = if to < self.scroll_offset.col {
             self.scroll_offset.col = to;
             true
@@ -160,18 +448,34 @@ impl View {
     }
 
     pub fn caret_position(&self) -> Position {
-        self.text_location_to_position()
-            .saturating_sub(self.scroll_offset)
+        let position = if self.line_wrap.wrap {
+            let (row, col) = self.wrapped_position(self.text_location);
+            let top = (self.scroll_offset.row, self.top_row);
+            Position {
+                row: self.rows_between(top, (self.text_location.line_index, row)),
+                // After the last grapheme of a full row, the caret stays on that row
+                col: col.min(self.text_width().saturating_sub(1)),
+            }
+        } else {
+            self.text_location_to_position()
+                .saturating_sub(self.scroll_offset)
+        };
+        Position {
+            col: position.col.saturating_add(self.gutter_width()),
+            ..position
+        }
     }
     fn text_location_to_position(&self) -> Position {
         let row = self.text_location.line_index;
-        let col = self.buffer.lines.get(row).map_or(0, |line| {
-            line.width_until(self.text_location.grapheme_index)
-        });
+        let col = self.buffer.borrow().column_of(self.text_location);
         Position { col, row }
     }
 
     fn scroll_text_location_into_view(&mut self) {
+        if self.line_wrap.wrap {
+            self.scroll_wrapped_into_view();
+            return;
+        }
         let Position { row, col } = self.text_location_to_position();
         self.scroll_vertically(row);
         self.scroll_horizontally(col);
@@ -189,14 +493,10 @@ impl View {
 
     #[allow(clippy::arithmetic_side_effects)]
     fn move_right(&mut self) {
-        let line_width = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+        let line_width = self.buffer.borrow().grapheme_count(self.text_location.line_index);
         if self.text_location.grapheme_index < line_width {
             self.text_location.grapheme_index += 1;
-        } else {
+        } else if self.text_location.line_index.saturating_add(1) < self.buffer.borrow().height() {
             self.move_to_start_of_line();
             self.move_down(1);
         }
@@ -217,33 +517,75 @@ impl View {
     }
 
     fn move_to_end_of_line(&mut self) {
-        self.text_location.grapheme_index = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, Line::grapheme_count);
+        self.text_location.grapheme_index =
+            self.buffer.borrow().grapheme_count(self.text_location.line_index);
+    }
+
+    // Where the caret can rest on a line in Normal mode: on its last grapheme
+    fn last_grapheme(&self, line_index: usize) -> usize {
+        self.buffer.borrow().grapheme_count(line_index).saturating_sub(1)
+    }
+
+    // Moves the caret back onto the last grapheme if it is after it, as it may
+    // be after an edit or coming from Insert mode
+    pub fn clamp_caret(&mut self) {
+        let last = self.last_grapheme(self.text_location.line_index);
+        if self.text_location.grapheme_index > last {
+            self.text_location.grapheme_index = last;
+            self.scroll_text_location_into_view();
+        }
+    }
+
+    // ESC from Insert mode puts the caret on the grapheme before it
+    pub fn leave_insert(&mut self) {
+        self.text_location.grapheme_index = self.text_location.grapheme_index.saturating_sub(1);
+        self.scroll_text_location_into_view();
     }
 
     fn snap_to_valid_grapheme(&mut self) {
-        self.text_location.grapheme_index = self
-            .buffer
-            .lines
-            .get(self.text_location.line_index)
-            .map_or(0, |line| {
-                min(line.grapheme_count(), self.text_location.grapheme_index)
-            });
+        self.text_location.grapheme_index = min(
+            self.buffer.borrow().grapheme_count(self.text_location.line_index),
+            self.text_location.grapheme_index,
+        );
     }
 
     fn snap_to_valid_line(&mut self) {
-        self.text_location.line_index = min(self.text_location.line_index, self.buffer.height());
+        self.text_location.line_index = min(
+            self.text_location.line_index,
+            self.buffer.borrow().height().saturating_sub(1),
+        );
+    }
+
+    pub fn line_ending(&self) -> LineEnding {
+        self.buffer.borrow().file_info.line_ending
+    }
+
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.buffer.borrow_mut().set_line_ending(line_ending);
+    }
+
+    pub fn file_type(&self) -> FileType {
+        self.buffer.borrow().file_type()
+    }
+
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.buffer.borrow_mut().set_file_type(file_type);
+        self.set_requires_redraw(true);
     }
 
     pub fn get_status(&self) -> DocumentStatus {
+        let buffer = self.buffer.borrow();
         DocumentStatus {
-            total_lines: self.buffer.height(),
+            total_lines: buffer.height(),
             current_line_index: self.text_location.line_index,
-            is_modified: self.buffer.dirty,
-            file_name: format!("{}", self.buffer.file_info),
+            is_modified: buffer.dirty,
+            file_name: if buffer.read_only {
+                format!("{} [RO]", buffer.file_info)
+            } else {
+                format!("{}", buffer.file_info)
+            },
+            file_format: buffer.file_info.format_description(),
+            file_type: buffer.file_type().name().to_string(),
         }
     }
 }
@@ -254,7 +596,10 @@ impl UIComponent for View {
     }
 
     fn requires_redraw(&self) -> bool {
+        // The buffer may have been changed through another window
         self.requires_redraw
+            || self.rendered_revision != self.buffer.borrow().revision()
+            || self.rendered_caret_line != self.text_location.line_index
     }
 
     fn set_size(&mut self, size: Size) {
@@ -262,31 +607,52 @@ impl UIComponent for View {
         self.scroll_text_location_into_view();
     }
 
-    fn draw(&mut self, origin_y: usize) -> Result<(), Error> {
+    fn draw(&mut self, terminal: &mut dyn Terminal, origin: Position) -> Result<(), Error> {
         let Size { height, width } = self.size;
-        let end_y = origin_y.saturating_add(height);
         // we allow this since we don't care if our welcome message is put _exactly_ in the top third.
         // it's allowed to be a bit too far up or down
         #[allow(clippy::integer_division)]
         let top_third = height / 3;
         let scroll_top = self.scroll_offset.row;
-        for current_row in origin_y..end_y {
-            // to get the correct line index, we have to take current_row (the absolute row on screen),
-            // subtract origin_y to get the current row relative to the view (ranging from 0 to self.size.height)
-            // and add the scroll offset.
-            let line_idx = current_row
-                .saturating_sub(origin_y)
-                .saturating_add(scroll_top);
-            if let Some(line) = self.buffer.lines.get(line_idx) {
-                let left = self.scroll_offset.col;
-                let right = self.scroll_offset.col.saturating_add(width);
-                Self::render_line(current_row, &line.get_visible_graphemes(left..right))?;
-            } else if current_row == top_third && self.buffer.is_empty() {
-                Self::render_line(current_row, &Self::build_welcome_message(width))?;
+        let mut highlights: Vec<Vec<Annotation>> = {
+            let mut buffer = self.buffer.borrow_mut();
+            (scroll_top..scroll_top.saturating_add(height))
+                .map(|line_index| buffer.highlight(line_index))
+                .collect()
+        };
+        let buffer = Rc::clone(&self.buffer);
+        let buffer = buffer.borrow();
+        // When wrapping, the top line may be shown from one of its later rows
+        let mut skip_rows = if self.line_wrap.wrap { self.top_row } else { 0 };
+        let mut row = 0;
+        let mut line_idx = scroll_top;
+        while row < height {
+            // row is relative to the view; the line shown there depends on the scroll offset
+            let at = Position {
+                row: origin.row.saturating_add(row),
+                col: origin.col,
+            };
+            if buffer.is_empty() && row == top_third {
+                self.render_line(terminal, at, &Self::build_welcome_message(width))?;
+                row = row.saturating_add(1);
+            } else if let Some(line) = buffer.line(line_idx) {
+                let syntax = highlights
+                    .get_mut(line_idx.saturating_sub(scroll_top))
+                    .map(mem::take)
+                    .unwrap_or_default();
+                let rows = height.saturating_sub(row);
+                let rows = skip_rows..skip_rows.saturating_add(rows);
+                let drawn = self.render_annotated_line(terminal, at, line_idx, &line, syntax, rows)?;
+                row = row.saturating_add(drawn);
             } else {
-                Self::render_line(current_row, "~")?;
+                terminal.print_styled_at(at, width, "~", Scope::Tilde)?;
+                row = row.saturating_add(1);
             }
+            line_idx = line_idx.saturating_add(1);
+            skip_rows = 0;
         }
+        self.rendered_revision = buffer.revision();
+        self.rendered_caret_line = self.text_location.line_index;
         Ok(())
     }
 }
@@ -1,119 +1,741 @@
 use std::io::{Write, Error};
-use std::fs::{read_to_string, File};
-use super::{Line, Location, FileInfo};
+use std::fs::{read_to_string, remove_file, File};
+use std::ops::Range;
+use ropey::Rope;
+use unicode_segmentation::UnicodeSegmentation;
+use super::{
+    Annotation, FileInfo, FileType, Highlighter, History, Indentation, Line, LineEnding, Location,
+    Marks, Operation, SwapFile, write_atomically,
+};
+
+const BOM: char = '\u{feff}';
+
+// The text is kept in a rope, so edits stay cheap regardless of file size.
+// Lines are handed out on demand: grapheme and width information is only
+// computed for the lines that are actually looked at.
+#[derive(Default)]
 pub struct Buffer {
-    pub lines: Vec<Line>,
+    text: Rope,
     pub dirty: bool,
-    pub file_info: FileInfo
+    pub file_info: FileInfo,
+    // Read-only buffers can be edited but not saved under their own name
+    pub read_only: bool,
+    history: History,
+    swap: Option<SwapFile>,
+    // Set when the swap file could not be created, so that it isn't tried
+    // again on every edit until the file is saved
+    swap_failed: bool,
+    // Why, until the editor reports it
+    swap_error: Option<Error>,
+    // Counts the changes to the text, so views know when to redraw
+    revision: usize,
+    file_type: FileType,
+    highlighter: Highlighter,
+    indentation: Indentation,
+    marks: Marks,
 }
 
-impl Default for Buffer {
-    fn default() -> Self {
-        Self {
-            lines: vec![Line::default()], // Start with at least one empty line
-            dirty: false,
-            file_info: FileInfo::default(),
-        }
+// The text of a line, without its line break
+fn line_of(text: &Rope, line_index: usize) -> Option<String> {
+    let line = text.get_line(line_index)?;
+    let mut line_str = line.to_string();
+    if line_str.ends_with('\n') {
+        line_str.pop();
     }
+    Some(line_str)
+}
+
+// The length of a line, if it is ASCII. Then every char is a grapheme of its
+// own, so edits on it don't need the line segmented into graphemes.
+fn ascii_line_length(text: &Rope, line_index: usize) -> Option<usize> {
+    let line = text.get_line(line_index)?;
+    let length = line.len_chars();
+    (line.len_bytes() == length).then(|| {
+        if line.chars_at(length).prev() == Some('\n') {
+            length.saturating_sub(1)
+        } else {
+            length
+        }
+    })
 }
 
 impl Buffer {
     pub fn load(file_name: &str) -> Result<Self, Error> {
         let contents = read_to_string(file_name)?;
-        let mut lines = Vec::new();
-        for value in contents.lines() {
-            lines.push(Line::from(value));
+        let mut file_info = FileInfo::from(file_name);
+        let contents = match contents.strip_prefix(BOM) {
+            Some(contents) => {
+                file_info.bom = true;
+                contents
+            }
+            None => contents.as_str(),
+        };
+        // The buffer itself always separates lines with \n
+        file_info.line_ending = LineEnding::detect(contents);
+        let mut contents = match file_info.line_ending {
+            LineEnding::Lf => contents.to_string(),
+            LineEnding::CrLf => contents.replace("\r\n", "\n"),
+            LineEnding::Cr => contents.replace('\r', "\n"),
+        };
+        file_info.final_newline = contents.ends_with('\n');
+        if file_info.final_newline {
+            contents.pop();
         }
 
-        let buffer = Self {
-            lines,
-            dirty: false,
-            file_info: FileInfo::from(file_name),
+        let mut buffer = Self {
+            text: Rope::from_str(&contents),
+            file_info,
+            ..Self::default()
         };
+        buffer.detect_file_type();
 
         Ok(buffer)
     }
+    // A buffer for a file that doesn't exist yet
+    pub fn new_file(file_name: &str) -> Self {
+        let mut buffer = Self {
+            file_info: FileInfo::from(file_name),
+            ..Self::default()
+        };
+        buffer.detect_file_type();
+        buffer
+    }
+
+    pub const fn file_type(&self) -> FileType {
+        self.file_type
+    }
+
+    pub fn set_file_type(&mut self, file_type: FileType) {
+        self.file_type = file_type;
+        self.highlighter = Highlighter::new(file_type.syntax());
+    }
+
+    pub const fn indentation(&self) -> Indentation {
+        self.indentation
+    }
+
+    // Changing the tab stop changes how every line looks, so views redraw
+    pub fn set_indentation(&mut self, indentation: Indentation) {
+        self.indentation = indentation;
+        self.revision = self.revision.wrapping_add(1);
+    }
+
+    fn detect_file_type(&mut self) {
+        let first_line = self.line_str(0).unwrap_or_default();
+        self.set_file_type(FileType::detect(&self.file_info, &first_line));
+    }
+
+    // The syntax highlighting of a line, by grapheme index
+    pub fn highlight(&mut self, line_index: usize) -> Vec<Annotation> {
+        let text = &self.text;
+        let tokens = self
+            .highlighter
+            .highlight(line_index, |index| line_of(text, index));
+        if tokens.is_empty() {
+            return Vec::new();
+        }
+        // On an ASCII line, char and grapheme indices are the same
+        let line = match ascii_line_length(text, line_index) {
+            Some(_) => None,
+            None => self.line(line_index),
+        };
+        let grapheme_index = |char_index| {
+            line.as_ref()
+                .map_or(char_index, |line: &Line| line.grapheme_index(char_index))
+        };
+        tokens
+            .into_iter()
+            .map(|(range, kind)| Annotation {
+                kind,
+                start: grapheme_index(range.start),
+                end: grapheme_index(range.end),
+            })
+            .collect()
+    }
+
     pub fn is_empty(&self) -> bool {
-        self.lines.is_empty()
+        self.text.len_chars() == 0
     }
 
     pub const fn is_file_loaded(&self) -> bool {
         self.file_info.has_path()
     }
 
+    // A buffer always has at least one (possibly empty) line
     pub fn height(&self) -> usize {
-        self.lines.len()
+        self.text.len_lines()
+    }
+
+    pub fn line(&self, line_index: usize) -> Option<Line> {
+        self.line_str(line_index)
+            .map(|line_str| Line::with_tab_stop(&line_str, self.indentation.tab_stop))
+    }
+
+    pub fn grapheme_count(&self, line_index: usize) -> usize {
+        ascii_line_length(&self.text, line_index).unwrap_or_else(|| {
+            self.line_str(line_index)
+                .map_or(0, |line_str| line_str.graphemes(true).count())
+        })
+    }
+
+    // The screen column a location is at, counted from the start of its line
+    pub fn column_of(&self, at: Location) -> usize {
+        self.line_str(at.line_index).map_or(0, |line_str| {
+            Line::prefix_width(&line_str, at.grapheme_index, self.indentation.tab_stop)
+        })
+    }
+
+    pub fn line_str(&self, line_index: usize) -> Option<String> {
+        line_of(&self.text, line_index)
     }
 
     pub fn insert_char(&mut self, character: char, at: Location) {
-        if at.line_index > self.height(){
+        if at.line_index >= self.height() {
             return;
         }
-        if at.line_index == self.height(){
-            self.lines.push(Line::from(&character.to_string()));
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index){
-            line.insert_char(character, at.grapheme_index);
-            self.dirty = true;
-        }
+        let at = self.char_index_of(at);
+        self.apply(Operation::Insert {
+            at,
+            text: character.to_string(),
+        });
     }
 
     pub fn delete(&mut self, at: Location) {
-        if let Some(line) = self.lines.get(at.line_index){
-            if at.grapheme_index >= line.grapheme_count() 
-                && self.height() > at.line_index.saturating_add(1)
-            {
-                let next_line = self.lines.remove(at.line_index.saturating_add(1));
-                #[allow(clippy::indexing_slicing)]
-                self.lines[at.line_index].append(&next_line);
-                self.dirty = true;
-
-            } else if at.grapheme_index < line.grapheme_count() {
-                self.lines[at.line_index].delete(at.grapheme_index);
-                self.dirty = true;
+        if at.line_index < self.height() {
+            let start = self.char_index_of(at);
+            let end = if at.grapheme_index < self.grapheme_count(at.line_index) {
+                self.char_index_of(Location {
+                    grapheme_index: at.grapheme_index.saturating_add(1),
+                    ..at
+                })
+            } else if self.height() > at.line_index.saturating_add(1) {
+                // Deleting at the end of a line joins it with the next one
+                start.saturating_add(1)
+            } else {
+                return;
+            };
+            let text = self.text.slice(start..end).to_string();
+            self.apply(Operation::Delete { at: start, text });
+        }
+    }
+
+    pub fn insert_newline(&mut self, at: Location) {
+        if at.line_index >= self.height() {
+            return;
+        }
+        let at = self.char_index_of(at);
+        self.apply(Operation::Insert {
+            at,
+            text: String::from("\n"),
+        });
+    }
+
+    pub fn text_in(&self, start: Location, end: Location) -> String {
+        let start = self.char_index_of(start);
+        let end = self.char_index_of(end).max(start);
+        self.text.slice(start..end).to_string()
+    }
+
+    // Returns the given lines, each terminated by a newline
+    pub fn lines_text(&self, first: usize, last: usize) -> String {
+        (first..=last)
+            .filter_map(|line_index| self.line_str(line_index))
+            .map(|line_str| line_str + "\n")
+            .collect()
+    }
+
+    // Inserts the text and returns the location right after it
+    pub fn insert_text(&mut self, at: Location, text: &str) -> Location {
+        if at.line_index >= self.height() || text.is_empty() {
+            return at;
+        }
+        let start = self.char_index_of(at);
+        self.apply(Operation::Insert {
+            at: start,
+            text: text.to_string(),
+        });
+        self.location_of(start.saturating_add(text.chars().count()))
+    }
+
+    pub fn delete_text(&mut self, start: Location, end: Location) -> String {
+        let text = self.text_in(start, end);
+        if !text.is_empty() {
+            let at = self.char_index_of(start);
+            self.apply(Operation::Delete {
+                at,
+                text: text.clone(),
+            });
+        }
+        text
+    }
+
+    // Replaces the chars in the given range of the line. Unlike the other edits, the
+    // range may start or end in the middle of a grapheme.
+    pub fn replace_in_line(&mut self, line_index: usize, chars: Range<usize>, text: &str) {
+        if line_index >= self.height() {
+            return;
+        }
+        let line_start = self.text.line_to_char(line_index);
+        let start = line_start.saturating_add(chars.start);
+        let end = line_start.saturating_add(chars.end).max(start);
+        if end > start {
+            let deleted = self.text.slice(start..end).to_string();
+            self.apply(Operation::Delete {
+                at: start,
+                text: deleted,
+            });
+        }
+        if !text.is_empty() {
+            self.apply(Operation::Insert {
+                at: start,
+                text: text.to_string(),
+            });
+        }
+    }
+
+    // Removes the given lines entirely and returns them, each terminated by a newline
+    pub fn delete_lines(&mut self, first: usize, last: usize) -> String {
+        let last = last.min(self.height().saturating_sub(1));
+        if first > last {
+            return String::new();
+        }
+        let deleted = self.lines_text(first, last);
+        let mut start = self.text.line_to_char(first);
+        let end = if last.saturating_add(1) < self.height() {
+            self.text.line_to_char(last.saturating_add(1))
+        } else {
+            // There is no newline after the last line, so take the one before the first instead
+            start = start.saturating_sub(1);
+            self.text.len_chars()
+        };
+        let text = self.text.slice(start..end).to_string();
+        if !text.is_empty() {
+            self.apply(Operation::Delete { at: start, text });
+        }
+        deleted
+    }
+
+    // Groups all edits until the matching end_change into one undo step.
+    pub fn begin_change(&mut self, caret: Location) {
+        self.history.begin_change(caret, &self.marks);
+    }
+
+    pub fn end_change(&mut self) {
+        self.history.end_change();
+        self.dirty = self.history.is_modified();
+    }
+
+    // Reverts the last change and returns where the caret should be placed.
+    pub fn undo(&mut self) -> Option<Location> {
+        let change = self.history.undo()?;
+        let operations: Vec<Operation> = change
+            .operations
+            .iter()
+            .rev()
+            .map(Operation::inverse)
+            .collect();
+        let caret = change.caret_before;
+        let marks = std::mem::replace(&mut change.marks, self.marks.clone());
+        for operation in &operations {
+            self.perform(operation);
+        }
+        self.marks.restore(&marks);
+        self.dirty = self.history.is_modified();
+        Some(caret)
+    }
+
+    // Reapplies the last undone change and returns where the caret should be placed.
+    pub fn redo(&mut self) -> Option<Location> {
+        let change = self.history.redo()?;
+        let operations = change.operations.clone();
+        let caret = change.caret_after;
+        let marks = std::mem::replace(&mut change.marks, self.marks.clone());
+        for operation in &operations {
+            self.perform(operation);
+        }
+        self.marks.restore(&marks);
+        self.dirty = self.history.is_modified();
+        Some(caret)
+    }
+
+    fn apply(&mut self, operation: Operation) {
+        let caret_before = match &operation {
+            Operation::Insert { at, .. } | Operation::Delete { at, .. } => self.location_of(*at),
+        };
+        self.history.begin_change(caret_before, &self.marks);
+        self.perform(&operation);
+        let caret_after = match &operation {
+            Operation::Insert { at, text } => {
+                self.location_of(at.saturating_add(text.chars().count()))
             }
+            Operation::Delete { at, .. } => self.location_of(*at),
+        };
+        self.history.record(operation, caret_after);
+        self.history.end_change();
+        self.dirty = self.history.is_modified();
+    }
+
+    // Replaces the text with the one journaled in a swap file and replays the
+    // journaled operations as a single change. Returns how many there were.
+    pub fn recover(&mut self) -> Result<usize, Error> {
+        let path = self
+            .file_info
+            .get_path()
+            .map(SwapFile::path_for)
+            .ok_or_else(|| Error::other("No file name"))?;
+        let recovery = SwapFile::read(&path)?;
+        self.swap = None;
+        self.text = Rope::from_str(&recovery.base);
+        self.highlighter.invalidate(0);
+        self.history = History::default();
+        self.history.begin_change(Location::default(), &self.marks);
+        let count = recovery.operations.len();
+        for operation in recovery.operations {
+            self.apply(operation);
+        }
+        self.history.end_change();
+        if self.swap.is_none() && SwapFile::running_owner(&path).is_none() {
+            // Nothing was replayed, so nothing took the stale swap file's place
+            let _ = remove_file(&path);
         }
+        self.dirty = true;
+        Ok(count)
     }
 
-    pub fn insert_newline(&mut self, at: Location){
-        if at.line_index == self.height(){
-            self.lines.push(Line::default());
-            self.dirty = true;
-        } else if let Some(line) = self.lines.get_mut(at.line_index){
-            let new = line.split(at.grapheme_index);
-            self.lines.insert(at.line_index.saturating_add(1), new);
-            self.dirty = true;
+    fn journal(&mut self, operation: &Operation) {
+        if self.swap.is_none()
+            && !self.swap_failed
+            && !self.read_only
+            && let Some(path) = self.file_info.get_path()
+        {
+            match SwapFile::create(path, &self.text.to_string()) {
+                Ok(swap) => self.swap = Some(swap),
+                Err(error) => {
+                    self.swap_failed = true;
+                    self.swap_error = Some(error);
+                }
+            }
+        }
+        if let Some(swap) = &self.swap {
+            swap.record(operation);
         }
     }
 
-    pub fn save(&mut self) -> Result<(), Error> {
-         if let Some(path) = &self.file_info.path {
-            let mut file = File::create(path)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
+    // Why the swap file could not be created, once
+    pub const fn take_swap_error(&mut self) -> Option<Error> {
+        self.swap_error.take()
+    }
+
+    pub fn set_mark(&mut self, name: char, at: Location) {
+        self.marks.set(name, at);
+    }
+
+    // Where a mark is, kept within the text as it may have got shorter
+    pub fn mark(&self, name: char) -> Option<Location> {
+        let location = self.marks.get(name)?;
+        let line_index = location.line_index.min(self.height().saturating_sub(1));
+        Some(Location {
+            grapheme_index: location.grapheme_index.min(self.grapheme_count(line_index)),
+            line_index,
+        })
+    }
+
+    pub fn remove_mark(&mut self, name: char) {
+        self.marks.remove(name);
+    }
+
+    // Moves the marks along with the lines they are on, before the operation
+    // changes the text
+    fn move_marks(&mut self, operation: &Operation) {
+        match operation {
+            Operation::Insert { at, text } => {
+                let line_index = self.text.char_to_line(*at);
+                // Text put in front of a line pushes it down. An empty line
+                // can't tell, so it stays put like when o is typed on it.
+                let pushed = *at == self.text.line_to_char(line_index)
+                    && self.text.get_char(*at).is_some_and(|character| character != '\n');
+                let first = if pushed {
+                    line_index
+                } else {
+                    line_index.saturating_add(1)
+                };
+                self.marks.insert_lines(first, text.matches('\n').count());
+            }
+            Operation::Delete { at, text } => {
+                let end = at.saturating_add(text.chars().count()).min(self.text.len_chars());
+                let first = self.text.char_to_line(*at);
+                let last = self.text.char_to_line(end);
+                if first == last {
+                    return;
+                }
+                // Lines that lose both their text and a line break go away
+                let removed = if end == self.text.len_chars() && *at > 0 {
+                    first.saturating_add(1)..last.saturating_add(1)
+                } else if *at == self.text.line_to_char(first) {
+                    first..last
+                } else {
+                    first.saturating_add(1)..last
+                };
+                self.marks.join_lines(first, last, &removed);
             }
+        }
+    }
+
+    pub const fn revision(&self) -> usize {
+        self.revision
+    }
+
+    fn perform(&mut self, operation: &Operation) {
+        self.journal(operation);
+        self.revision = self.revision.wrapping_add(1);
+        let (Operation::Insert { at, .. } | Operation::Delete { at, .. }) = operation;
+        let line_index = self.text.char_to_line((*at).min(self.text.len_chars()));
+        self.highlighter.invalidate(line_index);
+        self.move_marks(operation);
+        match operation {
+            Operation::Insert { at, text } => self.text.insert(*at, text),
+            Operation::Delete { at, text } => {
+                let end = at.saturating_add(text.chars().count());
+                self.text.remove(*at..end);
+            }
+        }
+    }
+
+    fn char_index_of(&self, at: Location) -> usize {
+        let line_start = self.text.line_to_char(at.line_index);
+        let in_line = match ascii_line_length(&self.text, at.line_index) {
+            Some(length) => at.grapheme_index.min(length),
+            None => self.line_str(at.line_index).map_or(0, |line_str| {
+                line_str
+                    .graphemes(true)
+                    .take(at.grapheme_index)
+                    .map(|grapheme| grapheme.chars().count())
+                    .sum()
+            }),
+        };
+        line_start.saturating_add(in_line)
+    }
+
+    fn location_of(&self, char_index: usize) -> Location {
+        let char_index = char_index.min(self.text.len_chars());
+        let line_index = self.text.char_to_line(char_index);
+        let offset = char_index.saturating_sub(self.text.line_to_char(line_index));
+        let grapheme_index = match ascii_line_length(&self.text, line_index) {
+            Some(length) => offset.min(length),
+            None => self.line_str(line_index).map_or(0, |line_str| {
+                // The first grapheme that starts at or after the char
+                let mut chars_so_far: usize = 0;
+                line_str
+                    .graphemes(true)
+                    .take_while(|grapheme| {
+                        let before = chars_so_far;
+                        chars_so_far = chars_so_far.saturating_add(grapheme.chars().count());
+                        before < offset
+                    })
+                    .count()
+            }),
+        };
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    // Changing the line ending rewrites every line of the file, so it counts as a modification
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        if self.file_info.line_ending != line_ending {
+            self.file_info.line_ending = line_ending;
+            self.dirty = true;
+        }
+    }
+
+    pub fn save(&mut self, backup: bool) -> Result<(), Error> {
+        if self.read_only {
+            return Err(Error::other("File is read-only, use :w <filename>"));
+        }
+        if let Some(path) = self.file_info.get_path() {
+            write_atomically(path, backup, |file| self.write_text(file))?;
+            self.history.mark_saved();
             self.dirty = false;
+            // What is saved no longer needs to be recovered
+            self.swap = None;
+            self.swap_failed = false;
         }
         Ok(())
     }
 
-     pub fn save_as(&mut self, file_name: &str) -> Result<(), Error> {
-        let file_info = FileInfo::from(file_name);
-        self.save_to_file(&file_info)?;
-        self.file_info = file_info;
+    pub fn save_as(&mut self, file_name: &str, backup: bool) -> Result<(), Error> {
+        let path = FileInfo::from(file_name).path;
+        if let Some(path) = &path {
+            write_atomically(path, backup, |file| self.write_text(file))?;
+        }
+        self.file_info.path = path;
+        self.detect_file_type();
+        self.read_only = false;
+        self.history.mark_saved();
         self.dirty = false;
+        self.swap = None;
+        self.swap_failed = false;
         Ok(())
     }
 
-    fn save_to_file(&self, file_info: &FileInfo) -> Result<(), Error> {
-        if let Some(file_path) = &file_info.get_path() {
-            let mut file = File::create(file_path)?;
-            for line in &self.lines {
-                writeln!(file, "{line}")?;
+    // Writes the text back the way it was read: with the same line endings,
+    // byte order mark and (lack of) final newline
+    fn write_text(&self, file: &mut File) -> Result<(), Error> {
+        let FileInfo {
+            line_ending,
+            final_newline,
+            bom,
+            ..
+        } = self.file_info;
+        if bom {
+            write!(file, "{BOM}")?;
+        }
+        for chunk in self.text.chunks() {
+            if line_ending == LineEnding::Lf {
+                file.write_all(chunk.as_bytes())?;
+            } else {
+                file.write_all(chunk.replace('\n', line_ending.as_str()).as_bytes())?;
             }
         }
+        if final_newline {
+            file.write_all(line_ending.as_str().as_bytes())?;
+        }
         Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env::temp_dir, fs};
+
+    #[test]
+    fn test_save_preserves_file_format() {
+        let path = temp_dir().join(format!("amoxcalli-format-{}.txt", std::process::id()));
+        let file_name = path.to_string_lossy().to_string();
+        for contents in ["\u{feff}one\r\ntwo\r\n", "one\rtwo", "one\ntwo\r\n", "", "\n"] {
+            fs::write(&path, contents).unwrap();
+            let mut buffer = Buffer::load(&file_name).unwrap();
+            buffer.save(false).unwrap();
+            assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        }
 
-    
+        fs::write(&path, "one\r\ntwo").unwrap();
+        let mut buffer = Buffer::load(&file_name).unwrap();
+        assert_eq!(buffer.file_info.format_description(), "dos [noeol]");
+        assert_eq!(buffer.line_str(0).as_deref(), Some("one"));
+        buffer.set_line_ending(LineEnding::Lf);
+        buffer.save(false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "one\ntwo");
+
+        // A line emptied of its text still ends in a newline
+        fs::write(&path, "one\n").unwrap();
+        let mut buffer = Buffer::load(&file_name).unwrap();
+        let end = Location {
+            grapheme_index: 3,
+            line_index: 0,
+        };
+        buffer.delete_text(Location::default(), end);
+        buffer.save(false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "\n");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_swap_file_failure() {
+        let directory = temp_dir().join(format!("amoxcalli-noswap-{}", std::process::id()));
+        let path = directory.join("file.txt");
+        let mut buffer = Buffer {
+            file_info: FileInfo::from(path.to_string_lossy().as_ref()),
+            ..Buffer::default()
+        };
+        // The directory doesn't exist, which is reported once and not retried
+        buffer.insert_text(Location::default(), "one");
+        assert!(buffer.take_swap_error().is_some());
+        buffer.insert_text(Location::default(), "two");
+        assert!(buffer.take_swap_error().is_none());
+        assert!(buffer.swap.is_none());
+
+        // Saving the file tries again
+        fs::create_dir(&directory).unwrap();
+        buffer.save(false).unwrap();
+        buffer.insert_text(Location::default(), "three");
+        assert!(buffer.take_swap_error().is_none());
+        assert!(buffer.swap.is_some());
+        drop(buffer);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_only_newlines_break_lines() {
+        let path = temp_dir().join(format!("amoxcalli-breaks-{}.txt", std::process::id()));
+        let file_name = path.to_string_lossy().to_string();
+        // Form feeds and Unicode line separators are text like any other
+        fs::write(&path, "a\x0Cb\u{2028}\nc\n").unwrap();
+        let mut buffer = Buffer::load(&file_name).unwrap();
+        assert_eq!(buffer.height(), 2);
+        let line = buffer.lines_text(0, 0);
+        buffer.insert_text(Location::default(), &line);
+        buffer.save(false).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a\x0Cb\u{2028}\na\x0Cb\u{2028}\nc\n");
+        fs::remove_file(&path).unwrap();
+
+        // Edits land on grapheme boundaries whether or not the line is ASCII
+        let at = |grapheme_index| Location {
+            grapheme_index,
+            line_index: 0,
+        };
+        let mut buffer = Buffer::default();
+        buffer.insert_text(at(0), "ae\u{301}b");
+        assert_eq!(buffer.grapheme_count(0), 3);
+        buffer.insert_char('x', at(2));
+        buffer.delete(at(1));
+        assert_eq!(buffer.line_str(0).as_deref(), Some("axb"));
+        assert_eq!(buffer.insert_text(at(3), "yz"), at(5));
+        assert_eq!(buffer.grapheme_count(0), 5);
+    }
+
+    #[test]
+    fn test_marks_follow_lines() {
+        let at = |line_index, grapheme_index| Location {
+            grapheme_index,
+            line_index,
+        };
+        let mut buffer = Buffer {
+            text: Rope::from_str("one\n\ntwo\nthree\nfour"),
+            ..Buffer::default()
+        };
+        buffer.set_mark('a', at(1, 0));
+        buffer.set_mark('b', at(3, 2));
+        buffer.set_mark('c', at(4, 1));
+        // A line opened below the empty line, then one above the first
+        buffer.insert_newline(at(1, 0));
+        buffer.insert_text(at(0, 0), "zero\n");
+        assert_eq!(buffer.mark('a'), Some(at(2, 0)));
+        assert_eq!(buffer.mark('b'), Some(at(5, 2)));
+
+        buffer.delete_lines(3, 4);
+        assert_eq!(buffer.mark('a'), Some(at(2, 0)));
+        assert_eq!(buffer.mark('b'), Some(at(3, 2)));
+        // Joining lines keeps the marks on the joined line
+        buffer.delete(at(3, 5));
+        assert_eq!(buffer.mark('c'), Some(at(3, 1)));
+        buffer.delete_lines(3, 3);
+        assert_eq!(buffer.mark('b'), None);
+        assert_eq!(buffer.mark('c'), None);
+        assert_eq!(buffer.mark('a'), Some(at(2, 0)));
+
+        // Undoing the changes brings them back where they were, and redoing
+        // them takes them away again
+        buffer.undo();
+        assert_eq!(buffer.mark('b'), Some(at(3, 2)));
+        assert_eq!(buffer.mark('c'), Some(at(3, 1)));
+        buffer.undo();
+        assert_eq!(buffer.mark('c'), Some(at(4, 1)));
+        buffer.redo();
+        buffer.redo();
+        assert_eq!(buffer.mark('b'), None);
+        assert_eq!(buffer.mark('c'), None);
+    }
 }
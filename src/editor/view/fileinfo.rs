@@ -5,16 +5,94 @@ use std::{
 
 const NO_NAME: &str = "[No Name]";
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    Cr,
+}
+
+impl LineEnding {
+    // A file is taken to be in dos format if every line feed follows a carriage return,
+    // and in mac format if it only has carriage returns
+    pub fn detect(text: &str) -> Self {
+        let line_feeds = text.matches('\n').count();
+        if line_feeds > 0 && text.matches("\r\n").count() == line_feeds {
+            Self::CrLf
+        } else if line_feeds == 0 && text.contains('\r') {
+            Self::Cr
+        } else {
+            Self::Lf
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "unix" => Some(Self::Lf),
+            "dos" => Some(Self::CrLf),
+            "mac" => Some(Self::Cr),
+            _ => None,
+        }
+    }
+
+    // The name used by :set fileformat
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Lf => "unix",
+            Self::CrLf => "dos",
+            Self::Cr => "mac",
+        }
+    }
+
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Lf => "\n",
+            Self::CrLf => "\r\n",
+            Self::Cr => "\r",
+        }
+    }
+}
+
+#[derive(Debug)]
 pub struct FileInfo {
     pub path: Option<PathBuf>,
+    pub line_ending: LineEnding,
+    // Whether the last line ends with a line ending
+    pub final_newline: bool,
+    // Whether the file starts with a UTF-8 byte order mark
+    pub bom: bool,
+}
+
+impl Default for FileInfo {
+    fn default() -> Self {
+        Self {
+            path: None,
+            line_ending: LineEnding::default(),
+            final_newline: true,
+            bom: false,
+        }
+    }
 }
 
 impl FileInfo {
     pub fn from(file_name: &str) -> Self {
         Self {
             path: Some(PathBuf::from(file_name)),
+            ..Self::default()
+        }
+    }
+
+    // The file format as shown in the status bar, e.g. "dos [BOM]"
+    pub fn format_description(&self) -> String {
+        let mut description = self.line_ending.name().to_string();
+        if self.bom {
+            description.push_str(" [BOM]");
+        }
+        if !self.final_newline {
+            description.push_str(" [noeol]");
         }
+        description
     }
     pub fn get_path(&self) -> Option<&Path> {
         self.path.as_deref()
@@ -0,0 +1,231 @@
+use super::{
+    AnnotationType, FileInfo,
+    highlighter::{BlockComment, Syntax},
+};
+
+// The languages the editor knows how to highlight
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub enum FileType {
+    #[default]
+    Text,
+    Rust,
+    C,
+    Python,
+    Markdown,
+    Toml,
+    Json,
+}
+
+impl FileType {
+    // Detects the file type from the file's extension or, for scripts, the
+    // interpreter named in a `#!` line
+    pub fn detect(file_info: &FileInfo, first_line: &str) -> Self {
+        let extension = file_info
+            .get_path()
+            .and_then(|path| path.extension())
+            .map(|extension| extension.to_string_lossy().to_lowercase());
+        let by_extension = match extension.as_deref() {
+            Some("rs") => Self::Rust,
+            Some("c" | "h") => Self::C,
+            Some("py" | "pyw") => Self::Python,
+            Some("md" | "markdown") => Self::Markdown,
+            Some("toml") => Self::Toml,
+            Some("json") => Self::Json,
+            _ => Self::Text,
+        };
+        if by_extension != Self::Text {
+            return by_extension;
+        }
+        let Some(shebang) = first_line.strip_prefix("#!") else {
+            return Self::Text;
+        };
+        // Either `#!/usr/bin/python3` or `#!/usr/bin/env python3`
+        let mut words = shebang.split_whitespace();
+        let interpreter = match words.next().and_then(|path| path.rsplit('/').next()) {
+            Some("env") => words.find(|word| !word.starts_with('-')),
+            interpreter => interpreter,
+        };
+        match interpreter {
+            Some(name) if name.starts_with("python") => Self::Python,
+            _ => Self::Text,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "text" | "" => Some(Self::Text),
+            "rust" => Some(Self::Rust),
+            "c" => Some(Self::C),
+            "python" => Some(Self::Python),
+            "markdown" => Some(Self::Markdown),
+            "toml" => Some(Self::Toml),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Rust => "rust",
+            Self::C => "c",
+            Self::Python => "python",
+            Self::Markdown => "markdown",
+            Self::Toml => "toml",
+            Self::Json => "json",
+        }
+    }
+
+    pub const fn syntax(self) -> Option<&'static Syntax> {
+        match self {
+            Self::Text => None,
+            Self::Rust => Some(&RUST),
+            Self::C => Some(&C),
+            Self::Python => Some(&PYTHON),
+            Self::Markdown => Some(&MARKDOWN),
+            Self::Toml => Some(&TOML),
+            Self::Json => Some(&JSON),
+        }
+    }
+}
+
+const RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "static", "struct", "super", "trait", "type", "union",
+        "unsafe", "use", "where", "while",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    literals: &["true", "false"],
+    line_comment: Some("//"),
+    block_comment: Some(BlockComment {
+        open: "/*",
+        close: "*/",
+        nested: true,
+    }),
+    quotes: &[],
+    multiline_strings: &["\""],
+    line_prefixes: &[],
+    numbers: true,
+    char_literals: true,
+    capitalized_types: true,
+};
+
+const C: Syntax = Syntax {
+    keywords: &[
+        "auto", "break", "case", "const", "continue", "default", "do", "else", "enum", "extern",
+        "for", "goto", "if", "inline", "register", "restrict", "return", "sizeof", "static",
+        "struct", "switch", "typedef", "union", "volatile", "while",
+    ],
+    types: &[
+        "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void", "bool",
+        "size_t", "ssize_t", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t",
+        "uint32_t", "uint64_t", "FILE",
+    ],
+    literals: &["NULL", "true", "false"],
+    line_comment: Some("//"),
+    block_comment: Some(BlockComment {
+        open: "/*",
+        close: "*/",
+        nested: false,
+    }),
+    quotes: &['"', '\''],
+    multiline_strings: &[],
+    // Preprocessor directives
+    line_prefixes: &[("#", AnnotationType::Keyword)],
+    numbers: true,
+    char_literals: false,
+    capitalized_types: false,
+};
+
+const PYTHON: Syntax = Syntax {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield",
+    ],
+    types: &[
+        "int", "float", "complex", "str", "bytes", "bool", "list", "tuple", "dict", "set",
+        "frozenset", "object",
+    ],
+    literals: &["True", "False", "None"],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    multiline_strings: &["\"\"\"", "'''"],
+    line_prefixes: &[],
+    numbers: true,
+    char_literals: false,
+    capitalized_types: false,
+};
+
+const MARKDOWN: Syntax = Syntax {
+    keywords: &[],
+    types: &[],
+    literals: &[],
+    line_comment: None,
+    block_comment: Some(BlockComment {
+        open: "<!--",
+        close: "-->",
+        nested: false,
+    }),
+    // Inline code
+    quotes: &['`'],
+    // Fenced code blocks
+    multiline_strings: &["```"],
+    line_prefixes: &[("#", AnnotationType::Heading), (">", AnnotationType::Comment)],
+    numbers: false,
+    char_literals: false,
+    capitalized_types: false,
+};
+
+const TOML: Syntax = Syntax {
+    keywords: &[],
+    types: &[],
+    literals: &["true", "false", "inf", "nan"],
+    line_comment: Some("#"),
+    block_comment: None,
+    quotes: &['"', '\''],
+    multiline_strings: &["\"\"\"", "'''"],
+    // Table headers
+    line_prefixes: &[("[", AnnotationType::Heading)],
+    numbers: true,
+    char_literals: false,
+    capitalized_types: false,
+};
+
+const JSON: Syntax = Syntax {
+    keywords: &[],
+    types: &[],
+    literals: &["true", "false", "null"],
+    line_comment: None,
+    block_comment: None,
+    quotes: &['"'],
+    multiline_strings: &[],
+    line_prefixes: &[],
+    numbers: true,
+    char_literals: false,
+    capitalized_types: false,
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_file_type() {
+        let detect = |name: &str, first_line| FileType::detect(&FileInfo::from(name), first_line);
+        assert_eq!(detect("src/main.rs", ""), FileType::Rust);
+        assert_eq!(detect("README.MD", ""), FileType::Markdown);
+        assert_eq!(detect("Cargo.toml", "[package]"), FileType::Toml);
+        assert_eq!(detect("script", "#!/usr/bin/env -S python3 -u"), FileType::Python);
+        assert_eq!(detect("script", "#!/usr/bin/python"), FileType::Python);
+        assert_eq!(detect("script", "#!/bin/sh"), FileType::Text);
+        assert_eq!(detect("notes.txt", ""), FileType::Text);
+    }
+}
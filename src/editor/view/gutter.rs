@@ -0,0 +1,81 @@
+// Which line numbers are shown: absolute ('number'), relative to the caret
+// ('relativenumber') or both, where the caret's line shows its own number
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+pub struct LineNumbers {
+    pub absolute: bool,
+    pub relative: bool,
+}
+
+// Line numbers always take at least this many columns, like Vim's 'numberwidth'
+const MIN_NUMBER_WIDTH: usize = 3;
+
+// The columns left of the text: a sign column (for markers like diagnostics or
+// diff changes) followed by the line numbers and a space
+#[derive(Clone, Copy, Default)]
+pub struct Gutter {
+    pub line_numbers: LineNumbers,
+    pub sign_width: usize,
+}
+
+impl Gutter {
+    // The width of the gutter for a buffer with this many lines
+    pub fn width(&self, line_count: usize) -> usize {
+        self.sign_width.saturating_add(self.number_width(line_count))
+    }
+
+    fn number_width(&self, line_count: usize) -> usize {
+        if !self.line_numbers.absolute && !self.line_numbers.relative {
+            return 0;
+        }
+        line_count
+            .to_string()
+            .len()
+            .max(MIN_NUMBER_WIDTH)
+            .saturating_add(1)
+    }
+
+    // The text of the gutter next to a line
+    pub fn text(&self, line_index: usize, caret_line: usize, line_count: usize) -> String {
+        let signs = " ".repeat(self.sign_width);
+        let width = self.number_width(line_count).saturating_sub(1);
+        if width == 0 {
+            return signs;
+        }
+        let LineNumbers { absolute, relative } = self.line_numbers;
+        let number = line_index.saturating_add(1);
+        if !relative {
+            format!("{signs}{number:>width$} ")
+        } else if line_index != caret_line {
+            format!("{signs}{:>width$} ", line_index.abs_diff(caret_line))
+        } else if absolute {
+            // Like Vim, the caret's own line number stands out by being left-aligned
+            format!("{signs}{number:<width$} ")
+        } else {
+            format!("{signs}{:>width$} ", 0)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gutter_text() {
+        let mut gutter = Gutter::default();
+        assert_eq!(gutter.width(10), 0);
+        assert_eq!(gutter.text(0, 0, 10), "");
+        gutter.line_numbers.absolute = true;
+        assert_eq!(gutter.width(10), 4);
+        assert_eq!(gutter.width(12345), 6);
+        assert_eq!(gutter.text(4, 0, 10), "  5 ");
+        gutter.line_numbers.relative = true;
+        assert_eq!(gutter.text(4, 1, 10), "  3 ");
+        assert_eq!(gutter.text(1, 1, 10), "2   ");
+        gutter.line_numbers.absolute = false;
+        assert_eq!(gutter.text(1, 1, 10), "  0 ");
+        gutter.sign_width = 2;
+        assert_eq!(gutter.width(10), 6);
+        assert_eq!(gutter.text(0, 1, 10), "    1 ");
+    }
+}
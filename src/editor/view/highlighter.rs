@@ -0,0 +1,336 @@
+use std::ops::Range;
+
+use super::AnnotationType;
+
+// How a language is highlighted
+pub struct Syntax {
+    pub keywords: &'static [&'static str],
+    pub types: &'static [&'static str],
+    // Constants like true and false
+    pub literals: &'static [&'static str],
+    pub line_comment: Option<&'static str>,
+    pub block_comment: Option<BlockComment>,
+    // Quotes of strings that end with the line at the latest
+    pub quotes: &'static [char],
+    // Delimiters of strings that can span several lines, like Python's """
+    pub multiline_strings: &'static [&'static str],
+    // Lines starting with one of these (after indentation) are highlighted as a whole
+    pub line_prefixes: &'static [(&'static str, AnnotationType)],
+    pub numbers: bool,
+    // Whether 'x' is a character literal, but 'a on its own is not a string (a lifetime)
+    pub char_literals: bool,
+    // Whether words starting with an uppercase letter are types
+    pub capitalized_types: bool,
+}
+
+pub struct BlockComment {
+    pub open: &'static str,
+    pub close: &'static str,
+    // Whether block comments can be nested, as in Rust
+    pub nested: bool,
+}
+
+// What a line starts inside of, carried over from the line before
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+enum State {
+    #[default]
+    Normal,
+    BlockComment {
+        depth: usize,
+    },
+    // The index of the delimiter in multiline_strings
+    String(usize),
+}
+
+// Highlights the lines of a buffer, remembering the state each line starts in
+// so that a line can be highlighted without going over the whole file again
+#[derive(Default)]
+pub struct Highlighter {
+    syntax: Option<&'static Syntax>,
+    // The state at the start of each line, as far as it is known
+    states: Vec<State>,
+}
+
+impl Highlighter {
+    pub fn new(syntax: Option<&'static Syntax>) -> Self {
+        Self {
+            syntax,
+            states: Vec::new(),
+        }
+    }
+
+    // Forgets the states after a line that has changed
+    pub fn invalidate(&mut self, line_index: usize) {
+        self.states.truncate(line_index.saturating_add(1));
+    }
+
+    // Highlights a line, returning char ranges. The lines before it are
+    // tokenized first if the state the line starts in isn't known yet.
+    pub fn highlight(
+        &mut self,
+        line_index: usize,
+        line_str: impl Fn(usize) -> Option<String>,
+    ) -> Vec<(Range<usize>, AnnotationType)> {
+        let Some(syntax) = self.syntax else {
+            return Vec::new();
+        };
+        if self.states.is_empty() {
+            self.states.push(State::Normal);
+        }
+        while self.states.len() <= line_index {
+            let index = self.states.len().saturating_sub(1);
+            let state = self.states.last().copied().unwrap_or_default();
+            let Some(text) = line_str(index) else {
+                break;
+            };
+            self.states.push(tokenize(syntax, &text, state).1);
+        }
+        let state = self.states.get(line_index).copied().unwrap_or_default();
+        line_str(line_index).map_or_else(Vec::new, |text| tokenize(syntax, &text, state).0)
+    }
+}
+
+// Splits a line into highlighted char ranges, given the state it starts in.
+// Also returns the state the next line starts in.
+fn tokenize(syntax: &Syntax, line: &str, mut state: State) -> (Vec<(Range<usize>, AnnotationType)>, State) {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let length = chars.len();
+
+    if state == State::Normal {
+        let indented = line.trim_start();
+        if let Some((_, kind)) = syntax
+            .line_prefixes
+            .iter()
+            .find(|(prefix, _)| indented.starts_with(prefix))
+        {
+            let start = length.saturating_sub(indented.chars().count());
+            tokens.push((start..length, *kind));
+            return (tokens, state);
+        }
+    }
+
+    let mut index = 0;
+    while index < length {
+        let at = |text: &str| starts_with_at(&chars, index, text);
+        match state {
+            State::BlockComment { depth } => {
+                let (end, depth) = block_comment_end(syntax, &chars, index, depth);
+                tokens.push((index..end, AnnotationType::Comment));
+                index = end;
+                if depth == 0 {
+                    state = State::Normal;
+                } else {
+                    state = State::BlockComment { depth };
+                }
+            }
+            State::String(delimiter) => {
+                let quote = syntax.multiline_strings.get(delimiter).copied().unwrap_or("\"");
+                let (end, closed) = string_end(&chars, index, quote);
+                tokens.push((index..end, AnnotationType::String));
+                index = end;
+                if closed {
+                    state = State::Normal;
+                }
+            }
+            State::Normal => {
+                let previous_is_word = index
+                    .checked_sub(1)
+                    .and_then(|previous| chars.get(previous))
+                    .is_some_and(|&character| is_word_char(character));
+                let character = chars.get(index).copied().unwrap_or_default();
+                if syntax.line_comment.is_some_and(at) {
+                    tokens.push((index..length, AnnotationType::Comment));
+                    break;
+                } else if let Some(comment) = syntax.block_comment.as_ref().filter(|comment| at(comment.open)) {
+                    let start = index;
+                    index = index.saturating_add(comment.open.chars().count());
+                    let (end, depth) = block_comment_end(syntax, &chars, index, 1);
+                    tokens.push((start..end, AnnotationType::Comment));
+                    index = end;
+                    if depth > 0 {
+                        state = State::BlockComment { depth };
+                    }
+                } else if let Some(delimiter) = syntax.multiline_strings.iter().position(|quote| at(quote)) {
+                    let start = index;
+                    let quote = syntax.multiline_strings.get(delimiter).copied().unwrap_or("\"");
+                    index = index.saturating_add(quote.chars().count());
+                    let (end, closed) = string_end(&chars, index, quote);
+                    tokens.push((start..end, AnnotationType::String));
+                    index = end;
+                    if !closed {
+                        state = State::String(delimiter);
+                    }
+                } else if syntax.quotes.contains(&character) {
+                    let quote = character.to_string();
+                    let (end, _) = string_end(&chars, index.saturating_add(1), &quote);
+                    tokens.push((index..end, AnnotationType::String));
+                    index = end;
+                } else if syntax.char_literals
+                    && character == '\''
+                    && let Some(end) = char_literal_end(&chars, index)
+                {
+                    tokens.push((index..end, AnnotationType::String));
+                    index = end;
+                } else if syntax.numbers && character.is_ascii_digit() && !previous_is_word {
+                    let end = number_end(&chars, index);
+                    tokens.push((index..end, AnnotationType::Number));
+                    index = end;
+                } else if is_word_char(character) && !previous_is_word {
+                    let end = chars
+                        .iter()
+                        .skip(index)
+                        .position(|&character| !is_word_char(character))
+                        .map_or(length, |offset| index.saturating_add(offset));
+                    let word: String = chars.get(index..end).unwrap_or_default().iter().collect();
+                    if let Some(kind) = classify(syntax, &word) {
+                        tokens.push((index..end, kind));
+                    }
+                    index = end;
+                } else {
+                    index = index.saturating_add(1);
+                }
+            }
+        }
+    }
+    (tokens, state)
+}
+
+fn classify(syntax: &Syntax, word: &str) -> Option<AnnotationType> {
+    if syntax.keywords.contains(&word) {
+        Some(AnnotationType::Keyword)
+    } else if syntax.literals.contains(&word) {
+        Some(AnnotationType::Literal)
+    } else if syntax.types.contains(&word)
+        || syntax.capitalized_types && word.starts_with(|character: char| character.is_uppercase())
+    {
+        Some(AnnotationType::Type)
+    } else {
+        None
+    }
+}
+
+fn is_word_char(character: char) -> bool {
+    character.is_alphanumeric() || character == '_'
+}
+
+fn starts_with_at(chars: &[char], index: usize, text: &str) -> bool {
+    let mut rest = chars.iter().skip(index);
+    text.chars().all(|character| rest.next() == Some(&character))
+}
+
+// Where a block comment that is `depth` levels deep ends, and how deep it still
+// is at that point (0 once it is closed)
+fn block_comment_end(syntax: &Syntax, chars: &[char], mut index: usize, mut depth: usize) -> (usize, usize) {
+    let Some(BlockComment { open, close, nested }) = syntax.block_comment else {
+        return (chars.len(), 0);
+    };
+    while index < chars.len() {
+        if starts_with_at(chars, index, close) {
+            index = index.saturating_add(close.chars().count());
+            depth = depth.saturating_sub(1);
+            if depth == 0 {
+                return (index, 0);
+            }
+        } else if nested && starts_with_at(chars, index, open) {
+            index = index.saturating_add(open.chars().count());
+            depth = depth.saturating_add(1);
+        } else {
+            index = index.saturating_add(1);
+        }
+    }
+    (chars.len(), depth)
+}
+
+// Where a string ends (after its closing quote), skipping escaped characters.
+// Also returns whether it was closed on this line.
+fn string_end(chars: &[char], mut index: usize, quote: &str) -> (usize, bool) {
+    while index < chars.len() {
+        if chars.get(index) == Some(&'\\') {
+            index = index.saturating_add(2);
+        } else if starts_with_at(chars, index, quote) {
+            return (index.saturating_add(quote.chars().count()), true);
+        } else {
+            index = index.saturating_add(1);
+        }
+    }
+    (chars.len(), false)
+}
+
+// The end of a character literal like 'a' or '\n' starting at index
+fn char_literal_end(chars: &[char], index: usize) -> Option<usize> {
+    let content = index.saturating_add(1);
+    let closing = if chars.get(content) == Some(&'\\') {
+        // Escapes like '\u{1F600}' are a bit longer
+        chars
+            .iter()
+            .skip(content.saturating_add(2))
+            .take(10)
+            .position(|&character| character == '\'')
+            .map(|offset| content.saturating_add(2).saturating_add(offset))?
+    } else {
+        content.saturating_add(1)
+    };
+    (chars.get(closing) == Some(&'\'')).then(|| closing.saturating_add(1))
+}
+
+// Numbers may have a prefix like 0x, separators, a fraction and a suffix like u8
+fn number_end(chars: &[char], mut index: usize) -> usize {
+    while let Some(&character) = chars.get(index) {
+        let fraction = character == '.'
+            && chars
+                .get(index.saturating_add(1))
+                .is_some_and(char::is_ascii_digit);
+        if is_word_char(character) || fraction {
+            index = index.saturating_add(1);
+        } else {
+            break;
+        }
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::filetype::FileType;
+
+    #[test]
+    fn test_tokenize() {
+        let Some(rust) = FileType::Rust.syntax() else {
+            panic!("Rust has a syntax");
+        };
+        let (tokens, state) = tokenize(rust, "let x: u8 = 0x1f; /* a /* b */", State::Normal);
+        assert_eq!(
+            tokens,
+            vec![
+                (0..3, AnnotationType::Keyword),
+                (7..9, AnnotationType::Type),
+                (12..16, AnnotationType::Number),
+                (18..30, AnnotationType::Comment),
+            ]
+        );
+        // Nested comments stay open until every level is closed
+        assert_eq!(state, State::BlockComment { depth: 1 });
+        let (tokens, state) = tokenize(rust, "*/ 'a' &'b str \"s\\\"", state);
+        assert_eq!(
+            tokens,
+            vec![
+                (0..2, AnnotationType::Comment),
+                (3..6, AnnotationType::String),
+                (11..14, AnnotationType::Type),
+                (15..19, AnnotationType::String),
+            ]
+        );
+        assert_eq!(state, State::String(0));
+
+        let mut highlighter = Highlighter::new(FileType::Python.syntax());
+        let lines = ["x = '''doc", "still doc'''  # done", "None"];
+        let line_str = |index: usize| lines.get(index).map(ToString::to_string);
+        assert_eq!(
+            highlighter.highlight(1, line_str),
+            vec![(0..12, AnnotationType::String), (14..20, AnnotationType::Comment)]
+        );
+        assert_eq!(highlighter.highlight(2, line_str), vec![(0..4, AnnotationType::Literal)]);
+    }
+}
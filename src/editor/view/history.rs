@@ -0,0 +1,182 @@
+use super::{Location, Marks};
+
+// A single reversible edit. Positions are absolute char indices into the
+// buffer text (lines joined by '\n'), which stay valid regardless of how
+// graphemes get re-segmented around the edit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Insert { at: usize, text: String },
+    Delete { at: usize, text: String },
+}
+
+impl Operation {
+    pub fn inverse(&self) -> Self {
+        match self {
+            Self::Insert { at, text } => Self::Delete {
+                at: *at,
+                text: text.clone(),
+            },
+            Self::Delete { at, text } => Self::Insert {
+                at: *at,
+                text: text.clone(),
+            },
+        }
+    }
+}
+
+// A group of operations that is undone and redone as one step.
+pub struct Change {
+    pub operations: Vec<Operation>,
+    pub caret_before: Location,
+    pub caret_after: Location,
+    // The marks as they were before the change when it is to be undone, and
+    // after it when it is to be redone
+    pub marks: Marks,
+    revision: usize,
+}
+
+pub struct History {
+    undo_stack: Vec<Change>,
+    redo_stack: Vec<Change>,
+    pending: Option<Change>,
+    group_depth: usize,
+    next_revision: usize,
+    saved_revision: usize,
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+            pending: None,
+            group_depth: 0,
+            next_revision: 1,
+            saved_revision: 0,
+        }
+    }
+}
+
+impl History {
+    // Starts grouping all following operations into a single change, until
+    // the matching end_change. Nested groups are folded into the outer one.
+    pub fn begin_change(&mut self, caret: Location, marks: &Marks) {
+        if self.group_depth == 0 {
+            self.commit();
+            self.pending = Some(self.new_change(caret, marks.clone()));
+        }
+        self.group_depth = self.group_depth.saturating_add(1);
+    }
+
+    pub fn end_change(&mut self) {
+        self.group_depth = self.group_depth.saturating_sub(1);
+        if self.group_depth == 0 {
+            self.commit();
+        }
+    }
+
+    // Adds an operation to the change begun last
+    pub fn record(&mut self, operation: Operation, caret_after: Location) {
+        if let Some(change) = &mut self.pending {
+            change.operations.push(operation);
+            change.caret_after = caret_after;
+        }
+    }
+
+    // Pops the most recent change, to be reverted by the caller.
+    pub fn undo(&mut self) -> Option<&mut Change> {
+        self.commit();
+        let change = self.undo_stack.pop()?;
+        self.redo_stack.push(change);
+        self.redo_stack.last_mut()
+    }
+
+    // Pops the most recently undone change, to be reapplied by the caller.
+    pub fn redo(&mut self) -> Option<&mut Change> {
+        self.commit();
+        let change = self.redo_stack.pop()?;
+        self.undo_stack.push(change);
+        self.undo_stack.last_mut()
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.saved_revision = self.current_revision();
+    }
+
+    pub fn is_modified(&self) -> bool {
+        let pending_changes = self
+            .pending
+            .as_ref()
+            .is_some_and(|change| !change.operations.is_empty());
+        pending_changes || self.current_revision() != self.saved_revision
+    }
+
+    fn current_revision(&self) -> usize {
+        self.undo_stack.last().map_or(0, |change| change.revision)
+    }
+
+    fn new_change(&mut self, caret: Location, marks: Marks) -> Change {
+        let revision = self.next_revision;
+        self.next_revision = self.next_revision.saturating_add(1);
+        Change {
+            operations: Vec::new(),
+            caret_before: caret,
+            caret_after: caret,
+            marks,
+            revision,
+        }
+    }
+
+    fn commit(&mut self) {
+        if let Some(change) = self.pending.take() {
+            if change.operations.is_empty() {
+                return;
+            }
+            self.undo_stack.push(change);
+            self.redo_stack.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert(at: usize, text: &str) -> Operation {
+        Operation::Insert {
+            at,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_grouping_and_saved_state() {
+        let mut history = History::default();
+        let caret = Location::default();
+        let marks = Marks::default();
+        history.begin_change(caret, &marks);
+        history.record(insert(0, "a"), caret);
+        history.begin_change(caret, &marks);
+        history.record(insert(1, "b"), caret);
+        history.end_change();
+        history.end_change();
+        assert!(history.is_modified());
+
+        history.mark_saved();
+        assert!(!history.is_modified());
+
+        history.begin_change(caret, &marks);
+        history.record(insert(2, "c"), caret);
+        history.end_change();
+        assert!(history.is_modified());
+        assert_eq!(history.undo().map(|c| c.operations.len()), Some(1));
+        assert!(!history.is_modified());
+        assert_eq!(history.undo().map(|c| c.operations.len()), Some(2));
+        assert!(history.is_modified());
+        assert!(history.undo().is_none());
+
+        assert!(history.redo().is_some());
+        assert!(!history.is_modified());
+    }
+}
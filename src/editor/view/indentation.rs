@@ -0,0 +1,196 @@
+use crate::editor::line::DEFAULT_TAB_STOP;
+
+use super::{Location, UIComponent, View};
+
+// How a buffer is indented: how wide tabs are and what Tab, Backspace and the
+// shift operators put in or take out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Indentation {
+    // Tabs reach to the next multiple of this many columns ('tabstop')
+    pub tab_stop: usize,
+    // Columns added or removed by > and <, or the tab stop if 0 ('shiftwidth')
+    pub shift_width: usize,
+    // Columns Tab and Backspace move by in Insert mode, mixing spaces and tabs,
+    // or 0 to insert plain tabs ('softtabstop')
+    pub soft_tab_stop: usize,
+    // Indent with spaces only ('expandtab')
+    pub expand_tab: bool,
+}
+
+impl Default for Indentation {
+    fn default() -> Self {
+        Self {
+            tab_stop: DEFAULT_TAB_STOP,
+            shift_width: 4,
+            soft_tab_stop: 0,
+            expand_tab: false,
+        }
+    }
+}
+
+impl Indentation {
+    pub fn shift_width(&self) -> usize {
+        if self.shift_width == 0 {
+            self.tab_stop
+        } else {
+            self.shift_width
+        }
+    }
+
+    // Whether Tab inserts a tab character rather than whitespace up to a stop
+    pub const fn inserts_tab(&self) -> bool {
+        self.soft_tab_stop == 0 && !self.expand_tab
+    }
+
+    // The columns Tab in Insert mode moves to a multiple of
+    pub fn tab_width(&self) -> usize {
+        if self.soft_tab_stop == 0 {
+            self.tab_stop
+        } else {
+            self.soft_tab_stop
+        }
+    }
+
+    // Whitespace that fills the columns from `start` to `end`: as many tabs as
+    // fit followed by spaces, or only spaces with 'expandtab'
+    pub fn whitespace(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut column = start;
+        if !self.expand_tab {
+            loop {
+                let tab_end = next_stop(column, self.tab_stop);
+                if tab_end > end {
+                    break;
+                }
+                text.push('\t');
+                column = tab_end;
+            }
+        }
+        text.push_str(&" ".repeat(end.saturating_sub(column)));
+        text
+    }
+}
+
+impl View {
+    pub fn indentation(&self) -> Indentation {
+        self.buffer.borrow().indentation()
+    }
+
+    pub fn set_indentation(&mut self, indentation: Indentation) {
+        self.buffer.borrow_mut().set_indentation(indentation);
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+    }
+
+    // Tab in Insert mode: a tab character, or whitespace up to the next soft
+    // tab stop. Without 'expandtab', the blanks right before the caret are
+    // redone too, so that spaces adding up to a tab become one.
+    pub(super) fn insert_tab(&mut self) -> bool {
+        let indentation = self.indentation();
+        if indentation.inserts_tab() {
+            return false;
+        }
+        let Location {
+            line_index,
+            grapheme_index,
+        } = self.text_location;
+        let Some(line) = self.buffer.borrow().line(line_index) else {
+            return false;
+        };
+        let end = next_stop(line.width_until(grapheme_index), indentation.tab_width());
+        let blanks = if indentation.expand_tab {
+            0
+        } else {
+            line.graphemes()
+                .take(grapheme_index)
+                .collect::<Vec<_>>()
+                .iter()
+                .rev()
+                .take_while(|grapheme| **grapheme == " " || **grapheme == "\t")
+                .count()
+        };
+        let start = Location {
+            grapheme_index: grapheme_index.saturating_sub(blanks),
+            line_index,
+        };
+        let text = indentation.whitespace(line.width_until(start.grapheme_index), end);
+        let mut buffer = self.buffer.borrow_mut();
+        buffer.delete_text(start, self.text_location);
+        self.text_location = buffer.insert_text(start, &text);
+        true
+    }
+
+    // Backspace in Insert mode with 'softtabstop': spaces before the caret are
+    // deleted back to the previous soft tab stop
+    pub(super) fn delete_soft_tab(&mut self) -> bool {
+        let indentation = self.indentation();
+        let Location {
+            line_index,
+            grapheme_index,
+        } = self.text_location;
+        let Some(line) = self.buffer.borrow().line(line_index) else {
+            return false;
+        };
+        if indentation.soft_tab_stop == 0 || grapheme_index == 0 {
+            return false;
+        }
+        let column = line.width_until(grapheme_index);
+        let stop = previous_stop(column, indentation.soft_tab_stop);
+        let spaces = line
+            .graphemes()
+            .take(grapheme_index)
+            .collect::<Vec<_>>()
+            .iter()
+            .rev()
+            .take(column.saturating_sub(stop))
+            .take_while(|grapheme| **grapheme == " ")
+            .count();
+        if spaces < 2 {
+            return false;
+        }
+        let start = Location {
+            grapheme_index: grapheme_index.saturating_sub(spaces),
+            line_index,
+        };
+        self.buffer.borrow_mut().delete_text(start, self.text_location);
+        self.text_location = start;
+        true
+    }
+}
+
+// The first multiple of `width` after a column
+pub fn next_stop(column: usize, width: usize) -> usize {
+    let width = width.max(1);
+    column
+        .saturating_sub(column.checked_rem(width).unwrap_or(0))
+        .saturating_add(width)
+}
+
+// The last multiple of `width` before a column, or 0
+pub fn previous_stop(column: usize, width: usize) -> usize {
+    let before = column.saturating_sub(1);
+    before.saturating_sub(before.checked_rem(width.max(1)).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whitespace() {
+        let mut indentation = Indentation::default();
+        assert_eq!(indentation.whitespace(0, 12), "\t    ");
+        assert_eq!(indentation.whitespace(3, 17), "\t\t ");
+        assert_eq!(indentation.whitespace(3, 6), "   ");
+        indentation.expand_tab = true;
+        assert_eq!(indentation.whitespace(2, 8), "      ");
+        assert_eq!(next_stop(4, 4), 8);
+        assert_eq!(next_stop(5, 4), 8);
+        assert_eq!(previous_stop(8, 4), 4);
+        assert_eq!(previous_stop(6, 4), 4);
+        assert_eq!(previous_stop(0, 4), 0);
+        assert_eq!(indentation.shift_width(), 4);
+        indentation.shift_width = 0;
+        assert_eq!(indentation.shift_width(), 8);
+    }
+}
@@ -0,0 +1,63 @@
+use std::{collections::HashMap, ops::Range, path::PathBuf};
+
+use super::Location;
+
+// Where a global mark (A-Z) was set. Jumping to it opens the file if it isn't
+// open; while it is, the mark is kept in its buffer like the others.
+#[derive(Clone)]
+pub struct GlobalMark {
+    pub path: PathBuf,
+    pub location: Location,
+}
+
+// The marks set in a buffer: a-z with m, A-Z while the buffer holds them, and
+// ' for where the last jump was made from. They stay on the line they were set
+// on as lines are inserted or deleted above it, and go away with the line
+// until the change that deleted it is undone.
+#[derive(Clone, Default)]
+pub struct Marks {
+    marks: HashMap<char, Location>,
+}
+
+impl Marks {
+    pub fn set(&mut self, name: char, location: Location) {
+        self.marks.insert(name, location);
+    }
+
+    pub fn get(&self, name: char) -> Option<Location> {
+        self.marks.get(&name).copied()
+    }
+
+    pub fn remove(&mut self, name: char) {
+        self.marks.remove(&name);
+    }
+
+    // Puts back the marks saved before a change that is undone or redone,
+    // leaving those set since alone, like Vim does
+    pub fn restore(&mut self, saved: &Self) {
+        self.marks.extend(saved.marks.iter());
+    }
+
+    // `count` lines were inserted in front of line `first`
+    pub fn insert_lines(&mut self, first: usize, count: usize) {
+        for location in self.marks.values_mut() {
+            if location.line_index >= first {
+                location.line_index = location.line_index.saturating_add(count);
+            }
+        }
+    }
+
+    // Lines `first` to `last` were joined into one, with the lines in `removed`
+    // taken out entirely
+    pub fn join_lines(&mut self, first: usize, last: usize, removed: &Range<usize>) {
+        self.marks
+            .retain(|_, location| !removed.contains(&location.line_index));
+        for location in self.marks.values_mut() {
+            if location.line_index > last {
+                location.line_index = location.line_index.saturating_sub(last.saturating_sub(first));
+            } else if location.line_index > first {
+                location.line_index = first;
+            }
+        }
+    }
+}
@@ -0,0 +1,433 @@
+use super::{Location, Motion, View};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum CharClass {
+    Blank,
+    Word,
+    Punctuation,
+}
+
+impl CharClass {
+    pub fn of(grapheme: &str) -> Self {
+        match grapheme.chars().next() {
+            None => Self::Blank,
+            Some(character) if character.is_whitespace() => Self::Blank,
+            Some(character) if character.is_alphanumeric() || character == '_' => Self::Word,
+            Some(_) => Self::Punctuation,
+        }
+    }
+}
+
+impl View {
+    // Moves the caret according to the motion. Returns false if the motion failed,
+    // e.g. because the character searched for was not found.
+    pub fn handle_motion(&mut self, motion: Motion, count: Option<usize>) -> bool {
+        let Some(mut target) = self.motion_target(motion, count) else {
+            return false;
+        };
+        // Without a selection this is Normal mode, where the caret rests on a
+        // grapheme: $ stops on the last one, and l fails there
+        if self.selection.is_none() {
+            let last = self.last_grapheme(target.line_index);
+            target.grapheme_index = target.grapheme_index.min(last);
+            if motion == Motion::Right && target == self.text_location {
+                return false;
+            }
+        }
+        self.text_location = target;
+        self.scroll_text_location_into_view();
+        true
+    }
+
+    pub fn motion_target(&mut self, motion: Motion, count: Option<usize>) -> Option<Location> {
+        let origin = self.text_location;
+        let steps = count.unwrap_or(1);
+        let target = match motion {
+            Motion::Left => Location {
+                grapheme_index: origin.grapheme_index.saturating_sub(steps),
+                ..origin
+            },
+            Motion::Right => Location {
+                grapheme_index: origin
+                    .grapheme_index
+                    .saturating_add(steps)
+                    .min(self.buffer.borrow().grapheme_count(origin.line_index)),
+                ..origin
+            },
+            Motion::Up => self.moved(|view| view.move_up(steps)),
+            Motion::Down => self.moved(|view| view.move_down(steps)),
+            Motion::DisplayUp | Motion::DisplayDown if !self.line_wrap.wrap => {
+                let up = motion == Motion::DisplayUp;
+                self.moved(|view| {
+                    if up {
+                        view.move_up(steps);
+                    } else {
+                        view.move_down(steps);
+                    }
+                })
+            }
+            Motion::DisplayUp => self.display_row_target(steps, true),
+            Motion::DisplayDown => self.display_row_target(steps, false),
+            Motion::LineStart => self.moved(Self::move_to_start_of_line),
+            Motion::LineEnd => self.moved(|view| {
+                view.move_down(steps.saturating_sub(1));
+                view.move_to_end_of_line();
+            }),
+            Motion::FirstNonBlank => self.first_non_blank(origin.line_index),
+            Motion::FileStart => self.first_non_blank(count.map_or(0, |line| line.saturating_sub(1))),
+            Motion::FileEnd => self.first_non_blank(
+                count.unwrap_or(self.buffer.borrow().height()).saturating_sub(1),
+            ),
+            Motion::WordForward => (0..steps).fold(origin, |from, _| self.next_word_start(from)),
+            Motion::WordBackward => (0..steps).fold(origin, |from, _| self.prev_word_start(from)),
+            Motion::WordEnd => (0..steps).fold(origin, |from, _| self.next_word_end(from)),
+            Motion::FindForward(character) => self.find_in_line(character, steps, false)?,
+            Motion::TillForward(character) => self.find_in_line(character, steps, true)?,
+            Motion::MatchingPair => self.matching_pair()?,
+            Motion::NextMatch => self.next_match(false, steps)?,
+            Motion::PreviousMatch => self.next_match(true, steps)?,
+            Motion::Mark(name) => self.buffer.borrow().mark(name)?,
+            Motion::MarkLine(name) => {
+                let line_index = self.buffer.borrow().mark(name)?.line_index;
+                self.first_non_blank(line_index)
+            }
+        };
+        // Plain h, j, k and l fail when they can't move at all, and j and k
+        // when they can't reach another line
+        let vertical = matches!(motion, Motion::Up | Motion::Down)
+            || matches!(motion, Motion::DisplayUp | Motion::DisplayDown) && !self.line_wrap.wrap;
+        let stuck = if vertical {
+            target.line_index == origin.line_index
+        } else {
+            matches!(
+                motion,
+                Motion::Left | Motion::Right | Motion::DisplayUp | Motion::DisplayDown
+            ) && target.line_index == origin.line_index
+                && target.grapheme_index == origin.grapheme_index
+        };
+        if stuck { None } else { Some(target) }
+    }
+
+    // Runs one of the movement helpers and reports where it would have put the caret.
+    fn moved(&mut self, movement: impl FnOnce(&mut Self)) -> Location {
+        let origin = self.text_location;
+        movement(self);
+        let target = self.text_location;
+        self.text_location = origin;
+        target
+    }
+
+    pub fn line_classes(&self, line_index: usize) -> Vec<CharClass> {
+        self.buffer.borrow().line(line_index).map_or_else(Vec::new, |line| {
+            line.graphemes().map(CharClass::of).collect()
+        })
+    }
+
+    pub fn first_non_blank(&self, line_index: usize) -> Location {
+        let line_index = line_index.min(self.buffer.borrow().height().saturating_sub(1));
+        let classes = self.line_classes(line_index);
+        let grapheme_index = classes
+            .iter()
+            .position(|class| *class != CharClass::Blank)
+            .unwrap_or(classes.len());
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    fn next_word_start(&self, from: Location) -> Location {
+        let mut line_index = from.line_index;
+        let mut classes = self.line_classes(line_index);
+        let mut index = from.grapheme_index;
+        if let Some(&class) = classes.get(index)
+            && class != CharClass::Blank
+        {
+            while classes.get(index) == Some(&class) {
+                index = index.saturating_add(1);
+            }
+        }
+        loop {
+            while classes.get(index) == Some(&CharClass::Blank) {
+                index = index.saturating_add(1);
+            }
+            if index < classes.len() {
+                break;
+            }
+            if line_index.saturating_add(1) >= self.buffer.borrow().height() {
+                index = classes.len();
+                break;
+            }
+            line_index = line_index.saturating_add(1);
+            classes = self.line_classes(line_index);
+            index = 0;
+            // An empty line counts as a word of its own
+            if classes.is_empty() {
+                break;
+            }
+        }
+        Location {
+            grapheme_index: index,
+            line_index,
+        }
+    }
+
+    fn next_word_end(&self, from: Location) -> Location {
+        let mut line_index = from.line_index;
+        let mut classes = self.line_classes(line_index);
+        let mut index = from.grapheme_index.saturating_add(1);
+        loop {
+            while classes.get(index) == Some(&CharClass::Blank) {
+                index = index.saturating_add(1);
+            }
+            if index < classes.len() {
+                break;
+            }
+            if line_index.saturating_add(1) >= self.buffer.borrow().height() {
+                return Location {
+                    grapheme_index: classes.len().saturating_sub(1),
+                    line_index,
+                };
+            }
+            line_index = line_index.saturating_add(1);
+            classes = self.line_classes(line_index);
+            index = 0;
+        }
+        if let Some(&class) = classes.get(index) {
+            while classes.get(index.saturating_add(1)) == Some(&class) {
+                index = index.saturating_add(1);
+            }
+        }
+        Location {
+            grapheme_index: index,
+            line_index,
+        }
+    }
+
+    fn prev_word_start(&self, from: Location) -> Location {
+        let mut line_index = from.line_index;
+        let mut classes = self.line_classes(line_index);
+        let mut index = from.grapheme_index.min(classes.len());
+        loop {
+            if index == 0 {
+                if line_index == 0 {
+                    return Location::default();
+                }
+                line_index = line_index.saturating_sub(1);
+                classes = self.line_classes(line_index);
+                index = classes.len();
+                if classes.is_empty() {
+                    return Location {
+                        grapheme_index: 0,
+                        line_index,
+                    };
+                }
+                continue;
+            }
+            index = index.saturating_sub(1);
+            if classes.get(index) != Some(&CharClass::Blank) {
+                break;
+            }
+        }
+        if let Some(&class) = classes.get(index) {
+            while index > 0 && classes.get(index.saturating_sub(1)) == Some(&class) {
+                index = index.saturating_sub(1);
+            }
+        }
+        Location {
+            grapheme_index: index,
+            line_index,
+        }
+    }
+
+    fn find_in_line(&self, character: char, count: usize, till: bool) -> Option<Location> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let line = self.buffer.borrow().line(line_index)?;
+        let wanted = character.to_string();
+        let found = line
+            .graphemes()
+            .enumerate()
+            .skip(grapheme_index.saturating_add(1))
+            .filter(|(_, grapheme)| *grapheme == wanted)
+            .nth(count.saturating_sub(1))?
+            .0;
+        Some(Location {
+            grapheme_index: if till { found.saturating_sub(1) } else { found },
+            line_index,
+        })
+    }
+
+    // Finds the bracket matching the first bracket at or after the caret on the current line
+    fn matching_pair(&self) -> Option<Location> {
+        const PAIRS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let line = self.buffer.borrow().line(line_index)?;
+        let (start_index, open, close, forward) = line
+            .graphemes()
+            .enumerate()
+            .skip(grapheme_index)
+            .find_map(|(index, grapheme)| {
+                PAIRS.iter().find_map(|&(open, close)| {
+                    if grapheme == open {
+                        Some((index, open, close, true))
+                    } else if grapheme == close {
+                        Some((index, open, close, false))
+                    } else {
+                        None
+                    }
+                })
+            })?;
+
+        let mut depth: usize = 0;
+        let mut current_line = line_index;
+        loop {
+            let graphemes: Vec<String> = self
+                .buffer
+                .borrow()
+                .line(current_line)?
+                .graphemes()
+                .map(str::to_string)
+                .collect();
+            let indices: Vec<usize> = if current_line == line_index {
+                if forward {
+                    (start_index..graphemes.len()).collect()
+                } else {
+                    (0..=start_index).rev().collect()
+                }
+            } else if forward {
+                (0..graphemes.len()).collect()
+            } else {
+                (0..graphemes.len()).rev().collect()
+            };
+            for index in indices {
+                let grapheme = graphemes.get(index).map(String::as_str);
+                let (opening, closing) = if forward { (open, close) } else { (close, open) };
+                if grapheme == Some(opening) {
+                    depth = depth.saturating_add(1);
+                } else if grapheme == Some(closing) {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return Some(Location {
+                            grapheme_index: index,
+                            line_index: current_line,
+                        });
+                    }
+                }
+            }
+            if forward {
+                current_line = current_line.saturating_add(1);
+                if current_line >= self.buffer.borrow().height() {
+                    return None;
+                }
+            } else {
+                current_line = current_line.checked_sub(1)?;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::{Buffer, ViewPosition};
+    use std::{cell::RefCell, rc::Rc};
+
+    fn view_of(text: &str) -> View {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(Location::default(), text);
+        let mut view = View::default();
+        view.show_buffer(Rc::new(RefCell::new(buffer)), ViewPosition::default());
+        view
+    }
+
+    const fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    // Where the motion goes from the location, if anywhere
+    fn target(view: &mut View, from: Location, motion: Motion, count: Option<usize>) -> Option<Location> {
+        view.text_location = from;
+        view.motion_target(motion, count)
+    }
+
+    #[test]
+    fn test_word_motions() {
+        let mut view = view_of("foo.bar(baz, [qux])\n\n  end; x");
+        // w stops at each run of word characters or of punctuation, and at
+        // empty lines
+        let mut starts = vec![at(0, 0)];
+        while let Some(&from) = starts.last()
+            && from != at(2, 7)
+        {
+            starts.push(target(&mut view, from, Motion::WordForward, None).unwrap());
+        }
+        let expected = [
+            (0, 0), (0, 3), (0, 4), (0, 7), (0, 8), (0, 11), (0, 13), (0, 14), (0, 17), (1, 0),
+            (2, 2), (2, 5), (2, 7),
+        ];
+        assert_eq!(starts, expected.map(|(line, grapheme)| at(line, grapheme)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::WordForward, Some(3)), Some(at(0, 7)));
+
+        // e skips empty lines, b doesn't
+        assert_eq!(target(&mut view, at(0, 0), Motion::WordEnd, None), Some(at(0, 2)));
+        assert_eq!(target(&mut view, at(0, 2), Motion::WordEnd, None), Some(at(0, 3)));
+        assert_eq!(target(&mut view, at(0, 17), Motion::WordEnd, None), Some(at(0, 18)));
+        assert_eq!(target(&mut view, at(0, 18), Motion::WordEnd, None), Some(at(2, 4)));
+        assert_eq!(target(&mut view, at(2, 2), Motion::WordBackward, None), Some(at(1, 0)));
+        assert_eq!(target(&mut view, at(1, 0), Motion::WordBackward, None), Some(at(0, 17)));
+        assert_eq!(target(&mut view, at(0, 4), Motion::WordBackward, None), Some(at(0, 3)));
+        assert_eq!(target(&mut view, at(2, 7), Motion::WordBackward, Some(2)), Some(at(2, 2)));
+        assert_eq!(target(&mut view, at(0, 2), Motion::WordBackward, Some(5)), Some(at(0, 0)));
+    }
+
+    #[test]
+    fn test_find_and_match_motions() {
+        let mut view = view_of("foo.bar(baz, [qux])\nfn x() {\n    if (a) { b }\n}");
+        assert_eq!(target(&mut view, at(0, 0), Motion::FindForward('a'), None), Some(at(0, 5)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::FindForward('a'), Some(2)), Some(at(0, 9)));
+        assert_eq!(target(&mut view, at(0, 5), Motion::FindForward('a'), None), Some(at(0, 9)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::FindForward('a'), Some(3)), None);
+        assert_eq!(target(&mut view, at(0, 0), Motion::TillForward('a'), None), Some(at(0, 4)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::TillForward('a'), Some(2)), Some(at(0, 8)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::FindForward('#'), None), None);
+
+        // % takes the first bracket at or after the caret, and skips nested pairs
+        assert_eq!(target(&mut view, at(0, 0), Motion::MatchingPair, None), Some(at(0, 18)));
+        assert_eq!(target(&mut view, at(0, 18), Motion::MatchingPair, None), Some(at(0, 7)));
+        assert_eq!(target(&mut view, at(0, 13), Motion::MatchingPair, None), Some(at(0, 17)));
+        assert_eq!(target(&mut view, at(0, 17), Motion::MatchingPair, None), Some(at(0, 13)));
+        assert_eq!(target(&mut view, at(1, 7), Motion::MatchingPair, None), Some(at(3, 0)));
+        assert_eq!(target(&mut view, at(3, 0), Motion::MatchingPair, None), Some(at(1, 7)));
+        assert_eq!(target(&mut view, at(2, 10), Motion::MatchingPair, None), Some(at(2, 15)));
+        assert_eq!(target(&mut view, at(3, 1), Motion::MatchingPair, None), None);
+    }
+
+    #[test]
+    fn test_line_motions() {
+        let mut view = view_of("one\n  two\n\tthree");
+        assert_eq!(target(&mut view, at(2, 3), Motion::FileStart, None), Some(at(0, 0)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::FileStart, Some(2)), Some(at(1, 2)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::FileEnd, None), Some(at(2, 1)));
+        assert_eq!(target(&mut view, at(2, 0), Motion::FileEnd, Some(1)), Some(at(0, 0)));
+        assert_eq!(target(&mut view, at(0, 0), Motion::FileEnd, Some(10)), Some(at(2, 1)));
+        assert_eq!(target(&mut view, at(1, 4), Motion::FirstNonBlank, None), Some(at(1, 2)));
+
+        // In Normal mode $ stops on the last grapheme, where l fails
+        view.text_location = at(1, 0);
+        assert!(view.handle_motion(Motion::LineEnd, None));
+        assert_eq!(view.text_location, at(1, 4));
+        assert!(!view.handle_motion(Motion::Right, None));
+        assert!(view.handle_motion(Motion::LineEnd, Some(2)));
+        assert_eq!(view.text_location, at(2, 5));
+        assert!(!view.handle_motion(Motion::Down, None));
+    }
+}
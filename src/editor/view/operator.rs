@@ -0,0 +1,519 @@
+use super::{
+    CharClass, Location, Motion, Operator, Register, RegisterKind, Target, UIComponent, View,
+};
+
+// The text an operator acts upon. Char ranges are end-exclusive, block ranges
+// span the display columns left..right on each of their lines.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum TextRange {
+    Chars {
+        start: Location,
+        end: Location,
+    },
+    Lines {
+        first: usize,
+        last: usize,
+    },
+    Block {
+        first: usize,
+        last: usize,
+        left: usize,
+        right: usize,
+    },
+}
+
+impl View {
+    // Applies the operator to the text covered by the target and returns that text,
+    // or None if the target's motion failed.
+    pub fn apply_operator(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<Register> {
+        let range = self.operator_range(operator, target, count)?;
+        Some(self.apply_operator_to_range(operator, range))
+    }
+
+    pub fn apply_operator_to_lines(
+        &mut self,
+        operator: Operator,
+        first: usize,
+        last: usize,
+    ) -> Register {
+        self.apply_operator_to_range(operator, TextRange::Lines { first, last })
+    }
+
+    pub fn apply_operator_to_range(&mut self, operator: Operator, range: TextRange) -> Register {
+        self.buffer.borrow_mut().begin_change(self.text_location);
+        let text = match operator {
+            Operator::Delete => self.delete_range(range),
+            Operator::Change => self.change_range(range),
+            Operator::Yank => self.yank_range(range),
+            Operator::ShiftRight | Operator::ShiftLeft => {
+                self.shift_range(range, operator == Operator::ShiftRight)
+            }
+            Operator::Lowercase => self.convert_case(range, str::to_lowercase),
+            Operator::Uppercase => self.convert_case(range, str::to_uppercase),
+            Operator::ToggleCase => self.convert_case(range, Self::toggle_case),
+        };
+        self.buffer.borrow_mut().end_change();
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+        let kind = match range {
+            TextRange::Chars { .. } => RegisterKind::Charwise,
+            TextRange::Lines { .. } => RegisterKind::Linewise,
+            TextRange::Block { .. } => RegisterKind::Blockwise,
+        };
+        Register::new(text, kind)
+    }
+
+    // Puts the register's text after (or before) the caret. Lines are put below
+    // (or above) the current line.
+    pub fn put(&mut self, register: &Register, before: bool, count: usize) {
+        let text = register.text.repeat(count);
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        self.buffer.borrow_mut().begin_change(self.text_location);
+        match register.kind {
+            RegisterKind::Charwise => {
+                let mut at = self.text_location;
+                if !before && grapheme_index < self.buffer.borrow().grapheme_count(line_index) {
+                    at.grapheme_index = grapheme_index.saturating_add(1);
+                }
+                let end = self.buffer.borrow_mut().insert_text(at, &text);
+                // Leave the caret on the last character that was put
+                self.text_location = if end.grapheme_index > 0 {
+                    Location {
+                        grapheme_index: end.grapheme_index.saturating_sub(1),
+                        ..end
+                    }
+                } else {
+                    end
+                };
+            }
+            RegisterKind::Linewise => {
+                let lines = text.strip_suffix('\n').unwrap_or(&text);
+                let target_line = if before {
+                    line_index
+                } else {
+                    line_index.saturating_add(1)
+                };
+                if before {
+                    self.buffer.borrow_mut().insert_newline(Location {
+                        grapheme_index: 0,
+                        line_index,
+                    });
+                } else {
+                    let line_end = self.line_end(line_index);
+                    self.buffer.borrow_mut().insert_newline(line_end);
+                }
+                self.buffer.borrow_mut().insert_text(
+                    Location {
+                        grapheme_index: 0,
+                        line_index: target_line,
+                    },
+                    lines,
+                );
+                self.text_location = self.first_non_blank(target_line);
+            }
+            RegisterKind::Blockwise => self.put_block(&register.text, before, count),
+        }
+        self.buffer.borrow_mut().end_change();
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+    }
+
+    fn operator_range(
+        &mut self,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<TextRange> {
+        let origin = self.text_location;
+        let motion = match target {
+            Target::Lines => {
+                let last = origin
+                    .line_index
+                    .saturating_add(count.unwrap_or(1).saturating_sub(1))
+                    .min(self.buffer.borrow().height().saturating_sub(1));
+                return Some(TextRange::Lines {
+                    first: origin.line_index,
+                    last,
+                });
+            }
+            Target::Object(object) => return self.text_object(object, count.unwrap_or(1)),
+            // cw on a word behaves like ce, leaving the following whitespace alone
+            Target::Motion(Motion::WordForward)
+                if operator == Operator::Change
+                    && self.line_classes(origin.line_index).get(origin.grapheme_index)
+                        != Some(&CharClass::Blank)
+                    && origin.grapheme_index < self.buffer.borrow().grapheme_count(origin.line_index) =>
+            {
+                Motion::WordEnd
+            }
+            Target::Motion(motion) => motion,
+        };
+        let target = self.motion_target(motion, count)?;
+        let (start, mut end) = if (target.line_index, target.grapheme_index)
+            < (origin.line_index, origin.grapheme_index)
+        {
+            (target, origin)
+        } else {
+            (origin, target)
+        };
+        if motion.is_linewise() {
+            return Some(TextRange::Lines {
+                first: start.line_index,
+                last: end.line_index,
+            });
+        }
+        if motion.is_inclusive() {
+            end.grapheme_index = end
+                .grapheme_index
+                .saturating_add(1)
+                .min(self.buffer.borrow().grapheme_count(end.line_index));
+        } else if end.line_index > start.line_index && end.grapheme_index == 0 {
+            // An exclusive motion that ends at the start of a line stops at the end
+            // of the previous one instead, so dw never joins lines
+            end.line_index = end.line_index.saturating_sub(1);
+            end.grapheme_index = self.buffer.borrow().grapheme_count(end.line_index);
+        }
+        Some(TextRange::Chars { start, end })
+    }
+
+    fn line_end(&self, line_index: usize) -> Location {
+        Location {
+            grapheme_index: self.buffer.borrow().grapheme_count(line_index),
+            line_index,
+        }
+    }
+
+    // Puts each line of the block into the following lines, at the caret's column
+    fn put_block(&mut self, text: &str, before: bool, count: usize) {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let grapheme_index = if before || self.buffer.borrow().grapheme_count(line_index) == 0 {
+            grapheme_index
+        } else {
+            grapheme_index.saturating_add(1)
+        };
+        let column = self
+            .buffer
+            .borrow()
+            .line(line_index)
+            .map_or(0, |line| line.width_until(grapheme_index));
+        for (offset, block_line) in text.split('\n').enumerate() {
+            let target_line = line_index.saturating_add(offset);
+            if target_line >= self.buffer.borrow().height() {
+                let last_line = self.buffer.borrow().height().saturating_sub(1);
+                let line_end = self.line_end(last_line);
+                self.buffer.borrow_mut().insert_newline(line_end);
+            }
+            let Some(line) = self.buffer.borrow().line(target_line) else {
+                break;
+            };
+            let mut at = Location {
+                grapheme_index: (0..=line.grapheme_count())
+                    .find(|&index| line.width_until(index) >= column)
+                    .unwrap_or_else(|| line.grapheme_count()),
+                line_index: target_line,
+            };
+            // Pad short lines so the block keeps its shape
+            let padding = column.saturating_sub(line.width());
+            if padding > 0 {
+                at = self.buffer.borrow_mut().insert_text(at, &" ".repeat(padding));
+            }
+            self.buffer.borrow_mut().insert_text(at, &block_line.repeat(count));
+        }
+        self.text_location = Location {
+            grapheme_index,
+            line_index,
+        };
+    }
+
+    // The char spans covered by the range, one per line for blocks
+    fn range_spans(&self, range: TextRange) -> Vec<(Location, Location)> {
+        match range {
+            TextRange::Chars { start, end } => vec![(start, end)],
+            TextRange::Lines { first, last } => vec![(
+                Location {
+                    grapheme_index: 0,
+                    line_index: first,
+                },
+                self.line_end(last),
+            )],
+            TextRange::Block {
+                first,
+                last,
+                left,
+                right,
+            } => (first..=last)
+                .map(|line_index| {
+                    let (start, end) = self.block_graphemes(line_index, left, right);
+                    (
+                        Location {
+                            grapheme_index: start,
+                            line_index,
+                        },
+                        Location {
+                            grapheme_index: end,
+                            line_index,
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn spans_text(&self, spans: &[(Location, Location)]) -> String {
+        spans
+            .iter()
+            .map(|(start, end)| self.buffer.borrow().text_in(*start, *end))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    fn delete_range(&mut self, range: TextRange) -> String {
+        match range {
+            TextRange::Chars { start, end } => {
+                self.text_location = start;
+                self.buffer.borrow_mut().delete_text(start, end)
+            }
+            TextRange::Lines { first, last } => {
+                let text = self.buffer.borrow_mut().delete_lines(first, last);
+                self.text_location = self.first_non_blank(first);
+                text
+            }
+            TextRange::Block { .. } => {
+                let spans = self.range_spans(range);
+                let text = self.spans_text(&spans);
+                for (start, end) in &spans {
+                    self.buffer.borrow_mut().delete_text(*start, *end);
+                }
+                if let Some((start, _)) = spans.first() {
+                    self.text_location = *start;
+                }
+                text
+            }
+        }
+    }
+
+    fn change_range(&mut self, range: TextRange) -> String {
+        match range {
+            TextRange::Chars { .. } | TextRange::Block { .. } => self.delete_range(range),
+            TextRange::Lines { first, last } => {
+                // Keep a single empty line to type into
+                let text = self.buffer.borrow().lines_text(first, last);
+                let start = Location {
+                    grapheme_index: 0,
+                    line_index: first,
+                };
+                let end = self.line_end(last);
+                self.buffer.borrow_mut().delete_text(start, end);
+                self.text_location = start;
+                text
+            }
+        }
+    }
+
+    fn yank_range(&mut self, range: TextRange) -> String {
+        match range {
+            TextRange::Chars { .. } | TextRange::Block { .. } => {
+                let spans = self.range_spans(range);
+                if let Some((start, _)) = spans.first() {
+                    self.text_location = *start;
+                }
+                self.spans_text(&spans)
+            }
+            TextRange::Lines { first, last } => {
+                self.text_location.line_index = first;
+                self.buffer.borrow().lines_text(first, last)
+            }
+        }
+    }
+
+    fn shift_range(&mut self, range: TextRange, right: bool) -> String {
+        let (first, last) = Self::lines_of(range);
+        let indentation = self.indentation();
+        for line_index in first..=last {
+            let Some(line) = self.buffer.borrow().line(line_index) else {
+                continue;
+            };
+            // Like Vim, empty lines are left alone
+            if right && line.grapheme_count() == 0 {
+                continue;
+            }
+            let indent_end = line
+                .graphemes()
+                .take_while(|grapheme| *grapheme == " " || *grapheme == "\t")
+                .count();
+            let width = line.width_until(indent_end);
+            let new_width = if right {
+                width.saturating_add(indentation.shift_width())
+            } else {
+                width.saturating_sub(indentation.shift_width())
+            };
+            let indent = indentation.whitespace(0, new_width);
+            if line.graphemes().take(indent_end).collect::<String>() == indent {
+                continue;
+            }
+            let line_start = Location {
+                grapheme_index: 0,
+                line_index,
+            };
+            let mut buffer = self.buffer.borrow_mut();
+            buffer.delete_text(
+                line_start,
+                Location {
+                    grapheme_index: indent_end,
+                    line_index,
+                },
+            );
+            buffer.insert_text(line_start, &indent);
+        }
+        self.text_location = self.first_non_blank(first);
+        String::new()
+    }
+
+    fn toggle_case(text: &str) -> String {
+        text.chars()
+            .flat_map(|character| {
+                let toggled: Vec<char> = if character.is_uppercase() {
+                    character.to_lowercase().collect()
+                } else {
+                    character.to_uppercase().collect()
+                };
+                toggled
+            })
+            .collect()
+    }
+
+    fn convert_case(&mut self, range: TextRange, conversion: fn(&str) -> String) -> String {
+        let spans = self.range_spans(range);
+        let text = self.spans_text(&spans);
+        for (start, end) in &spans {
+            let original = self.buffer.borrow().text_in(*start, *end);
+            let converted = conversion(&original);
+            if converted != original {
+                self.buffer.borrow_mut().delete_text(*start, *end);
+                self.buffer.borrow_mut().insert_text(*start, &converted);
+            }
+        }
+        if let Some((start, _)) = spans.first() {
+            self.text_location = *start;
+        }
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::{Buffer, Indentation, ViewPosition};
+    use std::{cell::RefCell, rc::Rc};
+
+    fn view_of(text: &str) -> View {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(Location::default(), text);
+        let mut view = View::default();
+        view.show_buffer(Rc::new(RefCell::new(buffer)), ViewPosition::default());
+        view
+    }
+
+    const fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    fn text_of(view: &View) -> String {
+        let buffer = view.buffer.borrow();
+        buffer.lines_text(0, buffer.height().saturating_sub(1))
+    }
+
+    // Applies the operator at the location, returning the text it acted upon
+    fn apply(
+        view: &mut View,
+        from: Location,
+        operator: Operator,
+        target: Target,
+        count: Option<usize>,
+    ) -> Option<String> {
+        view.text_location = from;
+        view.apply_operator(operator, target, count)
+            .map(|register| register.text)
+    }
+
+    #[test]
+    fn test_delete_and_change() {
+        let mut view = view_of("foo.bar baz\none");
+        let word = Target::Motion(Motion::WordForward);
+        assert_eq!(apply(&mut view, at(0, 0), Operator::Delete, word, None).as_deref(), Some("foo"));
+        assert_eq!(text_of(&view), ".bar baz\none\n");
+        // dw on the last word of a line doesn't join it with the next
+        assert_eq!(apply(&mut view, at(0, 5), Operator::Delete, word, None).as_deref(), Some("baz"));
+        assert_eq!(text_of(&view), ".bar \none\n");
+        // A motion that fails leaves the text alone
+        assert_eq!(apply(&mut view, at(0, 0), Operator::Delete, Target::Motion(Motion::FindForward('z')), None), None);
+        assert_eq!(text_of(&view), ".bar \none\n");
+
+        // c$ leaves the caret after the line to type there, cw acts like ce
+        let mut view = view_of("foo bar baz");
+        let line_end = Target::Motion(Motion::LineEnd);
+        assert_eq!(apply(&mut view, at(0, 4), Operator::Change, line_end, None).as_deref(), Some("bar baz"));
+        assert_eq!(text_of(&view), "foo \n");
+        assert_eq!(view.text_location, at(0, 4));
+        assert_eq!(apply(&mut view, at(0, 0), Operator::Change, word, None).as_deref(), Some("foo"));
+        assert_eq!(text_of(&view), " \n");
+        assert_eq!(view.text_location, at(0, 0));
+
+        // d2d, and cc keeping an empty line to type into
+        let mut view = view_of("one\ntwo\nthree");
+        assert_eq!(apply(&mut view, at(0, 1), Operator::Delete, Target::Lines, Some(2)).as_deref(), Some("one\ntwo\n"));
+        assert_eq!(text_of(&view), "three\n");
+        assert_eq!(apply(&mut view, at(0, 1), Operator::Change, Target::Lines, None).as_deref(), Some("three\n"));
+        assert_eq!(text_of(&view), "\n");
+    }
+
+    #[test]
+    fn test_convert_case() {
+        let mut view = view_of("straße déjà\nÉTÉ ǄEMAL\nAb");
+        apply(&mut view, at(0, 0), Operator::Uppercase, Target::Lines, None);
+        apply(&mut view, at(1, 0), Operator::Lowercase, Target::Lines, None);
+        apply(&mut view, at(2, 0), Operator::ToggleCase, Target::Lines, None);
+        assert_eq!(text_of(&view), "STRASSE DÉJÀ\nété ǆemal\naB\n");
+        apply(&mut view, at(0, 8), Operator::Lowercase, Target::Motion(Motion::WordForward), None);
+        assert_eq!(text_of(&view), "STRASSE déjà\nété ǆemal\naB\n");
+        assert_eq!(view.text_location, at(0, 8));
+    }
+
+    #[test]
+    fn test_shift() {
+        let mut view = view_of("x\n\n  y");
+        // 'shiftwidth' 4 and 'tabstop' 8, so two shifts make a tab
+        apply(&mut view, at(0, 0), Operator::ShiftRight, Target::Lines, Some(3));
+        assert_eq!(text_of(&view), "    x\n\n      y\n");
+        apply(&mut view, at(0, 0), Operator::ShiftRight, Target::Lines, None);
+        assert_eq!(text_of(&view), "\tx\n\n      y\n");
+        assert_eq!(view.text_location, at(0, 1));
+        apply(&mut view, at(2, 0), Operator::ShiftLeft, Target::Lines, None);
+        apply(&mut view, at(2, 0), Operator::ShiftLeft, Target::Lines, None);
+        assert_eq!(text_of(&view), "\tx\n\ny\n");
+
+        view.set_indentation(Indentation {
+            shift_width: 2,
+            expand_tab: true,
+            ..Indentation::default()
+        });
+        apply(&mut view, at(0, 0), Operator::ShiftLeft, Target::Motion(Motion::FileEnd), None);
+        assert_eq!(text_of(&view), "      x\n\ny\n");
+    }
+}
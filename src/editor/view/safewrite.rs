@@ -0,0 +1,173 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{Error, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+};
+
+// Names tried for the temporary file before giving up
+const TEMPORARY_NAMES: usize = 100;
+
+// Writes a file so that a crash or a full disk never leaves it half written:
+// the contents go to a temporary file next to it, which is synced to disk and
+// then renamed over the original. If the path is a symlink, the file it points
+// to is replaced and the link is kept. With backup set, the previous contents
+// are kept in a copy whose name ends in `~`.
+pub fn write_atomically(
+    path: &Path,
+    backup: bool,
+    write: impl FnOnce(&mut File) -> Result<(), Error>,
+) -> Result<(), Error> {
+    let target = resolve_symlinks(path);
+    let directory = match target.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let file_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .ok_or_else(|| Error::other(format!("Not a file name: {}", path.display())))?;
+    let original = fs::metadata(&target).ok();
+
+    if backup && original.is_some() {
+        let backup_path = directory.join(format!("{file_name}~"));
+        fs::copy(&target, &backup_path).map_err(|error| {
+            with_context(&error, &format!("Could not write backup {}", backup_path.display()))
+        })?;
+    }
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    // The temporary file is never readable by more users than the original
+    #[cfg(unix)]
+    if let Some(metadata) = &original {
+        use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+        options.mode(metadata.mode());
+    }
+    let (temporary, mut file) = create_temporary(&directory, &file_name, &options).map_err(|error| {
+        with_context(
+            &error,
+            &format!("Could not create a temporary file in {}", directory.display()),
+        )
+    })?;
+    if let Some(metadata) = &original {
+        copy_ownership(&temporary, metadata);
+    }
+    let result = write(&mut file)
+        .and_then(|()| file.sync_all())
+        .map_err(|error| with_context(&error, &format!("Could not write {}", path.display())))
+        .and_then(|()| {
+            fs::rename(&temporary, &target).map_err(|error| {
+                with_context(&error, &format!("Could not replace {}", path.display()))
+            })
+        });
+    if result.is_err() {
+        let _ = fs::remove_file(&temporary);
+        return result;
+    }
+    // Make the rename itself durable
+    if let Ok(directory) = File::open(&directory) {
+        let _ = directory.sync_all();
+    }
+    Ok(())
+}
+
+// Creates the temporary file, named after the file and this process. One of
+// that name left behind by a crashed editor whose pid was reused is stepped
+// around by adding a number.
+fn create_temporary(
+    directory: &Path,
+    file_name: &str,
+    options: &OpenOptions,
+) -> Result<(PathBuf, File), Error> {
+    let mut taken = Error::from(ErrorKind::AlreadyExists);
+    for attempt in 0..TEMPORARY_NAMES {
+        let number = if attempt == 0 { String::new() } else { format!(".{attempt}") };
+        let path = directory.join(format!(".{file_name}.{}{number}.tmp", process::id()));
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => taken = error,
+            Err(error) => return Err(error),
+        }
+    }
+    Err(taken)
+}
+
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+    // Bounded, so a symlink loop can't hang the editor
+    for _ in 0..32 {
+        let Ok(link) = fs::read_link(&path) else {
+            break;
+        };
+        path = match path.parent() {
+            Some(parent) if link.is_relative() => parent.join(link),
+            _ => link,
+        };
+    }
+    path
+}
+
+// Gives the new file, before anything is written to it, the owner (where
+// allowed) and mode bits of the old one
+fn copy_ownership(path: &Path, original: &fs::Metadata) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{MetadataExt, chown};
+        // Only root may give a file away, so failing here is expected and harmless
+        let _ = chown(path, Some(original.uid()), Some(original.gid()));
+    }
+    let _ = fs::set_permissions(path, original.permissions());
+}
+
+fn with_context(error: &Error, context: &str) -> Error {
+    Error::new(error.kind(), format!("{context}: {error}"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::{
+        env::temp_dir,
+        io::Write,
+        os::unix::fs::{PermissionsExt, symlink},
+    };
+
+    #[test]
+    fn test_write_atomically() {
+        let directory = temp_dir().join(format!("amoxcalli-safewrite-{}", process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let target = directory.join("file.txt");
+        let link = directory.join("link.txt");
+        fs::write(&target, "old").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        symlink("file.txt", &link).unwrap();
+
+        // The contents are private from the moment they are written
+        write_atomically(&link, true, |file| {
+            assert_eq!(file.metadata()?.permissions().mode() & 0o777, 0o600);
+            file.write_all(b"new")
+        })
+        .unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::read_to_string(directory.join("file.txt~")).unwrap(), "old");
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        let mode = fs::metadata(&target).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A failed write leaves the original alone
+        let result = write_atomically(&target, false, |_| Err(Error::other("disk full")));
+        assert!(result.unwrap_err().to_string().contains("disk full"));
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 3);
+
+        // A temporary file left behind under the same name is stepped around
+        let stale = directory.join(format!(".file.txt.{}.tmp", process::id()));
+        fs::write(&stale, "stale").unwrap();
+        write_atomically(&target, false, |file| file.write_all(b"newer")).unwrap();
+        assert_eq!(fs::read_to_string(&target).unwrap(), "newer");
+        assert_eq!(fs::read_to_string(&stale).unwrap(), "stale");
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 4);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
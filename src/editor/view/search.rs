@@ -0,0 +1,316 @@
+use super::{Annotation, AnnotationType, Line, Location, UIComponent, View};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SearchDirection {
+    Forward,
+    Backward,
+}
+
+#[derive(Clone)]
+pub struct Search {
+    pattern: String,
+    ignore_case: bool,
+    direction: SearchDirection,
+    // Cleared by :nohlsearch until the next search
+    highlight: bool,
+}
+
+// What to go back to if a search typed into the prompt is cancelled
+pub struct IncrementalSearch {
+    origin: Location,
+    previous: Option<Search>,
+}
+
+impl View {
+    pub fn start_search(&mut self) {
+        self.incremental_search = Some(IncrementalSearch {
+            origin: self.text_location,
+            previous: self.search.clone(),
+        });
+    }
+
+    // Moves the caret to the first match of the pattern as seen from where the
+    // search started. Returns false if there is none.
+    pub fn search(&mut self, pattern: &str, direction: SearchDirection, ignore_case: bool) -> bool {
+        let Some(origin) = self
+            .incremental_search
+            .as_ref()
+            .map(|incremental| incremental.origin)
+        else {
+            return false;
+        };
+        self.search = if pattern.is_empty() {
+            None
+        } else {
+            Some(Search {
+                pattern: pattern.to_string(),
+                ignore_case,
+                direction,
+                highlight: true,
+            })
+        };
+        let found = self.find_match(origin, false);
+        self.text_location = found.unwrap_or(origin);
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+        found.is_some()
+    }
+
+    // Accepts the search, returning where it started
+    pub fn end_search(&mut self) -> Option<Location> {
+        self.incremental_search.take().map(|incremental| incremental.origin)
+    }
+
+    pub fn cancel_search(&mut self) {
+        if let Some(IncrementalSearch { origin, previous }) = self.incremental_search.take() {
+            self.search = previous;
+            self.restore_location(origin);
+        }
+    }
+
+    pub fn clear_search_highlight(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.highlight = false;
+            self.set_requires_redraw(true);
+        }
+    }
+
+    // The last search pattern is shared by all windows
+    pub fn copy_search_from(&mut self, other: &Self) {
+        self.search.clone_from(&other.search);
+        self.set_requires_redraw(true);
+    }
+
+    pub fn search_pattern(&self) -> Option<&str> {
+        self.search.as_ref().map(|search| search.pattern.as_str())
+    }
+
+    // The direction in which the last search, n or N wrapped around the end of
+    // the buffer, if it did
+    pub const fn search_wrapped(&self) -> Option<SearchDirection> {
+        self.search_wrapped
+    }
+
+    // The target of n (or N, if reverse is set)
+    pub fn next_match(&mut self, reverse: bool, count: usize) -> Option<Location> {
+        let search = self.search.as_mut()?;
+        search.highlight = true;
+        self.set_requires_redraw(true);
+        let mut location = self.text_location;
+        let mut wrapped = None;
+        for _ in 0..count {
+            location = self.find_match(location, reverse)?;
+            wrapped = wrapped.or(self.search_wrapped);
+        }
+        self.search_wrapped = wrapped;
+        Some(location)
+    }
+
+    // Finds the nearest match after (or before) the location, wrapping around the
+    // end of the buffer
+    fn find_match(&mut self, from: Location, reverse: bool) -> Option<Location> {
+        let search = self.search.clone()?;
+        let forward = (search.direction == SearchDirection::Forward) != reverse;
+        let height = self.buffer.borrow().height();
+        self.search_wrapped = None;
+        let starts_on = |line: &Line| -> Vec<usize> {
+            line.find_all(&search.pattern, search.ignore_case)
+                .into_iter()
+                .map(|range| range.start)
+                .collect()
+        };
+        // Visit every line once, starting and ending with the line of the location
+        for step in 0..=height {
+            let line_index = if forward {
+                let line_index = from.line_index.saturating_add(step);
+                if line_index >= height {
+                    line_index.saturating_sub(height)
+                } else {
+                    line_index
+                }
+            } else if step <= from.line_index {
+                from.line_index.saturating_sub(step)
+            } else {
+                from.line_index.saturating_add(height).saturating_sub(step)
+            };
+            let Some(line) = self.buffer.borrow().line(line_index) else {
+                continue;
+            };
+            let starts = starts_on(&line);
+            let found = if step == 0 {
+                if forward {
+                    starts.into_iter().find(|&start| start > from.grapheme_index)
+                } else {
+                    starts.into_iter().rev().find(|&start| start < from.grapheme_index)
+                }
+            } else if step == height {
+                if forward {
+                    starts.into_iter().find(|&start| start <= from.grapheme_index)
+                } else {
+                    starts.into_iter().rev().find(|&start| start >= from.grapheme_index)
+                }
+            } else if forward {
+                starts.first().copied()
+            } else {
+                starts.last().copied()
+            };
+            if let Some(grapheme_index) = found {
+                let wrapped = if forward {
+                    line_index < from.line_index || step == height
+                } else {
+                    line_index > from.line_index || step == height
+                };
+                self.search_wrapped = wrapped.then_some(if forward {
+                    SearchDirection::Forward
+                } else {
+                    SearchDirection::Backward
+                });
+                return Some(Location {
+                    grapheme_index,
+                    line_index,
+                });
+            }
+        }
+        None
+    }
+
+    // Highlights for every match on the line; the one under the caret stands out
+    pub fn match_annotations(&self, line_index: usize, line: &Line) -> Vec<Annotation> {
+        let Some(search) = self.search.as_ref().filter(|search| search.highlight) else {
+            return Vec::new();
+        };
+        line.find_all(&search.pattern, search.ignore_case)
+            .into_iter()
+            .map(|range| {
+                let is_current = line_index == self.text_location.line_index
+                    && range.start == self.text_location.grapheme_index;
+                Annotation {
+                    kind: if is_current {
+                        AnnotationType::SelectedMatch
+                    } else {
+                        AnnotationType::Match
+                    },
+                    start: range.start,
+                    end: range.end,
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::{Buffer, ViewPosition};
+    use std::{cell::RefCell, rc::Rc};
+
+    fn view_of(text: &str) -> View {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(Location::default(), text);
+        let mut view = View::default();
+        view.show_buffer(Rc::new(RefCell::new(buffer)), ViewPosition::default());
+        view
+    }
+
+    const fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    // Where a search typed at the location lands, if it finds anything
+    fn search(
+        view: &mut View,
+        from: Location,
+        pattern: &str,
+        direction: SearchDirection,
+        ignore_case: bool,
+    ) -> Option<Location> {
+        view.text_location = from;
+        view.start_search();
+        let found = view.search(pattern, direction, ignore_case);
+        view.end_search();
+        found.then_some(view.text_location)
+    }
+
+    // Where n (or N) lands from the location
+    fn next(view: &mut View, from: Location, reverse: bool, count: usize) -> Option<Location> {
+        view.text_location = from;
+        view.next_match(reverse, count)
+    }
+
+    #[test]
+    fn test_find_matches() {
+        let mut view = view_of("foo bar foo\nbaz\nfoo 漢字foo e\u{301}foo");
+        // The match after the caret on its own line comes first
+        assert_eq!(search(&mut view, at(0, 0), "foo", SearchDirection::Forward, false), Some(at(0, 8)));
+        assert_eq!(view.search_wrapped(), None);
+        // Matches after wide and combining graphemes are at their grapheme
+        assert_eq!(next(&mut view, at(0, 8), false, 1), Some(at(2, 0)));
+        assert_eq!(next(&mut view, at(2, 0), false, 1), Some(at(2, 6)));
+        assert_eq!(next(&mut view, at(2, 6), false, 1), Some(at(2, 11)));
+        assert_eq!(view.search_wrapped(), None);
+        assert_eq!(next(&mut view, at(2, 11), false, 1), Some(at(0, 0)));
+        assert_eq!(view.search_wrapped(), Some(SearchDirection::Forward));
+        // N goes the other way, wrapping around the start
+        assert_eq!(next(&mut view, at(0, 0), true, 1), Some(at(2, 11)));
+        assert_eq!(view.search_wrapped(), Some(SearchDirection::Backward));
+        assert_eq!(next(&mut view, at(0, 8), true, 1), Some(at(0, 0)));
+        assert_eq!(view.search_wrapped(), None);
+
+        // A count takes that many matches, wrapping if need be
+        assert_eq!(next(&mut view, at(0, 0), false, 3), Some(at(2, 6)));
+        assert_eq!(view.search_wrapped(), None);
+        assert_eq!(next(&mut view, at(0, 8), false, 4), Some(at(0, 0)));
+        assert_eq!(view.search_wrapped(), Some(SearchDirection::Forward));
+
+        // A backward search finds the match before the caret on its line, and
+        // n keeps going backward
+        assert_eq!(search(&mut view, at(0, 8), "foo", SearchDirection::Backward, false), Some(at(0, 0)));
+        assert_eq!(next(&mut view, at(0, 0), false, 1), Some(at(2, 11)));
+        assert_eq!(next(&mut view, at(2, 11), true, 1), Some(at(0, 0)));
+        assert_eq!(view.search_wrapped(), Some(SearchDirection::Forward));
+
+        // Case folding, and a failed search leaving the caret where it was
+        assert_eq!(search(&mut view, at(0, 0), "FOO", SearchDirection::Forward, true), Some(at(0, 8)));
+        assert_eq!(search(&mut view, at(0, 0), "FOO", SearchDirection::Forward, false), None);
+        assert_eq!(view.text_location, at(0, 0));
+    }
+
+    #[test]
+    fn test_only_match_on_the_caret_line() {
+        let mut view = view_of("abc foo abc");
+        assert_eq!(search(&mut view, at(0, 4), "abc", SearchDirection::Forward, false), Some(at(0, 8)));
+        assert_eq!(view.search_wrapped(), None);
+        // Coming back around to the same line
+        assert_eq!(next(&mut view, at(0, 8), false, 1), Some(at(0, 0)));
+        assert_eq!(view.search_wrapped(), Some(SearchDirection::Forward));
+        assert_eq!(next(&mut view, at(0, 0), true, 1), Some(at(0, 8)));
+        assert_eq!(view.search_wrapped(), Some(SearchDirection::Backward));
+        // A match on the caret counts only once around
+        assert_eq!(search(&mut view, at(0, 4), "foo", SearchDirection::Forward, false), Some(at(0, 4)));
+        assert_eq!(view.search_wrapped(), Some(SearchDirection::Forward));
+    }
+
+    #[test]
+    fn test_match_annotations() {
+        let mut view = view_of("x\nfoo 漢字Foo e\u{301}foo");
+        search(&mut view, at(0, 0), "foo", SearchDirection::Forward, true);
+        assert_eq!(view.text_location, at(1, 0));
+        view.text_location = at(1, 6);
+        let line = view.buffer.borrow().line(1).unwrap();
+        let annotation = |kind, start, end| Annotation { kind, start, end };
+        assert_eq!(
+            view.match_annotations(1, &line),
+            [
+                annotation(AnnotationType::Match, 0, 3),
+                annotation(AnnotationType::SelectedMatch, 6, 9),
+                annotation(AnnotationType::Match, 11, 14),
+            ]
+        );
+        assert!(view.match_annotations(0, &line).iter().all(|annotation| annotation.kind == AnnotationType::Match));
+        view.clear_search_highlight();
+        assert!(view.match_annotations(1, &line).is_empty());
+    }
+}
@@ -0,0 +1,310 @@
+use super::{
+    Annotation, AnnotationType, Location, Operator, Register, TextRange, UIComponent, View,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SelectionKind {
+    Chars,
+    Lines,
+    Block,
+}
+
+// The selection runs from the anchor (where Visual mode started) to the caret
+#[derive(Clone, Copy)]
+pub struct Selection {
+    pub anchor: Location,
+    pub kind: SelectionKind,
+}
+
+// Text typed after changing a block is repeated on the block's other lines
+#[derive(Clone, Copy)]
+pub struct BlockInsert {
+    start: Location,
+    last_line: usize,
+    column: usize,
+}
+
+impl View {
+    pub fn start_selection(&mut self, kind: SelectionKind) {
+        self.selection = Some(Selection {
+            anchor: self.text_location,
+            kind,
+        });
+        self.set_requires_redraw(true);
+    }
+
+    pub fn set_selection_kind(&mut self, kind: SelectionKind) {
+        if let Some(selection) = &mut self.selection {
+            selection.kind = kind;
+            self.set_requires_redraw(true);
+        }
+    }
+
+    // Ends the selection, remembering its lines for '<,'>
+    pub fn end_selection(&mut self) {
+        if let Some(range) = self.selection_range() {
+            self.last_selected_lines = Some(Self::lines_of(range));
+        }
+        self.selection = None;
+        self.set_requires_redraw(true);
+    }
+
+    pub fn swap_selection_ends(&mut self) {
+        if let Some(selection) = &mut self.selection {
+            std::mem::swap(&mut selection.anchor, &mut self.text_location);
+            self.scroll_text_location_into_view();
+            self.set_requires_redraw(true);
+        }
+    }
+
+    // First and last line of the last (or current) selection
+    pub const fn last_selected_lines(&self) -> Option<(usize, usize)> {
+        self.last_selected_lines
+    }
+
+    pub fn apply_operator_to_selection(&mut self, operator: Operator) -> Option<Register> {
+        let range = self.selection_range()?;
+        self.end_selection();
+        let register = self.apply_operator_to_range(operator, range);
+        if operator == Operator::Change
+            && let TextRange::Block { first, last, left, .. } = range
+            && last > first
+        {
+            self.block_insert = Some(BlockInsert {
+                start: self.text_location,
+                last_line: last,
+                column: left,
+            });
+        }
+        Some(register)
+    }
+
+    // Repeats the text typed since a block change on the other lines of the block
+    pub fn finish_block_insert(&mut self) {
+        let Some(BlockInsert {
+            start,
+            last_line,
+            column,
+        }) = self.block_insert.take()
+        else {
+            return;
+        };
+        let caret = self.text_location;
+        if caret.line_index != start.line_index || caret.grapheme_index <= start.grapheme_index {
+            return;
+        }
+        let text = self.buffer.borrow().text_in(start, caret);
+        for line_index in start.line_index.saturating_add(1)..=last_line {
+            let Some(line) = self.buffer.borrow().line(line_index) else {
+                break;
+            };
+            if line.width() < column {
+                continue;
+            }
+            let grapheme_index = (0..=line.grapheme_count())
+                .find(|&index| line.width_until(index) >= column)
+                .unwrap_or_else(|| line.grapheme_count());
+            self.buffer.borrow_mut().insert_text(
+                Location {
+                    grapheme_index,
+                    line_index,
+                },
+                &text,
+            );
+        }
+        self.set_requires_redraw(true);
+    }
+
+    pub fn selection_range(&self) -> Option<TextRange> {
+        let Selection { anchor, kind } = self.selection?;
+        let caret = self.text_location;
+        let (start, end) = if (caret.line_index, caret.grapheme_index)
+            < (anchor.line_index, anchor.grapheme_index)
+        {
+            (caret, anchor)
+        } else {
+            (anchor, caret)
+        };
+        let range = match kind {
+            SelectionKind::Chars => TextRange::Chars {
+                start,
+                end: Location {
+                    grapheme_index: end
+                        .grapheme_index
+                        .saturating_add(1)
+                        .min(self.buffer.borrow().grapheme_count(end.line_index)),
+                    ..end
+                },
+            },
+            SelectionKind::Lines => TextRange::Lines {
+                first: start.line_index,
+                last: end.line_index,
+            },
+            SelectionKind::Block => {
+                // Columns are display columns, so the block lines up visually
+                // even across wide graphemes
+                let (anchor_left, anchor_right) = self.grapheme_columns(anchor);
+                let (caret_left, caret_right) = self.grapheme_columns(caret);
+                TextRange::Block {
+                    first: start.line_index,
+                    last: end.line_index,
+                    left: anchor_left.min(caret_left),
+                    right: anchor_right.max(caret_right),
+                }
+            }
+        };
+        Some(range)
+    }
+
+    // The display columns covered by the grapheme at the location
+    fn grapheme_columns(&self, location: Location) -> (usize, usize) {
+        self.buffer
+            .borrow()
+            .line(location.line_index)
+            .map_or((0, 1), |line| {
+                let left = line.width_until(location.grapheme_index);
+                let right = line
+                    .width_until(location.grapheme_index.saturating_add(1))
+                    .max(left.saturating_add(1));
+                (left, right)
+            })
+    }
+
+    // The graphemes of the line which overlap the display columns left..right
+    pub fn block_graphemes(&self, line_index: usize, left: usize, right: usize) -> (usize, usize) {
+        let Some(line) = self.buffer.borrow().line(line_index) else {
+            return (0, 0);
+        };
+        let count = line.grapheme_count();
+        let start = (0..count)
+            .find(|&index| line.width_until(index.saturating_add(1)) > left)
+            .unwrap_or(count);
+        let end = (start..count)
+            .find(|&index| line.width_until(index) >= right)
+            .unwrap_or(count);
+        (start, end)
+    }
+
+    pub const fn lines_of(range: TextRange) -> (usize, usize) {
+        match range {
+            TextRange::Chars { start, end } => (start.line_index, end.line_index),
+            TextRange::Lines { first, last } | TextRange::Block { first, last, .. } => {
+                (first, last)
+            }
+        }
+    }
+
+    pub fn selection_annotation(&self, line_index: usize) -> Option<Annotation> {
+        let range = self.selection_range()?;
+        let (first, last) = Self::lines_of(range);
+        if line_index < first || line_index > last {
+            return None;
+        }
+        let line_length = self.buffer.borrow().grapheme_count(line_index);
+        let (start, end) = match range {
+            TextRange::Chars { start, end } => (
+                if line_index == first {
+                    start.grapheme_index
+                } else {
+                    0
+                },
+                if line_index == last {
+                    end.grapheme_index
+                } else {
+                    line_length
+                },
+            ),
+            TextRange::Lines { .. } => (0, line_length),
+            TextRange::Block { left, right, .. } => self.block_graphemes(line_index, left, right),
+        };
+        Some(Annotation {
+            kind: AnnotationType::Selection,
+            start,
+            end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::{Buffer, RegisterKind, ViewPosition};
+    use std::{cell::RefCell, rc::Rc};
+
+    fn view_of(text: &str) -> View {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(Location::default(), text);
+        let mut view = View::default();
+        view.show_buffer(Rc::new(RefCell::new(buffer)), ViewPosition::default());
+        view
+    }
+
+    const fn at(line_index: usize, grapheme_index: usize) -> Location {
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+
+    fn select_block(view: &mut View, anchor: Location, caret: Location) {
+        view.text_location = anchor;
+        view.start_selection(SelectionKind::Block);
+        view.text_location = caret;
+    }
+
+    fn text_of(view: &View) -> String {
+        let buffer = view.buffer.borrow();
+        buffer.lines_text(0, buffer.height().saturating_sub(1))
+    }
+
+    #[test]
+    fn test_block_over_wide_graphemes() {
+        // Display columns: 漢 and 字 take 2-3 and 4-5, 😀 takes 1-2
+        let mut view = view_of("ab漢字cd\nabcdefgh\na😀bcdef\nx");
+        select_block(&mut view, at(0, 2), at(2, 2));
+        assert!(
+            view.selection_range()
+                == Some(TextRange::Block {
+                    first: 0,
+                    last: 2,
+                    left: 2,
+                    right: 4,
+                })
+        );
+        // Each line has the graphemes that overlap columns 2-3
+        let graphemes: Vec<Option<(usize, usize)>> = (0..4)
+            .map(|line_index| {
+                view.selection_annotation(line_index)
+                    .map(|annotation| (annotation.start, annotation.end))
+            })
+            .collect();
+        assert_eq!(graphemes, [Some((2, 3)), Some((2, 4)), Some((1, 3)), None]);
+
+        let register = view.apply_operator_to_selection(Operator::Yank).unwrap();
+        assert_eq!(register.text, "漢\ncd\n😀b");
+        assert_eq!(register.kind, RegisterKind::Blockwise);
+        assert!(view.selection_range().is_none());
+        assert_eq!(view.last_selected_lines(), Some((0, 2)));
+
+        // The other way around, from the bottom right
+        select_block(&mut view, at(2, 2), at(0, 2));
+        let register = view.apply_operator_to_selection(Operator::Delete).unwrap();
+        assert_eq!(register.text, "漢\ncd\n😀b");
+        assert_eq!(text_of(&view), "ab字cd\nabefgh\nacdef\nx\n");
+        assert_eq!(view.text_location, at(0, 2));
+    }
+
+    #[test]
+    fn test_block_insert() {
+        let mut view = view_of("ab漢字cd\nabcdefgh\na😀bcdef\nxyz");
+        // Column 6: c, g and e
+        select_block(&mut view, at(0, 4), at(2, 5));
+        view.apply_operator_to_selection(Operator::Change);
+        assert_eq!(text_of(&view), "ab漢字d\nabcdefh\na😀bcdf\nxyz\n");
+        assert_eq!(view.text_location, at(0, 4));
+        view.insert_char('X');
+        view.insert_char('漢');
+        view.finish_block_insert();
+        assert_eq!(text_of(&view), "ab漢字X漢d\nabcdefX漢h\na😀bcdX漢f\nxyz\n");
+    }
+}
@@ -0,0 +1,257 @@
+use std::ops::Range;
+
+use regex::{Captures, Regex};
+
+use super::{Annotation, AnnotationType, Location, UIComponent, View};
+
+// A :s command in progress. With the c flag it advances one confirmed match at a time.
+pub struct Substitution {
+    regex: Regex,
+    replacement: String,
+    global: bool,
+    // Where the next match is looked for
+    line_index: usize,
+    byte_offset: usize,
+    last_line: usize,
+    // The byte range of the match waiting to be replaced on line_index
+    current: Option<Range<usize>>,
+    substitutions: usize,
+    lines: usize,
+    last_changed_line: Option<usize>,
+}
+
+impl Substitution {
+    pub const fn new(regex: Regex, replacement: String, global: bool) -> Self {
+        Self {
+            regex,
+            replacement,
+            global,
+            line_index: 0,
+            byte_offset: 0,
+            last_line: 0,
+            current: None,
+            substitutions: 0,
+            lines: 0,
+            last_changed_line: None,
+        }
+    }
+}
+
+impl View {
+    // Starts substituting on the given lines, as one undoable change.
+    // Returns false if there is nothing to substitute.
+    pub fn start_substitution(&mut self, mut substitution: Substitution, first: usize, last: usize) -> bool {
+        substitution.line_index = first;
+        substitution.last_line = last;
+        self.buffer.borrow_mut().begin_change(self.text_location);
+        self.substitution = Some(substitution);
+        self.find_substitution_match()
+    }
+
+    // Replaces the current match (or skips it) and moves on to the next one.
+    // Returns false once there are no more matches.
+    pub fn confirm_substitution(&mut self, replace: bool) -> bool {
+        if replace {
+            self.replace_current_match();
+        } else {
+            self.skip_current_match();
+        }
+        self.find_substitution_match()
+    }
+
+    // Substitutes all the remaining matches without asking
+    pub fn substitute_all(&mut self) {
+        while self.confirm_substitution(true) {}
+    }
+
+    // Ends the substitution and returns how many substitutions were made on how many lines
+    pub fn finish_substitution(&mut self) -> (usize, usize) {
+        let Some(substitution) = self.substitution.take() else {
+            return (0, 0);
+        };
+        self.buffer.borrow_mut().end_change();
+        if let Some(line_index) = substitution.last_changed_line {
+            self.text_location = self.first_non_blank(line_index);
+        }
+        self.snap_to_valid_line();
+        self.snap_to_valid_grapheme();
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+        (substitution.substitutions, substitution.lines)
+    }
+
+    // Highlights the match waiting for confirmation
+    pub fn substitution_annotation(&self, line_index: usize) -> Option<Annotation> {
+        let substitution = self.substitution.as_ref()?;
+        let range = substitution.current.clone()?;
+        if substitution.line_index != line_index {
+            return None;
+        }
+        let line_str = self.buffer.borrow().line_str(line_index)?;
+        let line = self.buffer.borrow().line(line_index)?;
+        let grapheme_at = |byte: usize| {
+            line.grapheme_index(line_str.get(..byte).map_or(0, |text| text.chars().count()))
+        };
+        let start = grapheme_at(range.start);
+        Some(Annotation {
+            kind: AnnotationType::SelectedMatch,
+            start,
+            end: grapheme_at(range.end).max(start.saturating_add(1)),
+        })
+    }
+
+    fn find_substitution_match(&mut self) -> bool {
+        let Some(substitution) = self.substitution.as_mut() else {
+            return false;
+        };
+        substitution.current = None;
+        while substitution.line_index <= substitution.last_line {
+            let Some(line_str) = self.buffer.borrow().line_str(substitution.line_index) else {
+                break;
+            };
+            let found = if substitution.byte_offset <= line_str.len() {
+                substitution
+                    .regex
+                    .find_at(&line_str, substitution.byte_offset)
+                    .map(|found| found.range())
+            } else {
+                None
+            };
+            if let Some(range) = found {
+                let line_index = substitution.line_index;
+                let char_index = line_str.get(..range.start).map_or(0, |text| text.chars().count());
+                substitution.current = Some(range);
+                self.text_location = Location {
+                    grapheme_index: self
+                        .buffer
+                        .borrow()
+                        .line(line_index)
+                        .map_or(0, |line| line.grapheme_index(char_index)),
+                    line_index,
+                };
+                self.scroll_text_location_into_view();
+                self.set_requires_redraw(true);
+                return true;
+            }
+            substitution.line_index = substitution.line_index.saturating_add(1);
+            substitution.byte_offset = 0;
+        }
+        false
+    }
+
+    fn replace_current_match(&mut self) {
+        let Some(substitution) = self.substitution.as_mut() else {
+            return;
+        };
+        let Some(range) = substitution.current.take() else {
+            return;
+        };
+        let line_index = substitution.line_index;
+        let Some(line_str) = self.buffer.borrow().line_str(line_index) else {
+            return;
+        };
+        let Some(captures) = substitution.regex.captures_at(&line_str, range.start) else {
+            return;
+        };
+        let replacement = expand_replacement(&captures, &substitution.replacement);
+        let chars_until = |byte: usize| line_str.get(..byte).map_or(0, |text| text.chars().count());
+        self.buffer.borrow_mut().replace_in_line(
+            line_index,
+            chars_until(range.start)..chars_until(range.end),
+            &replacement,
+        );
+        substitution.substitutions = substitution.substitutions.saturating_add(1);
+        if substitution.last_changed_line != Some(line_index) {
+            substitution.lines = substitution.lines.saturating_add(1);
+        }
+
+        // Continue after the replacement, which may have split the line
+        let new_lines = replacement.matches('\n').count();
+        substitution.line_index = line_index.saturating_add(new_lines);
+        substitution.last_line = substitution.last_line.saturating_add(new_lines);
+        substitution.last_changed_line = Some(substitution.line_index);
+        substitution.byte_offset = match replacement.rfind('\n') {
+            Some(newline) => replacement.len().saturating_sub(newline.saturating_add(1)),
+            None => range.start.saturating_add(replacement.len()),
+        };
+        let matched_nothing = range.is_empty();
+        self.advance_substitution(matched_nothing);
+    }
+
+    fn skip_current_match(&mut self) {
+        let Some(substitution) = self.substitution.as_mut() else {
+            return;
+        };
+        let Some(range) = substitution.current.take() else {
+            return;
+        };
+        substitution.byte_offset = range.end;
+        self.advance_substitution(range.is_empty());
+    }
+
+    // Moves on to the next line unless the g flag is set. An empty match is
+    // stepped over so that it isn't found again.
+    fn advance_substitution(&mut self, matched_nothing: bool) {
+        let Some(substitution) = self.substitution.as_mut() else {
+            return;
+        };
+        if !substitution.global {
+            substitution.line_index = substitution.line_index.saturating_add(1);
+            substitution.byte_offset = 0;
+        } else if matched_nothing {
+            let next_char_length = self
+                .buffer
+                .borrow()
+                .line_str(substitution.line_index)
+                .and_then(|line_str| {
+                    line_str
+                        .get(substitution.byte_offset..)
+                        .and_then(|rest| rest.chars().next())
+                })
+                .map_or(1, char::len_utf8);
+            substitution.byte_offset = substitution.byte_offset.saturating_add(next_char_length);
+        }
+    }
+}
+
+// Expands the replacement of a :s command: `&` and `\0` stand for the whole match,
+// `\1` to `\9` for capture groups and `\n` (or `\r`) for a line break.
+// `\&` and `\\` stand for themselves.
+fn expand_replacement(captures: &Captures, replacement: &str) -> String {
+    let mut expanded = String::new();
+    let mut characters = replacement.chars();
+    while let Some(character) = characters.next() {
+        match character {
+            '&' => expanded.push_str(captures.get(0).map_or("", |found| found.as_str())),
+            '\\' => match characters.next() {
+                Some(digit @ '0'..='9') => {
+                    let group = digit
+                        .to_digit(10)
+                        .and_then(|group| usize::try_from(group).ok())
+                        .and_then(|group| captures.get(group));
+                    expanded.push_str(group.map_or("", |found| found.as_str()));
+                }
+                Some('n' | 'r') => expanded.push('\n'),
+                Some('t') => expanded.push('\t'),
+                Some(other) => expanded.push(other),
+                None => expanded.push('\\'),
+            },
+            _ => expanded.push(character),
+        }
+    }
+    expanded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_replacement() {
+        let regex = Regex::new(r"(\w+)@(\w+)").unwrap();
+        let captures = regex.captures("mail: me@host").unwrap();
+        assert_eq!(expand_replacement(&captures, r"\2 at \1"), "host at me");
+        assert_eq!(expand_replacement(&captures, r"<&> \& \\"), "<me@host> & \\");
+        assert_eq!(expand_replacement(&captures, r"\1\n\2"), "me\nhost");
+    }
+}
@@ -0,0 +1,279 @@
+use std::{
+    fs::{self, File},
+    io::{Error, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{Arc, Mutex, PoisonError, Weak},
+    thread,
+};
+
+use super::Operation;
+
+const HEADER: &str = "amoxcalli swap 1";
+// Journaled operations are written out at the latest after this many
+const FLUSH_AFTER_OPERATIONS: usize = 200;
+// The swap file names tried in turn when another running editor journals into
+// the first ones
+const EXTENSIONS: [&str; 4] = ["swp", "swo", "swn", "swm"];
+
+// Every open swap file, so the panic hook can flush them
+static JOURNALS: Mutex<Vec<Weak<Mutex<Journal>>>> = Mutex::new(Vec::new());
+
+struct Journal {
+    file: File,
+    pending: Vec<u8>,
+    pending_operations: usize,
+}
+
+impl Journal {
+    fn flush(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+        if self.file.write_all(&self.pending).is_ok() {
+            let _ = self.file.sync_data();
+        }
+        self.pending.clear();
+        self.pending_operations = 0;
+    }
+}
+
+// Journals the changes to a file's buffer into `.name.swp` next to it: a snapshot
+// of the text when the journal was started, followed by every operation since.
+// Replaying the journal restores unsaved changes after a crash. The swap file is
+// removed when the buffer is saved or closed, but kept if the editor panics.
+pub struct SwapFile {
+    path: PathBuf,
+    journal: Arc<Mutex<Journal>>,
+}
+
+// The contents of a swap file left behind
+pub struct Recovery {
+    pub base: String,
+    pub operations: Vec<Operation>,
+}
+
+impl SwapFile {
+    pub fn path_for(file: &Path) -> PathBuf {
+        Self::path_with(file, "swp")
+    }
+
+    fn path_with(file: &Path, extension: &str) -> PathBuf {
+        let name = file
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        file.with_file_name(format!(".{name}.{extension}"))
+    }
+
+    // Starts a journal in `.name.swp`, replacing one left behind by a crash.
+    // One that another running editor writes to is left alone, and the journal
+    // goes in `.name.swo` (or `.swn`, `.swm`) instead.
+    pub fn create(file: &Path, base: &str) -> Result<Self, Error> {
+        let path = EXTENSIONS
+            .iter()
+            .map(|extension| Self::path_with(file, extension))
+            .find(|path| Self::running_owner(path).is_none())
+            .ok_or_else(|| Error::other("Every swap file name is in use"))?;
+        let mut swap = File::create(&path)?;
+        write!(swap, "{HEADER}\npid {}\nB {}\n{base}\n", process::id(), base.len())?;
+        swap.sync_data()?;
+        let journal = Arc::new(Mutex::new(Journal {
+            file: swap,
+            pending: Vec::new(),
+            pending_operations: 0,
+        }));
+        let mut journals = JOURNALS.lock().unwrap_or_else(PoisonError::into_inner);
+        journals.retain(|journal| journal.strong_count() > 0);
+        journals.push(Arc::downgrade(&journal));
+        Ok(Self { path, journal })
+    }
+
+    pub fn record(&self, operation: &Operation) {
+        let (kind, at, text) = match operation {
+            Operation::Insert { at, text } => ('I', at, text),
+            Operation::Delete { at, text } => ('D', at, text),
+        };
+        let mut journal = self.journal.lock().unwrap_or_else(PoisonError::into_inner);
+        let record = format!("{kind} {at} {}\n{text}\n", text.len());
+        journal.pending.extend_from_slice(record.as_bytes());
+        journal.pending_operations = journal.pending_operations.saturating_add(1);
+        if journal.pending_operations >= FLUSH_AFTER_OPERATIONS {
+            journal.flush();
+        }
+    }
+
+    pub fn flush(&self) {
+        self.journal
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .flush();
+    }
+
+    // Writes out what every open swap file has journaled so far
+    pub fn flush_all() {
+        // try_lock, since a panic may have happened while the lock was held
+        let journals = match JOURNALS.try_lock() {
+            Ok(journals) => journals,
+            Err(std::sync::TryLockError::Poisoned(poisoned)) => poisoned.into_inner(),
+            Err(std::sync::TryLockError::WouldBlock) => return,
+        };
+        for journal in journals.iter().filter_map(Weak::upgrade) {
+            if let Ok(mut journal) = journal.try_lock() {
+                journal.flush();
+            }
+        }
+    }
+
+    // The process that wrote the swap file, if it is still running. Only Linux
+    // can tell, through /proc; elsewhere every swap file is taken to be left
+    // behind by a crash.
+    pub fn running_owner(path: &Path) -> Option<u32> {
+        let contents = fs::read(path).ok()?;
+        let pid: u32 = String::from_utf8_lossy(&contents)
+            .lines()
+            .nth(1)?
+            .strip_prefix("pid ")?
+            .parse()
+            .ok()?;
+        (pid != process::id() && is_running(pid)).then_some(pid)
+    }
+
+    // Reads back a swap file. A record cut short by a crash ends the journal.
+    pub fn read(path: &Path) -> Result<Recovery, Error> {
+        let contents = fs::read(path)?;
+        let mut reader = Reader {
+            contents: &contents,
+            position: 0,
+        };
+        let invalid = || Error::other(format!("Not a valid swap file: {}", path.display()));
+        if reader.line() != Some(HEADER) {
+            return Err(invalid());
+        }
+        reader.line();
+        let Some(('B', None, base)) = reader.record() else {
+            return Err(invalid());
+        };
+        let mut operations = Vec::new();
+        while let Some((kind, Some(at), text)) = reader.record() {
+            operations.push(match kind {
+                'I' => Operation::Insert { at, text },
+                'D' => Operation::Delete { at, text },
+                _ => break,
+            });
+        }
+        Ok(Recovery { base, operations })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn is_running(pid: u32) -> bool {
+    Path::new(&format!("/proc/{pid}")).exists()
+}
+
+#[cfg(not(target_os = "linux"))]
+const fn is_running(_pid: u32) -> bool {
+    false
+}
+
+impl Drop for SwapFile {
+    fn drop(&mut self) {
+        // After a panic, the swap file is all that is left of the unsaved changes
+        if thread::panicking() {
+            self.flush();
+        } else {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+struct Reader<'a> {
+    contents: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn line(&mut self) -> Option<&str> {
+        let rest = self.contents.get(self.position..)?;
+        let length = rest.iter().position(|&byte| byte == b'\n')?;
+        self.position = self.position.saturating_add(length).saturating_add(1);
+        std::str::from_utf8(rest.get(..length)?).ok()
+    }
+
+    // A record is a line like `I 42 5` (kind, char index, length in bytes),
+    // followed by that many bytes of text and a newline
+    fn record(&mut self) -> Option<(char, Option<usize>, String)> {
+        let line = self.line()?.to_string();
+        let mut fields = line.split(' ');
+        let kind = fields.next()?.chars().next()?;
+        let mut numbers: Vec<usize> = fields.map(str::parse).collect::<Result<_, _>>().ok()?;
+        let length = numbers.pop()?;
+        let at = numbers.pop();
+        let end = self.position.checked_add(length)?;
+        let text = std::str::from_utf8(self.contents.get(self.position..end)?).ok()?;
+        if self.contents.get(end) != Some(&b'\n') {
+            return None;
+        }
+        self.position = end.saturating_add(1);
+        Some((kind, at, text.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_journal_round_trip() {
+        let file = temp_dir().join(format!("amoxcalli-swap-{}.txt", process::id()));
+        let swap = SwapFile::create(&file, "one\ntwo").unwrap();
+        let operations = vec![
+            Operation::Insert {
+                at: 3,
+                text: "\n½".to_string(),
+            },
+            Operation::Delete {
+                at: 0,
+                text: "o".to_string(),
+            },
+        ];
+        for operation in &operations {
+            swap.record(operation);
+        }
+        SwapFile::flush_all();
+        let path = SwapFile::path_for(&file);
+        let recovery = SwapFile::read(&path).unwrap();
+        assert_eq!(recovery.base, "one\ntwo");
+        assert_eq!(recovery.operations, operations);
+        assert_eq!(SwapFile::running_owner(&path), None);
+
+        // A record cut short is ignored
+        let mut contents = fs::read(&path).unwrap();
+        contents.truncate(contents.len().saturating_sub(2));
+        fs::write(&path, contents).unwrap();
+        assert_eq!(SwapFile::read(&path).unwrap().operations.len(), 1);
+
+        drop(swap);
+        assert!(!path.exists());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_swap_file_in_use() {
+        let file = temp_dir().join(format!("amoxcalli-swap-in-use-{}.txt", process::id()));
+        // Process 1 is always running
+        let in_use = format!("{HEADER}\npid 1\nB 0\n\n");
+        let path = SwapFile::path_for(&file);
+        fs::write(&path, &in_use).unwrap();
+        assert_eq!(SwapFile::running_owner(&path), Some(1));
+
+        let swap = SwapFile::create(&file, "text").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), in_use);
+        let alternate = SwapFile::path_with(&file, "swo");
+        assert_eq!(SwapFile::read(&alternate).unwrap().base, "text");
+        drop(swap);
+        assert!(!alternate.exists());
+        fs::remove_file(&path).unwrap();
+    }
+}
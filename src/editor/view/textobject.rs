@@ -0,0 +1,645 @@
+use regex::Regex;
+use std::{cmp::Reverse, ops::Range, sync::LazyLock};
+
+use super::{
+    Buffer, CharClass, Location, Selection, SelectionKind, TextObject, TextObjectKind, TextRange,
+    UIComponent, View,
+};
+
+// An opening or closing tag: whether it closes, its name, and whether it
+// closes itself like <br/>
+const TAG_PATTERN: &str = r"<(/?)([A-Za-z][^\s/>]*)[^>]*?(/?)>";
+
+static TAG: LazyLock<Option<Regex>> = LazyLock::new(|| Regex::new(TAG_PATTERN).ok());
+
+// it and at look for tags at most this many lines above and below the caret
+const TAG_SCAN_LINES: usize = 1000;
+
+// Lines joined into one string, for the objects that may span lines
+struct JoinedLines {
+    text: String,
+    first_line: usize,
+    // Where each line starts in the text
+    starts: Vec<usize>,
+}
+
+impl JoinedLines {
+    fn new(buffer: &Buffer, lines: Range<usize>, separator: char) -> Self {
+        let first_line = lines.start;
+        let mut text = String::new();
+        let mut starts = Vec::new();
+        for line_index in lines {
+            if !starts.is_empty() {
+                text.push(separator);
+            }
+            starts.push(text.len());
+            text.push_str(&buffer.line_str(line_index).unwrap_or_default());
+        }
+        Self {
+            text,
+            first_line,
+            starts,
+        }
+    }
+
+    fn offset_of(&self, buffer: &Buffer, location: Location) -> usize {
+        let start = self
+            .starts
+            .get(location.line_index.saturating_sub(self.first_line))
+            .copied()
+            .unwrap_or(self.text.len());
+        let chars = buffer
+            .line(location.line_index)
+            .map_or(0, |line| line.char_index(location.grapheme_index));
+        self.text
+            .get(start..)
+            .and_then(|rest| rest.char_indices().nth(chars))
+            .map_or(self.text.len(), |(offset, _)| start.saturating_add(offset))
+    }
+
+    fn location_of(&self, buffer: &Buffer, offset: usize) -> Location {
+        let line = self
+            .starts
+            .partition_point(|start| *start <= offset)
+            .saturating_sub(1);
+        let start = self.starts.get(line).copied().unwrap_or(0);
+        let chars = self
+            .text
+            .get(start..offset)
+            .map_or(0, |text| text.chars().count());
+        let line_index = self.first_line.saturating_add(line);
+        Location {
+            grapheme_index: buffer
+                .line(line_index)
+                .map_or(0, |line| line.grapheme_index(chars)),
+            line_index,
+        }
+    }
+}
+
+// Splits text into sentences, each with the blanks after it. Like in Vim, a
+// sentence ends at '.', '!' or '?', then any of ')', ']', '"' and '\'', before a
+// blank or the end of the text.
+fn sentence_bounds(text: &str) -> Vec<(usize, &str)> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((_, character)) = chars.next() {
+        if !matches!(character, '.' | '!' | '?') {
+            continue;
+        }
+        while chars
+            .next_if(|(_, next)| matches!(next, ')' | ']' | '"' | '\''))
+            .is_some()
+        {}
+        if chars.peek().is_some_and(|(_, next)| !next.is_whitespace()) {
+            continue;
+        }
+        while chars.next_if(|(_, next)| next.is_whitespace()).is_some() {}
+        let end = chars.peek().map_or(text.len(), |(offset, _)| *offset);
+        sentences.push((start, text.get(start..end).unwrap_or_default()));
+        start = end;
+    }
+    if start < text.len() {
+        sentences.push((start, text.get(start..).unwrap_or_default()));
+    }
+    sentences
+}
+
+// The first `wanted` after (or before) `from` that isn't part of a pair nested
+// in between, whose other half is `nested`
+fn find_unmatched(
+    buffer: &Buffer,
+    from: Location,
+    wanted: &str,
+    nested: &str,
+    forward: bool,
+) -> Option<Location> {
+    let mut depth: usize = 0;
+    let mut line_index = from.line_index;
+    loop {
+        let graphemes: Vec<String> = buffer
+            .line(line_index)?
+            .graphemes()
+            .map(str::to_string)
+            .collect();
+        let indices: Vec<usize> = match (line_index == from.line_index, forward) {
+            (true, true) => (from.grapheme_index.saturating_add(1)..graphemes.len()).collect(),
+            (true, false) => (0..from.grapheme_index.min(graphemes.len())).rev().collect(),
+            (false, true) => (0..graphemes.len()).collect(),
+            (false, false) => (0..graphemes.len()).rev().collect(),
+        };
+        for grapheme_index in indices {
+            let grapheme = graphemes.get(grapheme_index).map(String::as_str);
+            if grapheme == Some(nested) {
+                depth = depth.saturating_add(1);
+            } else if grapheme == Some(wanted) {
+                if depth == 0 {
+                    return Some(Location {
+                        grapheme_index,
+                        line_index,
+                    });
+                }
+                depth = depth.saturating_sub(1);
+            }
+        }
+        line_index = if forward {
+            line_index.saturating_add(1)
+        } else {
+            line_index.checked_sub(1)?
+        };
+    }
+}
+
+impl View {
+    // The text an object covers at the caret: `count` words, sentences or
+    // paragraphs from it, or the `count`th pair or tag out from it
+    pub fn text_object(&self, object: TextObject, count: usize) -> Option<TextRange> {
+        let TextObject { kind, around } = object;
+        let count = count.max(1);
+        match kind {
+            TextObjectKind::Word | TextObjectKind::BigWord => {
+                self.word_object(kind == TextObjectKind::BigWord, around, count)
+            }
+            TextObjectKind::Sentence => self.sentence_object(around, count),
+            TextObjectKind::Paragraph => self.paragraph_object(around, count),
+            TextObjectKind::Quote(quote) => self.quote_object(quote, around),
+            TextObjectKind::Pair(open, close) => self.pair_object(open, close, around, count),
+            TextObjectKind::Tag => self.tag_object(around, count),
+        }
+    }
+
+    // Selects the object in Visual mode and returns the kind of selection that
+    // makes. Selecting the same object again selects the next one out.
+    pub fn select_object(&mut self, object: TextObject, count: Option<usize>) -> Option<SelectionKind> {
+        let count = count.unwrap_or(1);
+        let mut range = self.text_object(object, count);
+        if range.is_some() && range == self.selection_range() {
+            range = self.text_object(object, count.saturating_add(1));
+        }
+        let (anchor, caret, kind) = match range? {
+            TextRange::Chars { start, end } if start != end => {
+                let caret = if end.grapheme_index > 0 {
+                    Location {
+                        grapheme_index: end.grapheme_index.saturating_sub(1),
+                        ..end
+                    }
+                } else {
+                    let line_index = end.line_index.saturating_sub(1);
+                    Location {
+                        grapheme_index: self
+                            .buffer
+                            .borrow()
+                            .grapheme_count(line_index)
+                            .saturating_sub(1),
+                        line_index,
+                    }
+                };
+                (start, caret, SelectionKind::Chars)
+            }
+            TextRange::Lines { first, last } => (
+                Location {
+                    grapheme_index: 0,
+                    line_index: first,
+                },
+                Location {
+                    grapheme_index: 0,
+                    line_index: last,
+                },
+                SelectionKind::Lines,
+            ),
+            TextRange::Chars { .. } | TextRange::Block { .. } => return None,
+        };
+        self.selection = Some(Selection { anchor, kind });
+        self.text_location = caret;
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+        Some(kind)
+    }
+
+    // The words of a line as grapheme ranges, with their class. A run of
+    // blanks or of punctuation makes one word; for WORDs (`big`), so does a
+    // run of anything but blanks.
+    fn words(&self, line_index: usize, big: bool) -> Vec<(Range<usize>, CharClass)> {
+        let Some(line) = self.buffer.borrow().line(line_index) else {
+            return Vec::new();
+        };
+        let graphemes: Vec<&str> = line.graphemes().collect();
+        let mut words: Vec<(Range<usize>, CharClass)> = Vec::new();
+        for bound in line.word_bounds() {
+            let class = match graphemes.get(bound.start).map(|grapheme| CharClass::of(grapheme)) {
+                Some(CharClass::Blank) | None => CharClass::Blank,
+                Some(_) if big => CharClass::Word,
+                Some(class) => class,
+            };
+            match words.last_mut() {
+                Some((range, last)) if *last == class && (big || class != CharClass::Word) => {
+                    range.end = bound.end;
+                }
+                _ => words.push((bound, class)),
+            }
+        }
+        words
+    }
+
+    // iw and aw, iW and aW. `a` takes the blanks after each word too, or the
+    // word after blanks.
+    fn word_object(&self, big: bool, around: bool, count: usize) -> Option<TextRange> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let words = self.words(line_index, big);
+        let first = words
+            .iter()
+            .position(|(range, _)| range.contains(&grapheme_index))
+            .or_else(|| words.len().checked_sub(1))?;
+        let mut index = first;
+        let mut end = 0;
+        let mut trailing_blanks = false;
+        for _ in 0..count {
+            let (range, class) = words.get(index)?;
+            end = range.end;
+            index = index.saturating_add(1);
+            trailing_blanks = false;
+            if around
+                && let Some((next, next_class)) = words.get(index)
+                && (*class == CharClass::Blank) != (*next_class == CharClass::Blank)
+            {
+                end = next.end;
+                index = index.saturating_add(1);
+                trailing_blanks = *next_class == CharClass::Blank;
+            }
+        }
+        let (range, class) = words.get(first)?;
+        let mut start = range.start;
+        // aw on the last word of a line takes the blanks before it instead
+        if around
+            && !trailing_blanks
+            && *class != CharClass::Blank
+            && let Some((previous, CharClass::Blank)) =
+                first.checked_sub(1).and_then(|previous| words.get(previous))
+        {
+            start = previous.start;
+        }
+        Some(TextRange::Chars {
+            start: Location {
+                grapheme_index: start,
+                line_index,
+            },
+            end: Location {
+                grapheme_index: end,
+                line_index,
+            },
+        })
+    }
+
+    // The run of lines around the line that are all blank, or all not, as
+    // (blank, first, last)
+    fn line_run(&self, line_index: usize) -> Option<(bool, usize, usize)> {
+        let buffer = self.buffer.borrow();
+        let blank = |line_index| {
+            buffer
+                .line_str(line_index)
+                .map(|line_str| line_str.trim().is_empty())
+        };
+        let run_blank = blank(line_index)?;
+        let mut first = line_index;
+        while let Some(previous) = first.checked_sub(1)
+            && blank(previous) == Some(run_blank)
+        {
+            first = previous;
+        }
+        let mut last = line_index;
+        while blank(last.saturating_add(1)) == Some(run_blank) {
+            last = last.saturating_add(1);
+        }
+        Some((run_blank, first, last))
+    }
+
+    // is and as, within the caret's paragraph, where line breaks count as
+    // blanks. `a` takes the blanks after the sentences too.
+    fn sentence_object(&self, around: bool, count: usize) -> Option<TextRange> {
+        let (blank, first, last) = self.line_run(self.text_location.line_index)?;
+        if blank {
+            return None;
+        }
+        let buffer = self.buffer.borrow();
+        let joined = JoinedLines::new(&buffer, first..last.saturating_add(1), ' ');
+        let caret = joined.offset_of(&buffer, self.text_location);
+        let sentences = sentence_bounds(&joined.text);
+        let text_end = |index: usize| {
+            sentences.get(index).map(|(start, sentence)| {
+                start.saturating_add(sentence.trim_end().len())
+            })
+        };
+        let full_end = |index: usize| {
+            sentences
+                .get(index)
+                .map(|(start, sentence)| start.saturating_add(sentence.len()))
+        };
+        let index = sentences.iter().rposition(|(start, _)| *start <= caret)?;
+        let (start, end) = if caret >= text_end(index)? {
+            // On the blanks after a sentence: those, and with `a` the next sentence
+            let end = if around {
+                text_end(index.saturating_add(1))?
+            } else {
+                full_end(index)?
+            };
+            (text_end(index)?, end)
+        } else {
+            let last = index.saturating_add(count).saturating_sub(1);
+            let (last_text_end, last_full_end) = (text_end(last)?, full_end(last)?);
+            // as on the last sentence takes the blanks before it instead
+            let start = match index.checked_sub(1) {
+                Some(previous) if around && last_full_end == last_text_end => text_end(previous)?,
+                _ => sentences.get(index)?.0,
+            };
+            (start, if around { last_full_end } else { last_text_end })
+        };
+        Some(TextRange::Chars {
+            start: joined.location_of(&buffer, start),
+            end: joined.location_of(&buffer, end),
+        })
+    }
+
+    // ip and ap, linewise. Blank lines between paragraphs count as one too,
+    // and `a` takes those after the paragraph.
+    fn paragraph_object(&self, around: bool, count: usize) -> Option<TextRange> {
+        let (starts_blank, mut first, mut last) = self.line_run(self.text_location.line_index)?;
+        let runs = if around { count.saturating_mul(2) } else { count };
+        for taken in 1..runs {
+            match self.line_run(last.saturating_add(1)) {
+                Some((_, _, next_last)) => last = next_last,
+                // ap on the last paragraph takes the blank lines before it instead
+                None if around && !starts_blank && taken.saturating_add(1) == runs => {
+                    if let Some((true, previous_first, _)) =
+                        first.checked_sub(1).and_then(|previous| self.line_run(previous))
+                    {
+                        first = previous_first;
+                    }
+                }
+                None => return None,
+            }
+        }
+        Some(TextRange::Lines { first, last })
+    }
+
+    // i" and a", on the caret's line. Quotes pair up from the start of the
+    // line, and the first pair that ends at or after the caret is taken.
+    fn quote_object(&self, quote: char, around: bool) -> Option<TextRange> {
+        let Location {
+            grapheme_index,
+            line_index,
+        } = self.text_location;
+        let line = self.buffer.borrow().line(line_index)?;
+        let graphemes: Vec<&str> = line.graphemes().collect();
+        let quote = quote.to_string();
+        let quotes: Vec<usize> = graphemes
+            .iter()
+            .enumerate()
+            .filter(|&(index, grapheme)| {
+                // Escaped quotes don't count, but those after an escaped
+                // backslash do
+                let backslashes = graphemes.get(..index).map_or(0, |before| {
+                    before.iter().rev().take_while(|before| **before == "\\").count()
+                });
+                *grapheme == quote && backslashes.is_multiple_of(2)
+            })
+            .map(|(index, _)| index)
+            .collect();
+        let (open, close) = quotes
+            .chunks_exact(2)
+            .filter_map(|pair| match pair {
+                [open, close] => Some((*open, *close)),
+                _ => None,
+            })
+            .find(|(_, close)| *close >= grapheme_index)?;
+        let (mut start, mut end) = if around {
+            (open, close.saturating_add(1))
+        } else {
+            (open.saturating_add(1), close)
+        };
+        if around {
+            // With the blanks after the closing quote, or else those before the opening one
+            let blank = |index: usize| graphemes.get(index).is_some_and(|grapheme| grapheme.trim().is_empty());
+            let after = (end..graphemes.len()).take_while(|index| blank(*index)).count();
+            if after > 0 {
+                end = end.saturating_add(after);
+            } else {
+                while let Some(previous) = start.checked_sub(1)
+                    && blank(previous)
+                {
+                    start = previous;
+                }
+            }
+        }
+        Some(TextRange::Chars {
+            start: Location {
+                grapheme_index: start,
+                line_index,
+            },
+            end: Location {
+                grapheme_index: end,
+                line_index,
+            },
+        })
+    }
+
+    // i( and a(, and the other brackets, which may span lines
+    fn pair_object(&self, open: char, close: char, around: bool, count: usize) -> Option<TextRange> {
+        let (open, close) = (open.to_string(), close.to_string());
+        let buffer = self.buffer.borrow();
+        let caret = self.text_location;
+        let on_open = buffer
+            .line(caret.line_index)
+            .and_then(|line| line.graphemes().nth(caret.grapheme_index).map(|grapheme| grapheme == open))
+            .unwrap_or(false);
+        let mut start = if on_open {
+            caret
+        } else {
+            find_unmatched(&buffer, caret, &open, &close, false)?
+        };
+        for _ in 1..count {
+            start = find_unmatched(&buffer, start, &open, &close, false)?;
+        }
+        let end = find_unmatched(&buffer, start, &close, &open, true)?;
+        if around {
+            return Some(TextRange::Chars {
+                start,
+                end: Location {
+                    grapheme_index: end.grapheme_index.saturating_add(1),
+                    ..end
+                },
+            });
+        }
+        let inner_start = Location {
+            grapheme_index: start.grapheme_index.saturating_add(1),
+            ..start
+        };
+        // Brackets on lines of their own, as around a block of code, have the
+        // lines between them inside
+        let close_first = buffer.line(end.line_index).is_some_and(|line| {
+            line.graphemes()
+                .take(end.grapheme_index)
+                .all(|grapheme| grapheme.trim().is_empty())
+        });
+        if end.line_index > start.line_index.saturating_add(1)
+            && inner_start.grapheme_index >= buffer.grapheme_count(start.line_index)
+            && close_first
+        {
+            return Some(TextRange::Lines {
+                first: start.line_index.saturating_add(1),
+                last: end.line_index.saturating_sub(1),
+            });
+        }
+        Some(TextRange::Chars {
+            start: inner_start,
+            end,
+        })
+    }
+
+    // it and at: the contents of the `count`th element out from the caret,
+    // or all of it with its tags
+    fn tag_object(&self, around: bool, count: usize) -> Option<TextRange> {
+        let tag = TAG.as_ref()?;
+        let buffer = self.buffer.borrow();
+        let line_index = self.text_location.line_index;
+        let lines = line_index.saturating_sub(TAG_SCAN_LINES)
+            ..line_index
+                .saturating_add(TAG_SCAN_LINES)
+                .saturating_add(1)
+                .min(buffer.height());
+        let joined = JoinedLines::new(&buffer, lines, '\n');
+        let caret = joined.offset_of(&buffer, self.text_location);
+        let mut open: Vec<(&str, Range<usize>)> = Vec::new();
+        let mut elements: Vec<(Range<usize>, Range<usize>)> = Vec::new();
+        for captures in tag.captures_iter(&joined.text) {
+            let (Some(whole), Some(name)) = (captures.get(0), captures.get(2)) else {
+                continue;
+            };
+            let closing = captures.get(1).is_some_and(|slash| !slash.is_empty());
+            let self_closing = captures.get(3).is_some_and(|slash| !slash.is_empty());
+            if closing {
+                // A close tag ends the innermost element of its name, and any
+                // left open inside it
+                if let Some(position) = open
+                    .iter()
+                    .rposition(|(open_name, _)| *open_name == name.as_str())
+                    && let Some((_, open_range)) = open.drain(position..).next()
+                {
+                    elements.push((open_range, whole.range()));
+                }
+            } else if !self_closing {
+                open.push((name.as_str(), whole.range()));
+            }
+        }
+        let mut enclosing: Vec<(Range<usize>, Range<usize>)> = elements
+            .into_iter()
+            .filter(|(open_tag, close_tag)| open_tag.start <= caret && caret < close_tag.end)
+            .collect();
+        // The innermost element starts last
+        enclosing.sort_by_key(|(open_tag, _)| Reverse(open_tag.start));
+        let (open_tag, close_tag) = enclosing.get(count.saturating_sub(1))?;
+        let (start, end) = if around {
+            (open_tag.start, close_tag.end)
+        } else {
+            (open_tag.end, close_tag.start)
+        };
+        Some(TextRange::Chars {
+            start: joined.location_of(&buffer, start),
+            end: joined.location_of(&buffer, end),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::view::ViewPosition;
+    use std::{cell::RefCell, rc::Rc};
+
+    fn view_of(text: &str) -> View {
+        let mut buffer = Buffer::default();
+        buffer.insert_text(Location::default(), text);
+        let mut view = View::default();
+        view.show_buffer(Rc::new(RefCell::new(buffer)), ViewPosition::default());
+        view
+    }
+
+    // The text an object covers with the caret on the given line and grapheme
+    fn covered(view: &mut View, at: (usize, usize), object: &str, count: usize) -> Option<String> {
+        view.text_location = Location {
+            grapheme_index: at.1,
+            line_index: at.0,
+        };
+        let mut keys = object.chars();
+        let around = keys.next() == Some('a');
+        let kind = match keys.next()? {
+            'w' => TextObjectKind::Word,
+            'W' => TextObjectKind::BigWord,
+            's' => TextObjectKind::Sentence,
+            'p' => TextObjectKind::Paragraph,
+            't' => TextObjectKind::Tag,
+            '(' => TextObjectKind::Pair('(', ')'),
+            '{' => TextObjectKind::Pair('{', '}'),
+            quote => TextObjectKind::Quote(quote),
+        };
+        let buffer = view.buffer();
+        match view.text_object(TextObject { kind, around }, count)? {
+            TextRange::Chars { start, end } => Some(buffer.borrow().text_in(start, end)),
+            TextRange::Lines { first, last } => Some(buffer.borrow().lines_text(first, last)),
+            TextRange::Block { .. } => None,
+        }
+    }
+
+    #[test]
+    fn test_text_objects() {
+        // Words follow Unicode word boundaries
+        let mut view = view_of("café naïve  漢字, ok");
+        assert_eq!(covered(&mut view, (0, 2), "iw", 1).as_deref(), Some("café"));
+        assert_eq!(covered(&mut view, (0, 6), "aw", 1).as_deref(), Some("naïve  "));
+        assert_eq!(covered(&mut view, (0, 0), "iw", 3).as_deref(), Some("café naïve"));
+        assert_eq!(covered(&mut view, (0, 12), "iw", 1).as_deref(), Some("漢"));
+        assert_eq!(covered(&mut view, (0, 12), "iW", 1).as_deref(), Some("漢字,"));
+        assert_eq!(covered(&mut view, (0, 16), "aw", 1).as_deref(), Some(" ok"));
+
+        let mut view = view_of("say \"hi \\\"you\\\"\" or 'x'");
+        assert_eq!(covered(&mut view, (0, 0), "i\"", 1).as_deref(), Some("hi \\\"you\\\""));
+        assert_eq!(covered(&mut view, (0, 22), "a'", 1).as_deref(), Some(" 'x'"));
+        let mut view = view_of("\"a\\\\\" b \"c\"");
+        assert_eq!(covered(&mut view, (0, 1), "i\"", 1).as_deref(), Some("a\\\\"));
+        assert_eq!(covered(&mut view, (0, 8), "i\"", 1).as_deref(), Some("c"));
+
+        let mut view = view_of("f(a, (b), c)\nfn x() {\n    body\n}");
+        assert_eq!(covered(&mut view, (0, 2), "i(", 1).as_deref(), Some("a, (b), c"));
+        assert_eq!(covered(&mut view, (0, 6), "a(", 1).as_deref(), Some("(b)"));
+        assert_eq!(covered(&mut view, (0, 6), "i(", 2).as_deref(), Some("a, (b), c"));
+        assert_eq!(covered(&mut view, (2, 5), "i{", 1).as_deref(), Some("    body\n"));
+        assert_eq!(covered(&mut view, (2, 5), "a(", 1), None);
+
+        let mut view = view_of("<div><p>one <b>two</b><br/></p>\n</div>");
+        assert_eq!(covered(&mut view, (0, 16), "it", 1).as_deref(), Some("two"));
+        assert_eq!(covered(&mut view, (0, 16), "at", 1).as_deref(), Some("<b>two</b>"));
+        assert_eq!(covered(&mut view, (0, 16), "it", 2).as_deref(), Some("one <b>two</b><br/>"));
+        assert_eq!(
+            covered(&mut view, (0, 9), "it", 2).as_deref(),
+            Some("<p>one <b>two</b><br/></p>\n")
+        );
+
+        let mut view = view_of("One two. Three\nfour!  Five\n\n\nSix");
+        assert_eq!(covered(&mut view, (0, 10), "is", 1).as_deref(), Some("Three\nfour!"));
+        assert_eq!(covered(&mut view, (1, 2), "as", 1).as_deref(), Some("Three\nfour!  "));
+        assert_eq!(covered(&mut view, (1, 8), "as", 1).as_deref(), Some("  Five"));
+        assert_eq!(covered(&mut view, (0, 0), "ip", 1).as_deref(), Some("One two. Three\nfour!  Five\n"));
+        assert_eq!(covered(&mut view, (2, 0), "ip", 1).as_deref(), Some("\n\n"));
+        assert_eq!(covered(&mut view, (4, 0), "ap", 1).as_deref(), Some("\n\nSix\n"));
+
+        // Sentences may start in lowercase, and end before closing quotes and brackets
+        let mut view = view_of("one. two (\"yes.\") three.\nnext. e.g. this");
+        assert_eq!(covered(&mut view, (0, 0), "is", 1).as_deref(), Some("one."));
+        assert_eq!(covered(&mut view, (0, 5), "as", 1).as_deref(), Some("two (\"yes.\") "));
+        assert_eq!(covered(&mut view, (0, 21), "is", 2).as_deref(), Some("three.\nnext."));
+        assert_eq!(covered(&mut view, (1, 7), "is", 1).as_deref(), Some("e.g."));
+    }
+}
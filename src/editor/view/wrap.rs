@@ -0,0 +1,129 @@
+use std::ops::Range;
+
+use super::{Location, UIComponent, View};
+
+// How a window shows lines that are wider than it
+#[derive(Clone, Copy, Default, PartialEq, Eq)]
+pub struct LineWrap {
+    // Long lines continue on the next rows ('wrap') instead of scrolling sideways
+    pub wrap: bool,
+    // Wrapped rows end at word boundaries ('linebreak')
+    pub line_break: bool,
+}
+
+impl View {
+    pub(super) fn set_line_wrap(&mut self, line_wrap: LineWrap) {
+        if self.line_wrap == line_wrap {
+            return;
+        }
+        self.line_wrap = line_wrap;
+        self.scroll_offset.col = 0;
+        self.top_row = 0;
+        self.scroll_text_location_into_view();
+        self.set_requires_redraw(true);
+    }
+
+    // The graphemes on each screen row of a line
+    pub(super) fn rows_of(&self, line_index: usize) -> Vec<Range<usize>> {
+        self.buffer.borrow().line(line_index).map_or_else(
+            || vec![Range::default()],
+            |line| line.wrap(self.text_width(), self.line_wrap.line_break),
+        )
+    }
+
+    // The row of its line a location is on, and its column within that row
+    pub(super) fn wrapped_position(&self, location: Location) -> (usize, usize) {
+        let Some(line) = self.buffer.borrow().line(location.line_index) else {
+            return (0, 0);
+        };
+        let rows = line.wrap(self.text_width(), self.line_wrap.line_break);
+        let row = rows
+            .iter()
+            .rposition(|row| row.start <= location.grapheme_index)
+            .unwrap_or(0);
+        let start = rows.get(row).map_or(0, |row| row.start);
+        (row, line.width_between(start, location.grapheme_index))
+    }
+
+    // The number of screen rows from one (line, row) to a later one
+    pub(super) fn rows_between(&self, from: (usize, usize), to: (usize, usize)) -> usize {
+        (from.0..to.0)
+            .map(|line_index| self.rows_of(line_index).len())
+            .sum::<usize>()
+            .saturating_add(to.1)
+            .saturating_sub(from.1)
+    }
+
+    // The (line, row) that is `steps` screen rows above or below another,
+    // stopping at the start or end of the buffer
+    fn step_rows(&self, from: (usize, usize), steps: usize, up: bool) -> (usize, usize) {
+        let (mut line_index, mut row) = from;
+        let mut row_count = self.rows_of(line_index).len();
+        let line_count = self.buffer.borrow().height();
+        for _ in 0..steps {
+            if up && row > 0 {
+                row = row.saturating_sub(1);
+            } else if up && line_index > 0 {
+                line_index = line_index.saturating_sub(1);
+                row_count = self.rows_of(line_index).len();
+                row = row_count.saturating_sub(1);
+            } else if !up && row.saturating_add(1) < row_count {
+                row = row.saturating_add(1);
+            } else if !up && line_index.saturating_add(1) < line_count {
+                line_index = line_index.saturating_add(1);
+                row_count = self.rows_of(line_index).len();
+                row = 0;
+            } else {
+                break;
+            }
+        }
+        (line_index, row)
+    }
+
+    // Scrolls so that the caret's row, and 'scrolloff' rows around it, are on
+    // screen, counting wrapped rows
+    pub(super) fn scroll_wrapped_into_view(&mut self) {
+        let (row, _) = self.wrapped_position(self.text_location);
+        let caret = (self.text_location.line_index, row);
+        let top = (self.scroll_offset.row, self.top_row);
+        let margin = self.scroll_margin();
+        let first = self.step_rows(caret, margin, true);
+        let new_top = if first < top {
+            first
+        } else {
+            // The top that puts the last row to show at the bottom, if the
+            // current one is above it
+            let last = self.step_rows(caret, margin, false);
+            top.max(self.step_rows(last, self.size.height.saturating_sub(1), true))
+        };
+        if new_top != top {
+            (self.scroll_offset.row, self.top_row) = new_top;
+            self.set_requires_redraw(true);
+        }
+    }
+
+    // Where moving `steps` rows down (or up) on screen takes the caret, keeping
+    // its column within the row where possible (gj and gk)
+    pub(super) fn display_row_target(&self, steps: usize, up: bool) -> Location {
+        let (row, column) = self.wrapped_position(self.text_location);
+        let (line_index, row) = self.step_rows((self.text_location.line_index, row), steps, up);
+        let rows = self.rows_of(line_index);
+        let range = rows.get(row).cloned().unwrap_or_default();
+        let is_last_row = row.saturating_add(1) >= rows.len();
+        let grapheme_index = self.buffer.borrow().line(line_index).map_or(0, |line| {
+            let past_column = range
+                .clone()
+                .find(|&index| line.width_between(range.start, index.saturating_add(1)) > column);
+            // Only the last row may leave the caret after its last grapheme
+            past_column.unwrap_or(if is_last_row || range.is_empty() {
+                range.end
+            } else {
+                range.end.saturating_sub(1)
+            })
+        });
+        Location {
+            grapheme_index,
+            line_index,
+        }
+    }
+}
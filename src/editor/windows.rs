@@ -0,0 +1,676 @@
+use std::mem;
+
+use super::{
+    Position, Size, Terminal, UIComponent, command::Direction, statusbar::StatusBar, theme::Scope,
+    view::View,
+};
+
+// A window needs a line of text and its status line
+const MIN_HEIGHT: usize = 2;
+const MIN_WIDTH: usize = 1;
+
+// The part of the screen a window covers, including its status line
+#[derive(Clone, Copy, Default)]
+pub struct Rect {
+    pub origin: Position,
+    pub size: Size,
+}
+
+impl Rect {
+    const fn contains(&self, position: Position) -> bool {
+        position.row >= self.origin.row
+            && position.row < self.origin.row.saturating_add(self.size.height)
+            && position.col >= self.origin.col
+            && position.col < self.origin.col.saturating_add(self.size.width)
+    }
+
+    // The size of the text area, leaving out the status line
+    pub const fn text_size(&self) -> Size {
+        Size {
+            height: self.size.height.saturating_sub(1),
+            width: self.size.width,
+        }
+    }
+
+    const fn status_origin(&self) -> Position {
+        Position {
+            row: self.origin.row.saturating_add(self.size.height).saturating_sub(1),
+            col: self.origin.col,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Window {
+    id: usize,
+    pub view: View,
+    pub status_bar: StatusBar,
+    rect: Rect,
+}
+
+impl Window {
+    fn render(terminal: &mut dyn Terminal, view: &mut View, status_bar: &mut StatusBar, rect: Rect) {
+        view.render(terminal, rect.origin);
+        status_bar.render(terminal, rect.status_origin());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SplitDirection {
+    Horizontal, // Windows above each other
+    Vertical,   // Windows side by side, with a separator column between them
+}
+
+impl SplitDirection {
+    const fn of(vertical: bool) -> Self {
+        if vertical { Self::Vertical } else { Self::Horizontal }
+    }
+
+    const fn extent(self, size: Size) -> usize {
+        match self {
+            Self::Horizontal => size.height,
+            Self::Vertical => size.width,
+        }
+    }
+
+    const fn min_size(self) -> usize {
+        match self {
+            Self::Horizontal => MIN_HEIGHT,
+            Self::Vertical => MIN_WIDTH,
+        }
+    }
+
+    const fn separator(self) -> usize {
+        match self {
+            Self::Horizontal => 0,
+            Self::Vertical => 1,
+        }
+    }
+}
+
+// A window, or windows split in one direction. Sizes are in rows or columns.
+enum Node {
+    Window(Box<Window>),
+    Split {
+        direction: SplitDirection,
+        children: Vec<Child>,
+    },
+}
+
+struct Child {
+    node: Node,
+    size: usize,
+}
+
+impl Node {
+    fn contains(&self, id: usize) -> bool {
+        match self {
+            Self::Window(window) => window.id == id,
+            Self::Split { children, .. } => children.iter().any(|child| child.node.contains(id)),
+        }
+    }
+
+    fn first_id(&self) -> usize {
+        match self {
+            Self::Window(window) => window.id,
+            Self::Split { children, .. } => children.first().map_or(0, |child| child.node.first_id()),
+        }
+    }
+
+    fn windows<'a>(&'a self, windows: &mut Vec<&'a Window>) {
+        match self {
+            Self::Window(window) => windows.push(window),
+            Self::Split { children, .. } => {
+                for child in children {
+                    child.node.windows(windows);
+                }
+            }
+        }
+    }
+
+    fn windows_mut<'a>(&'a mut self, windows: &mut Vec<&'a mut Window>) {
+        match self {
+            Self::Window(window) => windows.push(window),
+            Self::Split { children, .. } => {
+                for child in children {
+                    child.node.windows_mut(windows);
+                }
+            }
+        }
+    }
+
+    fn into_window(self, id: usize) -> Option<Box<Window>> {
+        match self {
+            Self::Window(window) => (window.id == id).then_some(window),
+            Self::Split { children, .. } => children
+                .into_iter()
+                .find_map(|child| child.node.into_window(id)),
+        }
+    }
+
+    // Lays out the windows in the rect. Sizes that no longer add up, e.g. after
+    // the terminal was resized, are scaled to fit.
+    fn arrange(&mut self, rect: Rect, separators: &mut Vec<Rect>) {
+        let (direction, children) = match self {
+            Self::Window(window) => {
+                window.rect = rect;
+                window.view.resize(rect.text_size());
+                window.status_bar.resize(Size {
+                    height: 1,
+                    width: rect.size.width,
+                });
+                return;
+            }
+            Self::Split {
+                direction,
+                children,
+            } => (*direction, children),
+        };
+        let separators_size = direction.separator().saturating_mul(children.len().saturating_sub(1));
+        let available = direction.extent(rect.size).saturating_sub(separators_size);
+        fit_sizes(children, available);
+        let mut offset = 0;
+        let count = children.len();
+        for (index, child) in children.iter_mut().enumerate() {
+            let mut child_rect = rect;
+            match direction {
+                SplitDirection::Horizontal => {
+                    child_rect.origin.row = rect.origin.row.saturating_add(offset);
+                    child_rect.size.height = child.size;
+                }
+                SplitDirection::Vertical => {
+                    child_rect.origin.col = rect.origin.col.saturating_add(offset);
+                    child_rect.size.width = child.size;
+                }
+            }
+            child.node.arrange(child_rect, separators);
+            offset = offset.saturating_add(child.size);
+            if direction == SplitDirection::Vertical && index.saturating_add(1) < count {
+                separators.push(Rect {
+                    origin: Position {
+                        row: rect.origin.row,
+                        col: rect.origin.col.saturating_add(offset),
+                    },
+                    size: Size {
+                        height: rect.size.height,
+                        width: 1,
+                    },
+                });
+                offset = offset.saturating_add(1);
+            }
+        }
+    }
+
+    // Splits the window in two, with the new window above or to the left
+    fn split(&mut self, id: usize, direction: SplitDirection, new: &mut Option<Box<Window>>) -> bool {
+        match self {
+            Self::Window(window) if window.id == id => {
+                let extent = direction.extent(window.rect.size);
+                let (first, second) = halve(extent.saturating_sub(direction.separator()));
+                let old = mem::replace(
+                    self,
+                    Self::Split {
+                        direction,
+                        children: Vec::new(),
+                    },
+                );
+                if let (Self::Split { children, .. }, Some(new)) = (self, new.take()) {
+                    children.push(Child {
+                        node: Self::Window(new),
+                        size: first,
+                    });
+                    children.push(Child {
+                        node: old,
+                        size: second,
+                    });
+                }
+                true
+            }
+            Self::Window(_) => false,
+            Self::Split {
+                direction: split_direction,
+                children,
+            } => {
+                // A window split in the direction of its parent just gets a new sibling
+                let sibling = children
+                    .iter()
+                    .position(|child| matches!(&child.node, Self::Window(window) if window.id == id));
+                if *split_direction == direction
+                    && let Some(index) = sibling
+                    && let Some(new) = new.take()
+                {
+                    let size = children.get(index).map_or(0, |child| child.size);
+                    let (first, second) = halve(size.saturating_sub(direction.separator()));
+                    if let Some(child) = children.get_mut(index) {
+                        child.size = second;
+                    }
+                    children.insert(
+                        index,
+                        Child {
+                            node: Self::Window(new),
+                            size: first,
+                        },
+                    );
+                    return true;
+                }
+                children
+                    .iter_mut()
+                    .any(|child| child.node.split(id, direction, new))
+            }
+        }
+    }
+
+    // Removes a window and gives its space to a neighbour. Returns the window
+    // that should become active.
+    fn remove(&mut self, id: usize) -> Option<usize> {
+        let Self::Split {
+            direction,
+            children,
+        } = self
+        else {
+            return None;
+        };
+        let Some(index) = children
+            .iter()
+            .position(|child| matches!(&child.node, Self::Window(window) if window.id == id))
+        else {
+            return children.iter_mut().find_map(|child| child.node.remove(id));
+        };
+        let removed = children.remove(index);
+        let neighbour = children.get_mut(index.saturating_sub(1))?;
+        neighbour.size = neighbour
+            .size
+            .saturating_add(removed.size)
+            .saturating_add(direction.separator());
+        let next = neighbour.node.first_id();
+        if children.len() == 1
+            && let Some(only) = children.pop()
+        {
+            *self = only.node;
+        }
+        Some(next)
+    }
+
+    // Changes the size of the window within the innermost split in the given
+    // direction, taking the space from (or giving it to) a sibling
+    fn resize(
+        &mut self,
+        id: usize,
+        direction: SplitDirection,
+        size: &dyn Fn(usize) -> usize,
+    ) -> bool {
+        let Self::Split {
+            direction: split_direction,
+            children,
+        } = self
+        else {
+            return false;
+        };
+        let Some(index) = children.iter().position(|child| child.node.contains(id)) else {
+            return false;
+        };
+        if children
+            .get_mut(index)
+            .is_some_and(|child| child.node.resize(id, direction, size))
+        {
+            return true;
+        }
+        if *split_direction != direction {
+            return false;
+        }
+        let sibling = if index.saturating_add(1) < children.len() {
+            index.saturating_add(1)
+        } else if index > 0 {
+            index.saturating_sub(1)
+        } else {
+            return false;
+        };
+        let current = children.get(index).map_or(0, |child| child.size);
+        let total = current.saturating_add(children.get(sibling).map_or(0, |child| child.size));
+        let min = direction.min_size();
+        let new_size = size(current).clamp(min, total.saturating_sub(min).max(min));
+        if let Some(child) = children.get_mut(index) {
+            child.size = new_size;
+        }
+        if let Some(child) = children.get_mut(sibling) {
+            child.size = total.saturating_sub(new_size);
+        }
+        true
+    }
+
+    fn equalize(&mut self) {
+        if let Self::Split { children, .. } = self {
+            for child in children {
+                child.size = 1;
+                child.node.equalize();
+            }
+        }
+    }
+}
+
+// Scales the sizes so that they add up to the space available
+fn fit_sizes(children: &mut [Child], available: usize) {
+    let total: usize = children.iter().map(|child| child.size).sum();
+    if total == available {
+        return;
+    }
+    let mut remaining = available;
+    let count = children.len();
+    for (index, child) in children.iter_mut().enumerate() {
+        child.size = if index.saturating_add(1) == count {
+            remaining
+        } else {
+            child
+                .size
+                .saturating_mul(available)
+                .checked_div(total)
+                .unwrap_or(0)
+                .max(1)
+                .min(remaining)
+        };
+        remaining = remaining.saturating_sub(child.size);
+    }
+}
+
+// Splits a size in two, the first half being the smaller one
+fn halve(size: usize) -> (usize, usize) {
+    let first = size.checked_div(2).unwrap_or(0);
+    (first, size.saturating_sub(first))
+}
+
+// The windows on screen, laid out as a tree of horizontal and vertical splits.
+// The view and status bar of the active window are lent to the editor, so its
+// window in the tree holds placeholders until another window becomes active.
+pub struct Windows {
+    root: Node,
+    active: usize,
+    last_id: usize,
+    area: Rect,
+    separators: Vec<Rect>,
+    requires_redraw: bool,
+}
+
+impl Default for Windows {
+    fn default() -> Self {
+        Self {
+            root: Node::Window(Box::default()),
+            active: 0,
+            last_id: 0,
+            area: Rect::default(),
+            separators: Vec::new(),
+            requires_redraw: true,
+        }
+    }
+}
+
+impl Windows {
+    pub const fn active(&self) -> usize {
+        self.active
+    }
+
+    pub fn count(&self) -> usize {
+        let mut windows = Vec::new();
+        self.root.windows(&mut windows);
+        windows.len()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Window> {
+        let mut windows = Vec::new();
+        self.root.windows(&mut windows);
+        windows.into_iter().find(|window| window.id == id)
+    }
+
+    fn get_mut(&mut self, id: usize) -> Option<&mut Window> {
+        self.windows_mut().into_iter().find(|window| window.id == id)
+    }
+
+    pub fn active_rect(&self) -> Rect {
+        self.get(self.active).map(|window| window.rect).unwrap_or_default()
+    }
+
+    // Every window but the active one
+    pub fn inactive_mut(&mut self) -> Vec<&mut Window> {
+        let active = self.active;
+        let mut windows = self.windows_mut();
+        windows.retain(|window| window.id != active);
+        windows
+    }
+
+    fn windows_mut(&mut self) -> Vec<&mut Window> {
+        let mut windows = Vec::new();
+        self.root.windows_mut(&mut windows);
+        windows
+    }
+
+    pub const fn set_area(&mut self, area: Rect) {
+        self.area = area;
+    }
+
+    // Lays out the windows again. The editor has to resize the active view itself.
+    pub fn arrange(&mut self) {
+        self.separators.clear();
+        self.root.arrange(self.area, &mut self.separators);
+        self.requires_redraw = true;
+    }
+
+    // Adds a window showing the view above (or left of) the active one and
+    // returns its id
+    pub fn split(&mut self, vertical: bool, view: View) -> Result<usize, String> {
+        let direction = SplitDirection::of(vertical);
+        let extent = direction.extent(self.active_rect().size);
+        let needed = direction
+            .min_size()
+            .saturating_mul(2)
+            .saturating_add(direction.separator());
+        if extent < needed {
+            return Err("Not enough room".to_string());
+        }
+        self.last_id = self.last_id.saturating_add(1);
+        let mut new = Some(Box::new(Window {
+            id: self.last_id,
+            view,
+            ..Window::default()
+        }));
+        self.root.split(self.active, direction, &mut new);
+        Ok(self.last_id)
+    }
+
+    // Closes the active window and returns the one that should become active,
+    // or None if it is the last window
+    pub fn close(&mut self) -> Option<usize> {
+        self.root.remove(self.active)
+    }
+
+    // Closes every window but the active one
+    pub fn only(&mut self) {
+        let root = mem::replace(&mut self.root, Node::Window(Box::default()));
+        if let Some(window) = root.into_window(self.active) {
+            self.root = Node::Window(window);
+        }
+    }
+
+    // Makes another window active: the editor's view and status bar go back to
+    // the active window, and those of the other window are handed out
+    pub fn focus(&mut self, id: usize, view: &mut View, status_bar: &mut StatusBar) {
+        if id == self.active {
+            return;
+        }
+        let Some(window) = self.get_mut(id) else {
+            return;
+        };
+        let new_view = mem::take(&mut window.view);
+        let new_status_bar = mem::take(&mut window.status_bar);
+        let old_view = mem::replace(view, new_view);
+        let old_status_bar = mem::replace(status_bar, new_status_bar);
+        // The old window may just have been closed
+        if let Some(window) = self.get_mut(self.active) {
+            window.view = old_view;
+            window.status_bar = old_status_bar;
+        }
+        self.active = id;
+    }
+
+    // The window next to the active one, seen from the caret
+    pub fn neighbour(&self, direction: Direction, caret: Position) -> Option<usize> {
+        let rect = self.active_rect();
+        let target = match direction {
+            Direction::Left => Position {
+                col: rect.origin.col.checked_sub(2)?,
+                ..caret
+            },
+            Direction::Right => Position {
+                col: rect.origin.col.saturating_add(rect.size.width).saturating_add(1),
+                ..caret
+            },
+            Direction::Up => Position {
+                row: rect.origin.row.checked_sub(1)?,
+                ..caret
+            },
+            Direction::Down => Position {
+                row: rect.origin.row.saturating_add(rect.size.height),
+                ..caret
+            },
+        };
+        let mut windows = Vec::new();
+        self.root.windows(&mut windows);
+        windows
+            .into_iter()
+            .find(|window| window.rect.contains(target))
+            .map(|window| window.id)
+    }
+
+    // The window after (or before) the active one, wrapping around
+    pub fn next(&self, backwards: bool) -> usize {
+        let mut windows = Vec::new();
+        self.root.windows(&mut windows);
+        let ids: Vec<usize> = windows.iter().map(|window| window.id).collect();
+        let index = ids.iter().position(|&id| id == self.active).unwrap_or(0);
+        let next = if backwards {
+            index.checked_sub(1).unwrap_or(ids.len().saturating_sub(1))
+        } else if index.saturating_add(1) >= ids.len() {
+            0
+        } else {
+            index.saturating_add(1)
+        };
+        ids.get(next).copied().unwrap_or(self.active)
+    }
+
+    // Resizes the active window by the amount, or to the given size (as large as
+    // possible if there is none). Returns false if there is nothing to resize.
+    pub fn resize(&mut self, vertical: bool, amount: isize) -> bool {
+        self.root.resize(self.active, SplitDirection::of(vertical), &|size| {
+            size.saturating_add_signed(amount)
+        })
+    }
+
+    pub fn set_size(&mut self, vertical: bool, size: Option<usize>) -> bool {
+        // A height counts the text lines, without the status line
+        let size = if vertical {
+            size
+        } else {
+            size.map(|size| size.saturating_add(1))
+        };
+        self.root.resize(self.active, SplitDirection::of(vertical), &|_| {
+            size.unwrap_or(usize::MAX)
+        })
+    }
+
+    pub fn equalize(&mut self) {
+        self.root.equalize();
+    }
+
+    // Draws the windows (the active one with the editor's view and status bar)
+    // and the separators between them
+    pub fn render(&mut self, terminal: &mut dyn Terminal, view: &mut View, status_bar: &mut StatusBar) {
+        for window in self.inactive_mut() {
+            window.status_bar.update_status(window.view.get_status());
+            Window::render(terminal, &mut window.view, &mut window.status_bar, window.rect);
+        }
+        Window::render(terminal, view, status_bar, self.active_rect());
+        if self.requires_redraw {
+            for separator in &self.separators {
+                for row in 0..separator.size.height {
+                    let origin = Position {
+                        row: separator.origin.row.saturating_add(row),
+                        col: separator.origin.col,
+                    };
+                    let _ = terminal.print_styled_at(origin, 1, "|", Scope::Border);
+                }
+            }
+            self.requires_redraw = false;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn layout(windows: &Windows) -> Vec<(usize, usize, usize, usize, usize)> {
+        let mut list = Vec::new();
+        windows.root.windows(&mut list);
+        list.iter()
+            .map(|window| {
+                let Rect { origin, size } = window.rect;
+                (window.id, origin.row, origin.col, size.height, size.width)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_split_and_close() {
+        let mut windows = Windows::default();
+        windows.set_area(Rect {
+            origin: Position::default(),
+            size: Size {
+                height: 20,
+                width: 81,
+            },
+        });
+        windows.arrange();
+        let left = windows.split(true, View::default()).unwrap();
+        windows.arrange();
+        assert_eq!(layout(&windows), vec![(1, 0, 0, 20, 40), (0, 0, 41, 20, 40)]);
+
+        // Splitting the right window above each other
+        let top = windows.split(false, View::default()).unwrap();
+        windows.arrange();
+        assert_eq!(
+            layout(&windows),
+            vec![(1, 0, 0, 20, 40), (2, 0, 41, 10, 40), (0, 10, 41, 10, 40)]
+        );
+        assert_eq!(top, 2);
+
+        let caret = Position { row: 15, col: 50 };
+        assert_eq!(windows.neighbour(Direction::Left, caret), Some(left));
+        assert_eq!(windows.neighbour(Direction::Up, caret), Some(top));
+        assert_eq!(windows.neighbour(Direction::Down, caret), None);
+
+        assert!(windows.resize(false, 3));
+        windows.arrange();
+        assert_eq!(
+            layout(&windows),
+            vec![(1, 0, 0, 20, 40), (2, 0, 41, 7, 40), (0, 7, 41, 13, 40)]
+        );
+
+        // The window above gets the space of the closed one
+        assert_eq!(windows.close(), Some(top));
+        windows.active = top;
+        windows.arrange();
+        assert_eq!(layout(&windows), vec![(1, 0, 0, 20, 40), (2, 0, 41, 20, 40)]);
+
+        // Terminal resizes scale the windows
+        windows.set_area(Rect {
+            origin: Position::default(),
+            size: Size {
+                height: 10,
+                width: 41,
+            },
+        });
+        windows.arrange();
+        assert_eq!(layout(&windows), vec![(1, 0, 0, 10, 20), (2, 0, 21, 10, 20)]);
+        windows.only();
+        windows.arrange();
+        assert_eq!(layout(&windows), vec![(2, 0, 0, 10, 41)]);
+    }
+}